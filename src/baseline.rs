@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::validator::ValidationResult;
+
+/// Violations accepted when adopting a policy, so that only new violations fail a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub violations: BTreeSet<BaselineEntry>,
+}
+
+/// An accepted unauthorized change. Array indices in `path` are stored as `*`, so the
+/// entry still matches after elements are inserted or removed before it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub rule_id: String,
+    pub file_path: String,
+    /// JSON Pointer of the change, empty when the violation concerns the whole file
+    pub path: String,
+}
+
+impl BaselineEntry {
+    fn new(rule_id: &str, file_path: &str, path: &str) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            file_path: file_path.to_string(),
+            path: normalize_indices(path),
+        }
+    }
+}
+
+impl Baseline {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read baseline file: {:?}", path))?;
+
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse baseline file: {:?}", path))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+
+        fs::write(path, content + "\n")
+            .with_context(|| format!("Failed to write baseline file: {:?}", path))
+    }
+
+    /// Record every unauthorized change of a run
+    pub fn from_result(result: &ValidationResult) -> Self {
+        let violations = result
+            .violations
+            .iter()
+            .flat_map(|violation| {
+                violation.unauthorized_changes.iter().map(|change| {
+                    BaselineEntry::new(&violation.rule_id, &violation.file_path, &change.path)
+                })
+            })
+            .collect();
+
+        Self { violations }
+    }
+
+    /// Remove the unauthorized changes recorded in the baseline from a result, dropping
+    /// violations left without changes. Returns the number of changes suppressed.
+    pub fn apply(&self, result: &mut ValidationResult) -> usize {
+        let mut suppressed = 0;
+
+        for violation in &mut result.violations {
+            let before = violation.unauthorized_changes.len();
+            violation.unauthorized_changes.retain(|change| {
+                !self.violations.contains(&BaselineEntry::new(
+                    &violation.rule_id,
+                    &violation.file_path,
+                    &change.path,
+                ))
+            });
+            suppressed += before - violation.unauthorized_changes.len();
+        }
+
+        result
+            .violations
+            .retain(|violation| !violation.unauthorized_changes.is_empty());
+        result.recount();
+
+        suppressed
+    }
+}
+
+/// Replace array indices in a JSON Pointer with `*`
+fn normalize_indices(path: &str) -> String {
+    path.split('/')
+        .enumerate()
+        .map(|(i, segment)| {
+            if i > 0 && !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                "*"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Severity;
+    use crate::validator::{UnauthorizedChange, Violation};
+
+    fn violation(rule_id: &str, file_path: &str, paths: &[&str]) -> Violation {
+        Violation {
+            file_path: file_path.to_string(),
+            rule_id: rule_id.to_string(),
+            rule_name: None,
+            severity: Severity::Error,
+            rule_description: String::new(),
+            rule_source: None,
+            unauthorized_changes: paths
+                .iter()
+                .map(|path| UnauthorizedChange {
+                    path: path.to_string(),
+                    old: None,
+                    new: None,
+                    reason: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn result(violations: Vec<Violation>) -> ValidationResult {
+        let mut result = ValidationResult {
+            is_valid: false,
+            violations,
+            files_processed: 1,
+            files_parsed: 1,
+            files_matched: 1,
+            matched_files: Vec::new(),
+            error_count: 0,
+            warning_count: 0,
+            info_count: 0,
+            rule_stats: Default::default(),
+            omitted_violations: 0,
+            truncated: false,
+            stats: Default::default(),
+            explanations: Vec::new(),
+        };
+        result.recount();
+        result
+    }
+
+    #[test]
+    fn test_normalize_indices() {
+        assert_eq!(
+            normalize_indices("/spec/generators/0/values/12"),
+            "/spec/generators/*/values/*"
+        );
+        assert_eq!(normalize_indices("/spec/v1/x"), "/spec/v1/x");
+        assert_eq!(normalize_indices(""), "");
+    }
+
+    #[test]
+    fn test_only_new_violations_remain() {
+        let baseline = Baseline::from_result(&result(vec![violation(
+            "app",
+            "deploy.yaml",
+            &["/spec/generators/0/image", "/spec/replicas"],
+        )]));
+        assert_eq!(baseline.violations.len(), 2);
+
+        // The same changes after a generator was inserted ahead of the known one
+        let mut current = result(vec![
+            violation(
+                "app",
+                "deploy.yaml",
+                &[
+                    "/spec/generators/1/image",
+                    "/spec/replicas",
+                    "/metadata/name",
+                ],
+            ),
+            violation("app", "other.yaml", &["/spec/replicas"]),
+            violation("other", "deploy.yaml", &["/spec/replicas"]),
+        ]);
+
+        assert_eq!(baseline.apply(&mut current), 2);
+        assert_eq!(current.violations.len(), 3);
+        assert_eq!(current.violations[0].unauthorized_changes.len(), 1);
+        assert_eq!(
+            current.violations[0].unauthorized_changes[0].path,
+            "/metadata/name"
+        );
+        assert_eq!(current.error_count, 3);
+
+        // A fully baselined run is valid
+        let mut known = result(vec![violation("app", "deploy.yaml", &["/spec/replicas"])]);
+        assert_eq!(baseline.apply(&mut known), 1);
+        assert!(known.violations.is_empty());
+        assert!(known.is_valid);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let baseline = Baseline::from_result(&result(vec![violation("app", "a.yaml", &[""])]));
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        baseline.write(file.path()).unwrap();
+        assert_eq!(Baseline::from_file(file.path()).unwrap(), baseline);
+    }
+}
@@ -1,39 +1,1327 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use crate::error::JiffsError;
+use crate::glob::{Glob, GlobSet};
+use crate::json_path::{ChangeKind, ChangePattern, IndexRange};
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+/// A jiffs policy file
 pub struct Config {
+    /// Policy files whose rules are loaded ahead of this file's own rules,
+    /// relative to the directory of the including file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+    /// Named condition lists that rules of this file reference with `matchRef` and
+    /// `whenRef`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub definitions: BTreeMap<String, Vec<PathValue>>,
+    /// Settings applied to every rule of this file that does not set them itself
+    #[serde(default, skip_serializing_if = "RuleDefaults::is_empty")]
+    pub defaults: RuleDefaults,
+    /// Rules checked against every changed file, in order
     pub rules: Vec<Rule>,
 }
 
+/// The `defaults` of a policy file. Each given setting replaces the built-in default of
+/// the same rule field.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(
+        rename = "allowNewFiles",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_new_files: Option<bool>,
+    #[serde(
+        rename = "allowDeletion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_deletion: Option<bool>,
+}
+
+impl RuleDefaults {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rule {
-    #[serde(rename = "match")]
+    /// Stable id reported with violations, derived from the whole rule when omitted
+    pub id: Option<String>,
+    /// Human-readable name used in violation messages
+    pub name: Option<String>,
+    /// Replaces the generated description of the rule's violations. `{path}`, `{old}`
+    /// and `{new}` are filled in from the first offending change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Globs over repository-relative paths; when given, the rule only applies to
+    /// matching files and `match` may be omitted
+    #[serde(default, skip_serializing_if = "GlobSet::is_empty")]
+    pub files: GlobSet,
+    /// Globs over branch names; when given, the rule only applies on a matching branch
+    #[serde(default, skip_serializing_if = "GlobSet::is_empty")]
+    pub branches: GlobSet,
+    /// Conditions a file must meet for the rule to apply
+    #[serde(rename = "match", default)]
     pub match_conditions: Vec<PathValue>,
+    /// Name of a `definitions` entry whose conditions are added to `match` on load
+    #[serde(rename = "matchRef", skip_serializing_if = "Option::is_none")]
+    pub match_ref: Option<String>,
+    /// Patterns of paths that may change; every path may change when omitted
     #[serde(rename = "allowedChanges")]
-    pub allowed_changes: Vec<String>,
+    pub allowed_changes: Option<Vec<AllowedChange>>,
+    /// Patterns of paths that must never change, nor any path above or below them
+    #[serde(rename = "forbiddenChanges", default)]
+    pub forbidden_changes: Vec<String>,
+    /// Patterns each of which some change must match
+    #[serde(rename = "requiredChanges", default)]
+    pub required_changes: Vec<String>,
+    /// JSON pointers to paths that may be removed. When given, removals anywhere else
+    /// are unauthorized, even at paths `allowedChanges` allows.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removals: Vec<String>,
+    /// JSON pointers to paths that must keep their value in modified files. Changing,
+    /// adding or removing them, or an object containing them, is always unauthorized.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub immutable: Vec<String>,
+    /// Conditions that must hold for allowed changes to be accepted
     #[serde(rename = "when")]
     pub when_conditions: Option<Vec<PathValue>>,
+    /// Name of a `definitions` entry whose conditions are added to `when` on load
+    #[serde(rename = "whenRef", skip_serializing_if = "Option::is_none")]
+    pub when_ref: Option<String>,
+    /// Conditions under which allowed changes are rejected after all: a change is
+    /// unauthorized when every one of them holds, aligned with the change like `when`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unless: Vec<PathValue>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Allow any content in new files matching the rule
+    #[serde(rename = "allowNewFiles", default = "default_true")]
+    pub allow_new_files: bool,
+    #[serde(rename = "versionPolicy")]
+    pub version_policy: Option<VersionPolicy>,
+    /// Skip the remaining rules once this one matches
+    #[serde(rename = "stopOnMatch", default)]
+    pub stop_on_match: bool,
+    /// Allow deleting files matching the rule
+    #[serde(rename = "allowDeletion", default)]
+    pub allow_deletion: bool,
+    /// Whether matching files may be renamed; their content is validated either way
+    #[serde(rename = "allowRenames", default = "default_true")]
+    pub allow_renames: bool,
+    /// Severity of deleting a matching file, defaulting to the rule's severity
+    #[serde(rename = "deletionSeverity")]
+    pub deletion_severity: Option<Severity>,
+    /// Whether allowed patterns match only changed leaves or whole subtrees
+    #[serde(default)]
+    pub granularity: Granularity,
+    /// Arrays, by pattern, whose elements are paired by the value of a field rather
+    /// than by position when diffing
+    #[serde(
+        rename = "arrayKeys",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub array_keys: BTreeMap<String, String>,
+    /// Policy file the rule was loaded from
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Line of the policy file where the rule starts, counting from 1
+    #[serde(skip)]
+    pub source_line: Option<usize>,
+}
+
+/// An `allowedChanges` entry: a pattern, written as a plain string to allow every kind
+/// of change, or as `{path, ops, allowedValues}` to allow only some
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawAllowedChange", into = "RawAllowedChange")]
+pub struct AllowedChange {
+    pub path: String,
+    /// Kinds of change allowed, never empty
+    pub ops: Vec<ChangeOp>,
+    /// Values the path may be changed to; any value when `None`
+    pub allowed_values: Option<Vec<AllowedValue>>,
+}
+
+/// A value an `allowedChanges` entry accepts: an exact value, or `{glob: ...}` or
+/// `{regex: ...}` over strings. Patterns are compiled when the policy is loaded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "RawAllowedValue")]
+pub enum AllowedValue {
+    Exact(serde_json::Value),
+    Glob(Glob),
+    Regex(regex::Regex),
+}
+
+impl AllowedValue {
+    /// Whether `value` is this value, or a string the glob or regex matches
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            AllowedValue::Exact(exact) => exact == value,
+            AllowedValue::Glob(glob) => value.as_str().is_some_and(|s| glob.is_match(s)),
+            AllowedValue::Regex(regex) => value.as_str().is_some_and(|s| regex.is_match(s)),
+        }
+    }
+}
+
+impl PartialEq for AllowedValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AllowedValue::Exact(a), AllowedValue::Exact(b)) => a == b,
+            (AllowedValue::Glob(a), AllowedValue::Glob(b)) => a.as_str() == b.as_str(),
+            (AllowedValue::Regex(a), AllowedValue::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<RawAllowedValue> for AllowedValue {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawAllowedValue) -> Result<Self> {
+        Ok(match raw {
+            RawAllowedValue::Glob { glob } => AllowedValue::Glob(Glob::new(&glob)?),
+            RawAllowedValue::Regex { regex } => AllowedValue::Regex(regex::Regex::new(&regex)?),
+            RawAllowedValue::Exact(value) => AllowedValue::Exact(value),
+        })
+    }
+}
+
+impl fmt::Display for AllowedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowedValue::Exact(value) => write!(f, "{}", value),
+            AllowedValue::Glob(glob) => write!(f, "glob {:?}", glob.as_str()),
+            AllowedValue::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+/// How an [`AllowedValue`] is written in a policy
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", schemars(rename = "AllowedValue"))]
+enum RawAllowedValue {
+    /// A glob, like `files`, over string values
+    Glob { glob: String },
+    /// A regular expression over string values
+    Regex { regex: String },
+    /// The exact value
+    Exact(serde_json::Value),
+}
+
+impl fmt::Display for RawAllowedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawAllowedValue::Glob { glob } => write!(f, "glob {:?}", glob),
+            RawAllowedValue::Regex { regex } => write!(f, "/{}/", regex),
+            RawAllowedValue::Exact(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<AllowedValue> for RawAllowedValue {
+    fn from(value: AllowedValue) -> Self {
+        match value {
+            AllowedValue::Exact(value) => RawAllowedValue::Exact(value),
+            AllowedValue::Glob(glob) => RawAllowedValue::Glob {
+                glob: glob.as_str().to_string(),
+            },
+            AllowedValue::Regex(regex) => RawAllowedValue::Regex {
+                regex: regex.as_str().to_string(),
+            },
+        }
+    }
+}
+
+/// A kind of change an `allowedChanges` entry may be restricted to
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    /// A value appears at a path that had none
+    Add,
+    /// The value at a path is replaced
+    Modify,
+    /// The value at a path is removed
+    Remove,
+}
+
+impl ChangeOp {
+    pub const ALL: [ChangeOp; 3] = [ChangeOp::Add, ChangeOp::Modify, ChangeOp::Remove];
+
+    fn of(kind: ChangeKind) -> Self {
+        match kind {
+            ChangeKind::Addition => ChangeOp::Add,
+            ChangeKind::Replacement => ChangeOp::Modify,
+            ChangeKind::Removal => ChangeOp::Remove,
+        }
+    }
+}
+
+impl fmt::Display for ChangeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeOp::Add => write!(f, "add"),
+            ChangeOp::Modify => write!(f, "modify"),
+            ChangeOp::Remove => write!(f, "remove"),
+        }
+    }
+}
+
+impl AllowedChange {
+    /// Whether the entry allows every kind of change
+    pub fn allows_all_ops(&self) -> bool {
+        ChangeOp::ALL.iter().all(|op| self.ops.contains(op))
+    }
+
+    /// Whether the entry allows every kind of change to any value, as a plain string does
+    pub fn allows_anything(&self) -> bool {
+        self.allows_all_ops() && self.allowed_values.is_none()
+    }
+}
+
+impl From<&str> for AllowedChange {
+    fn from(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            ops: ChangeOp::ALL.to_vec(),
+            allowed_values: None,
+        }
+    }
+}
+
+impl AsRef<str> for AllowedChange {
+    fn as_ref(&self) -> &str {
+        &self.path
+    }
+}
+
+impl ChangePattern for AllowedChange {
+    fn covers(&self, kind: ChangeKind) -> bool {
+        self.ops.contains(&ChangeOp::of(kind))
+    }
+
+    fn accepts(&self, value: &serde_json::Value) -> bool {
+        self.allowed_values
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|allowed| allowed.matches(value)))
+    }
+}
+
+impl PartialEq<&str> for AllowedChange {
+    fn eq(&self, other: &&str) -> bool {
+        self.allows_anything() && self.path == *other
+    }
+}
+
+impl fmt::Display for AllowedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.allows_anything() {
+            return write!(f, "{}", self.path);
+        }
+
+        let mut restrictions = Vec::new();
+        if !self.allows_all_ops() {
+            let ops = self.ops.iter().map(ToString::to_string).collect::<Vec<_>>();
+            restrictions.push(ops.join(", "));
+        }
+        if let Some(values) = &self.allowed_values {
+            let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+            restrictions.push(format!("values: {}", values.join(", ")));
+        }
+        write!(f, "{} ({})", self.path, restrictions.join("; "))
+    }
+}
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawAllowedChange {
+    /// A pattern allowing every kind of change
+    Path(String),
+    /// A pattern allowing only some kinds of change, or only some values
+    WithOps {
+        path: String,
+        /// Kinds of change allowed, all three when omitted
+        #[serde(default = "all_ops")]
+        ops: Vec<ChangeOp>,
+        /// Values the path may be changed to
+        #[serde(
+            rename = "allowedValues",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        allowed_values: Option<Vec<RawAllowedValue>>,
+    },
+}
+
+fn all_ops() -> Vec<ChangeOp> {
+    ChangeOp::ALL.to_vec()
+}
+
+impl TryFrom<RawAllowedChange> for AllowedChange {
+    type Error = String;
+
+    fn try_from(raw: RawAllowedChange) -> Result<Self, String> {
+        match raw {
+            RawAllowedChange::Path(path) => Ok(Self::from(path.as_str())),
+            RawAllowedChange::WithOps {
+                path,
+                mut ops,
+                allowed_values,
+            } => {
+                ops.sort();
+                ops.dedup();
+                let allowed_values = allowed_values
+                    .map(|values| {
+                        values
+                            .into_iter()
+                            .map(|value| {
+                                let text = value.to_string();
+                                AllowedValue::try_from(value).map_err(|err| {
+                                    format!(
+                                        "Invalid allowedValues entry {} for '{}': {:#}",
+                                        text, path, err
+                                    )
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .transpose()?;
+
+                Ok(Self {
+                    path,
+                    ops,
+                    allowed_values,
+                })
+            }
+        }
+    }
 }
 
+impl From<AllowedChange> for RawAllowedChange {
+    fn from(allowed: AllowedChange) -> Self {
+        if allowed.allows_anything() {
+            RawAllowedChange::Path(allowed.path)
+        } else {
+            RawAllowedChange::WithOps {
+                path: allowed.path,
+                ops: allowed.ops,
+                allowed_values: allowed
+                    .allowed_values
+                    .map(|values| values.into_iter().map(RawAllowedValue::from).collect()),
+            }
+        }
+    }
+}
+
+/// Paths holding semantic versions that may only move forward
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionPolicy {
+    pub paths: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How serious a violation of a rule is. Ordered from least to most severe.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    #[default]
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// How allowed change patterns are matched against changed paths
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// A pattern must match the changed leaf itself
+    #[default]
+    Leaf,
+    /// A pattern also authorizes every change nested below the paths it matches
+    Subtree,
+}
+
+/// A condition on the value at a path of a document
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PathValue {
+    /// JSON Pointer of the value to compare, `*` and `**` allowed
     pub path: String,
+    /// Expected value, which may be omitted when `length` is given
+    #[serde(default)]
     pub value: serde_json::Value,
+    #[serde(default)]
+    pub op: Operator,
+    /// Invert the condition, so it holds when no value at the path compares to the
+    /// expected value, including when the path is absent
+    #[serde(default)]
+    pub negate: bool,
+    /// How the condition evaluates when its path does not resolve
+    #[serde(rename = "onMissing", default)]
+    pub on_missing: OnMissing,
+    /// Compare the number of elements of the array or object at the path instead of
+    /// the value itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<LengthBounds>,
+    /// Whether any or every value a wildcard path resolves to must compare to the
+    /// expected value
+    #[serde(
+        rename = "matchMode",
+        default,
+        skip_serializing_if = "MatchMode::is_any"
+    )]
+    pub match_mode: MatchMode,
+    /// Only test whether the path resolves (`true`) or not (`false`), to any value. A
+    /// wildcard path resolves when it matches at least one value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
+}
+
+/// How the values a wildcard path resolves to combine into one outcome
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Some value must compare to the expected value
+    #[default]
+    Any,
+    /// Every value must compare to the expected value
+    All,
+}
+
+impl MatchMode {
+    fn is_any(&self) -> bool {
+        *self == MatchMode::Any
+    }
+}
+
+/// Bounds on the number of elements of an array or object, e.g. `{ge: 1}`. Every
+/// given bound must hold.
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LengthBounds {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ne: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ge: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub le: Option<u64>,
+}
+
+impl LengthBounds {
+    /// The given bounds as operator and expected length pairs
+    pub fn bounds(&self) -> Vec<(Operator, u64)> {
+        [
+            (Operator::Eq, self.eq),
+            (Operator::Ne, self.ne),
+            (Operator::Gt, self.gt),
+            (Operator::Ge, self.ge),
+            (Operator::Lt, self.lt),
+            (Operator::Le, self.le),
+        ]
+        .into_iter()
+        .filter_map(|(op, bound)| bound.map(|bound| (op, bound)))
+        .collect()
+    }
+}
+
+impl fmt::Display for LengthBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds = self
+            .bounds()
+            .iter()
+            .map(|(op, bound)| format!("{} {}", op, bound))
+            .collect::<Vec<_>>()
+            .join(" and ");
+        write!(f, "length {}", bounds)
+    }
+}
+
+/// Outcome of a condition whose path is absent from the document
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnMissing {
+    /// The comparison fails, as if no value matched
+    #[default]
+    Fail,
+    /// The comparison passes, as if the value matched
+    Pass,
+    /// The condition is ignored
+    Skip,
+}
+
+/// Comparison applied between the value found at a path and the expected value
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operator {
+    #[default]
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// The value is a regular expression the actual value must match
+    Regex,
+}
+
+impl Operator {
+    /// Whether the operator orders values rather than testing equality
+    pub fn is_ordering(&self) -> bool {
+        !matches!(self, Operator::Eq | Operator::Ne | Operator::Regex)
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Eq => write!(f, "eq"),
+            Operator::Ne => write!(f, "ne"),
+            Operator::Gt => write!(f, "gt"),
+            Operator::Ge => write!(f, "ge"),
+            Operator::Lt => write!(f, "lt"),
+            Operator::Le => write!(f, "le"),
+            Operator::Regex => write!(f, "regex"),
+        }
+    }
+}
+
+impl fmt::Display for PathValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negate {
+            write!(f, "not ")?;
+        }
+        if self.match_mode == MatchMode::All {
+            write!(f, "all ")?;
+        }
+        match (&self.length, self.exists) {
+            (_, Some(true)) => write!(f, "{} exists", self.path),
+            (_, Some(false)) => write!(f, "{} does not exist", self.path),
+            (Some(length), None) => write!(f, "{} {}", self.path, length),
+            (None, None) => write!(f, "{} {} {}", self.path, self.op, self.value),
+        }
+    }
+}
+
+impl PathValue {
+    /// Check that the condition can be evaluated, e.g. that ordering operators
+    /// are only used with numbers and strings
+    pub fn validate(&self) -> Result<()> {
+        let pointer = if self.path.starts_with('/') {
+            self.path.clone()
+        } else {
+            format!("/{}", self.path)
+        };
+        validate_pointer(&pointer).with_context(|| format!("Invalid path '{}'", self.path))?;
+
+        if self.exists.is_some()
+            && (!self.value.is_null() || self.op != Operator::Eq || self.length.is_some())
+        {
+            anyhow::bail!(
+                "'exists' on path '{}' cannot be combined with 'value', 'op' or 'length'",
+                self.path
+            );
+        }
+
+        if let Some(length) = &self.length {
+            if length.bounds().is_empty() {
+                anyhow::bail!(
+                    "'length' on path '{}' requires at least one bound",
+                    self.path
+                );
+            }
+            if !self.value.is_null() || self.op != Operator::Eq {
+                anyhow::bail!(
+                    "'length' on path '{}' cannot be combined with 'value' or 'op'",
+                    self.path
+                );
+            }
+        }
+
+        if self.op == Operator::Regex {
+            let pattern = self.value.as_str().with_context(|| {
+                format!(
+                    "Operator 'regex' on path '{}' requires a string value, got {}",
+                    self.path, self.value
+                )
+            })?;
+            regex::Regex::new(pattern)
+                .with_context(|| format!("Invalid regex on path '{}'", self.path))?;
+        }
+
+        if self.op.is_ordering() && !(self.value.is_number() || self.value.is_string()) {
+            anyhow::bail!(
+                "Operator '{}' on path '{}' requires a number or string value, got {}",
+                self.op,
+                self.path,
+                self.value
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Rule {
+    /// Whether the rule applies on `branch`: always without `branches`, and otherwise
+    /// only on a known branch matching one of them
+    pub fn applies_to_branch(&self, branch: Option<&str>) -> bool {
+        if self.branches.is_empty() {
+            return true;
+        }
+
+        branch.is_some_and(|branch| self.branches.is_match(branch))
+    }
+
+    /// The rule's configured id, or a deterministic id derived from its whole definition
+    pub fn id(&self) -> String {
+        if let Some(id) = &self.id {
+            return id.clone();
+        }
+
+        // Every field takes part, so rules that differ in anything get different ids
+        let definition = serde_json::to_string(self).expect("rules serialize to JSON");
+        format!("rule-{:016x}", fnv1a(definition.as_bytes()))
+    }
+
+    /// Where the rule is defined, as `file:line`, or just the file when the line is unknown
+    pub fn location(&self) -> Option<String> {
+        let source = self.source.as_ref()?.display();
+
+        Some(match self.source_line {
+            Some(line) => format!("{}:{}", source, line),
+            None => source.to_string(),
+        })
+    }
+
+    /// Human-readable reference to the rule for use in messages
+    pub fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("Rule '{}'", name),
+            None if self.match_conditions.is_empty() && !self.files.is_empty() => {
+                format!("Rule for files {:?}", self.files.patterns())
+            }
+            None => format!(
+                "Rule matching {:?}",
+                self.match_conditions
+                    .iter()
+                    .map(|c| format!("{}={}", c.path, c.value))
+                    .collect::<Vec<_>>()
+            ),
+        }
+    }
+}
+
+/// Check that a path or change pattern is a JSON Pointer, allowing `*` and `**`
+/// wildcards. Malformed patterns would otherwise silently never match.
+fn validate_pointer(pointer: &str) -> Result<()> {
+    let invalid = |reason: String| Err(JiffsError::InvalidPointer(anyhow::anyhow!(reason)).into());
+
+    if !pointer.starts_with('/') {
+        return invalid("must start with '/'".to_string());
+    }
+
+    for segment in pointer[1..].split('/') {
+        if segment.is_empty() {
+            return invalid("contains an empty segment".to_string());
+        }
+
+        if segment.contains("**") && segment != "**" {
+            return invalid(format!("'**' must be a whole segment, got '{}'", segment));
+        }
+
+        if segment.starts_with('[') && segment.ends_with(']') {
+            match IndexRange::parse(segment) {
+                Some(range) if range.end.is_some_and(|end| end <= range.start) => {
+                    return invalid(format!("index range '{}' is empty", segment));
+                }
+                Some(_) => {}
+                None => {
+                    return invalid(format!(
+                        "index range must be written as [start:end], got '{}'",
+                        segment
+                    ));
+                }
+            }
+        }
+
+        let mut chars = segment.chars();
+        while let Some(c) = chars.next() {
+            if c == '~' && !matches!(chars.next(), Some('0' | '1')) {
+                return invalid(format!(
+                    "'~' must be escaped as '~0' in segment '{}'",
+                    segment
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand variables in a string value, including strings nested in arrays
+fn interpolate_value(
+    value: &mut serde_json::Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<()> {
+    match value {
+        serde_json::Value::String(text) => *text = interpolate(text, lookup)?,
+        serde_json::Value::Array(items) => {
+            for item in items {
+                interpolate_value(item, lookup)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replace `${VAR}` with the variable's value and `${VAR:-default}` with the value or,
+/// when the variable is undefined, the default. An undefined variable without a
+/// default is an error.
+fn interpolate(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find('}') else {
+            anyhow::bail!("Unterminated variable reference in '{}'", text);
+        };
+        let reference = &rest[start + 2..start + end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+
+        let valid = name
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+        if name.is_empty() || !valid {
+            anyhow::bail!("Invalid variable name '{}' in '{}'", name, text);
+        }
+
+        match (lookup(name), default) {
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => anyhow::bail!("Environment variable '{}' is not set", name),
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Lines, counting from 1, where the items of a block-style top-level `rules` list
+/// start. Flow-style lists yield no lines.
+fn rule_lines(content: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut in_rules = false;
+    let mut item_indent = None;
+
+    for (number, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if !in_rules {
+            in_rules = line.strip_prefix("rules:").is_some_and(|rest| {
+                let rest = rest.trim();
+                rest.is_empty() || rest.starts_with('#')
+            });
+            continue;
+        }
+
+        if line == "---" || line == "..." {
+            break;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let is_item = trimmed == "-" || trimmed.starts_with("- ");
+        let item_indent = *item_indent.get_or_insert(indent);
+
+        if indent < item_indent || (indent == item_indent && !is_item) {
+            break;
+        }
+        if indent == item_indent {
+            lines.push(number + 1);
+        }
+    }
+
+    lines
+}
+
+/// 64-bit FNV-1a hash, used for ids that must be stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where a `--policy` argument is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicySource<'a> {
+    /// `-`
+    Stdin,
+    /// An `http://` or `https://` URL, fetched with the `remote-policy` feature
+    Url(&'a str),
+    File(&'a Path),
+}
+
+impl<'a> PolicySource<'a> {
+    pub fn of(path: &'a Path) -> Self {
+        match path.to_str() {
+            Some("-") => Self::Stdin,
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                Self::Url(url)
+            }
+            _ => Self::File(path),
+        }
+    }
+}
+
+/// Fetch a policy over HTTP(S), failing on any status other than 2xx
+#[cfg(feature = "remote-policy")]
+fn fetch_policy(url: &str) -> Result<String> {
+    let mut response = ureq::get(url).call().map_err(|err| match err {
+        ureq::Error::StatusCode(status) => {
+            anyhow::anyhow!(
+                "Failed to fetch policy from {}: HTTP status {}",
+                url,
+                status
+            )
+        }
+        err => anyhow::Error::new(err).context(format!("Failed to fetch policy from {}", url)),
+    })?;
+
+    response
+        .body_mut()
+        .read_to_string()
+        .with_context(|| format!("Failed to read policy from {}", url))
+}
+
+#[cfg(not(feature = "remote-policy"))]
+fn fetch_policy(url: &str) -> Result<String> {
+    anyhow::bail!(
+        "Cannot fetch policy from {}: jiffs was built without the remote-policy feature",
+        url
+    )
+}
+
+impl FromStr for Config {
+    type Err = JiffsError;
+
+    /// Load a policy from YAML, e.g. fetched from a ConfigMap rather than read from disk.
+    /// Anchors and aliases are expanded; includes are resolved against the current
+    /// directory.
+    fn from_str(content: &str) -> Result<Self, JiffsError> {
+        let config =
+            Self::load_content(content, None, &mut Vec::new()).map_err(JiffsError::config)?;
+        config.check_unique_ids().map_err(JiffsError::config)?;
+
+        Ok(config)
+    }
 }
 
 impl Config {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path.as_ref())
-            .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
+    /// Load a policy from a file, stdin or a URL, see [`PolicySource`]. Includes of
+    /// policies not read from a file are resolved against the current directory.
+    pub fn from_source(path: &Path) -> Result<Self, JiffsError> {
+        match PolicySource::of(path) {
+            PolicySource::File(path) => Self::from_file(path),
+            PolicySource::Stdin => {
+                let mut content = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut content)
+                    .context("Failed to read policy from stdin")
+                    .map_err(JiffsError::config)?;
 
-        let config: Config =
-            serde_norway::from_str(&content).with_context(|| "Failed to parse YAML config")?;
+                content.parse()
+            }
+            PolicySource::Url(url) => fetch_policy(url).map_err(JiffsError::config)?.parse(),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, JiffsError> {
+        let config = Self::load(path.as_ref(), &mut Vec::new()).map_err(JiffsError::config)?;
+        config.check_unique_ids().map_err(JiffsError::config)?;
+
+        Ok(config)
+    }
+
+    /// Load a policy file and, recursively, the files it includes. `stack` holds the
+    /// files currently being loaded so include cycles can be reported.
+    fn load(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
+
+        Self::load_content(&content, Some(path), stack)
+    }
+
+    /// Parse and validate policy YAML, then load its includes relative to the policy
+    /// file, or to the current directory when it was not read from a file
+    fn load_content(content: &str, path: Option<&Path>, stack: &mut Vec<PathBuf>) -> Result<Self> {
+        let mut config: Config =
+            serde_norway::from_str(content).with_context(|| "Failed to parse YAML config")?;
+
+        // Lines are only attributed when the scan found exactly one item per rule
+        let lines = rule_lines(content);
+        let lines_known = lines.len() == config.rules.len();
+
+        for (i, rule) in config.rules.iter_mut().enumerate() {
+            rule.source = path.map(Path::to_path_buf);
+            rule.source_line = lines_known.then(|| lines[i]);
+        }
+
+        if !config.defaults.is_empty() {
+            let mut raw: serde_norway::Value =
+                serde_norway::from_str(content).with_context(|| "Failed to parse YAML config")?;
+            raw.apply_merge()
+                .with_context(|| "Failed to parse YAML config")?;
+            config.apply_defaults(&raw);
+        }
+
+        config.resolve_refs().with_context(|| match path {
+            Some(path) => format!("Invalid policy in {:?}", path),
+            None => "Invalid policy".to_string(),
+        })?;
+
+        config
+            .interpolate(&|name| std::env::var(name).ok())
+            .with_context(|| match path {
+                Some(path) => format!("Failed to expand variables in {:?}", path),
+                None => "Failed to expand variables".to_string(),
+            })?;
+
+        config.validate().with_context(|| match path {
+            Some(path) => format!("Invalid policy in {:?}", path),
+            None => "Invalid policy".to_string(),
+        })?;
+
+        if config.include.is_empty() {
+            return Ok(config);
+        }
+
+        if let Some(path) = path {
+            let canonical = path
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve config file: {:?}", path))?;
+
+            if let Some(start) = stack.iter().position(|p| *p == canonical) {
+                let cycle = stack[start..]
+                    .iter()
+                    .chain(std::iter::once(&canonical))
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                anyhow::bail!("Include cycle detected: {}", cycle);
+            }
+
+            stack.push(canonical);
+        }
+
+        let base_dir = path.and_then(Path::parent).unwrap_or(Path::new(""));
+        let mut rules = Vec::new();
+
+        for include in &config.include {
+            let included =
+                Self::load(&base_dir.join(include), stack).with_context(|| match path {
+                    Some(path) => format!("Failed to include {:?} from {:?}", include, path),
+                    None => format!("Failed to include {:?}", include),
+                })?;
+            rules.extend(included.rules);
+        }
+
+        if path.is_some() {
+            stack.pop();
+        }
+
+        rules.append(&mut config.rules);
+        config.rules = rules;
+        config.include.clear();
+
+        Ok(config)
+    }
+
+    /// Apply the file's `defaults` to the settings its rules leave out. Whether a rule
+    /// sets a field is read from the raw policy `raw`, since deserializing fills in the
+    /// built-in default.
+    fn apply_defaults(&mut self, raw: &serde_norway::Value) {
+        let defaults = &self.defaults;
+
+        for (i, rule) in self.rules.iter_mut().enumerate() {
+            let sets = |key: &str| raw["rules"][i].get(key).is_some();
+
+            if let Some(severity) = defaults.severity
+                && !sets("severity")
+            {
+                rule.severity = severity;
+            }
+            if let Some(allow_new_files) = defaults.allow_new_files
+                && !sets("allowNewFiles")
+            {
+                rule.allow_new_files = allow_new_files;
+            }
+            if let Some(allow_deletion) = defaults.allow_deletion
+                && !sets("allowDeletion")
+            {
+                rule.allow_deletion = allow_deletion;
+            }
+        }
+    }
+
+    /// Inline the `definitions` referenced by `matchRef` and `whenRef` ahead of each
+    /// rule's own conditions, so later stages only see plain condition lists
+    fn resolve_refs(&mut self) -> Result<()> {
+        for rule in &mut self.rules {
+            let describe = rule.describe();
+            let lookup = |field: &str, name: &str| {
+                self.definitions.get(name).with_context(|| {
+                    format!("Unknown definition '{}' in {} of {}", name, field, describe)
+                })
+            };
+
+            if let Some(name) = rule.match_ref.take() {
+                let mut conditions = lookup("matchRef", &name)?.clone();
+                conditions.append(&mut rule.match_conditions);
+                rule.match_conditions = conditions;
+            }
+
+            if let Some(name) = rule.when_ref.take() {
+                let mut conditions = lookup("whenRef", &name)?.clone();
+                conditions.extend(rule.when_conditions.take().into_iter().flatten());
+                rule.when_conditions = Some(conditions);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expand `${VAR}` and `${VAR:-default}` in string condition values and change
+    /// patterns, looking variables up with `lookup`
+    fn interpolate(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+        for rule in &mut self.rules {
+            let describe = rule.describe();
+
+            for condition in rule
+                .match_conditions
+                .iter_mut()
+                .chain(rule.when_conditions.iter_mut().flatten())
+                .chain(&mut rule.unless)
+            {
+                interpolate_value(&mut condition.value, lookup)
+                    .with_context(|| format!("Invalid condition in {}", describe))?;
+            }
+
+            for pattern in rule
+                .allowed_changes
+                .iter_mut()
+                .flatten()
+                .map(|allowed| &mut allowed.path)
+                .chain(&mut rule.forbidden_changes)
+                .chain(&mut rule.required_changes)
+                .chain(&mut rule.removals)
+                .chain(&mut rule.immutable)
+            {
+                *pattern = interpolate(pattern, lookup)
+                    .with_context(|| format!("Invalid change pattern in {}", describe))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every condition can be evaluated and every path and change pattern
+    /// is a valid JSON Pointer, so mistakes surface before any git work happens
+    pub fn validate(&self) -> Result<()> {
+        for rule in &self.rules {
+            for condition in rule
+                .match_conditions
+                .iter()
+                .chain(rule.when_conditions.iter().flatten())
+                .chain(&rule.unless)
+            {
+                condition
+                    .validate()
+                    .with_context(|| format!("Invalid condition in {}", rule.describe()))?;
+            }
+
+            let patterns = rule
+                .allowed_changes
+                .iter()
+                .flatten()
+                .map(|allowed| ("allowedChanges", &allowed.path))
+                .chain(
+                    rule.forbidden_changes
+                        .iter()
+                        .map(|p| ("forbiddenChanges", p)),
+                )
+                .chain(rule.required_changes.iter().map(|p| ("requiredChanges", p)))
+                .chain(rule.removals.iter().map(|p| ("removals", p)))
+                .chain(rule.immutable.iter().map(|p| ("immutable", p)))
+                .chain(rule.array_keys.keys().map(|p| ("arrayKeys", p)))
+                .chain(
+                    rule.version_policy
+                        .iter()
+                        .flat_map(|v| &v.paths)
+                        .map(|p| ("versionPolicy", p)),
+                );
+
+            if let Some(allowed) = rule
+                .allowed_changes
+                .iter()
+                .flatten()
+                .find(|allowed| allowed.ops.is_empty())
+            {
+                anyhow::bail!(
+                    "allowedChanges entry '{}' in {} has no ops; omit ops to allow every kind of change",
+                    allowed.path,
+                    rule.describe()
+                );
+            }
+
+            if let Some((pattern, _)) = rule.array_keys.iter().find(|(_, key)| key.is_empty()) {
+                anyhow::bail!(
+                    "arrayKeys entry '{}' in {} names no key field",
+                    pattern,
+                    rule.describe()
+                );
+            }
+
+            for allowed in rule.allowed_changes.iter().flatten() {
+                if allowed.allowed_values.as_ref().is_some_and(Vec::is_empty) {
+                    anyhow::bail!(
+                        "allowedChanges entry '{}' in {} has no allowedValues; omit allowedValues to allow any value",
+                        allowed.path,
+                        rule.describe()
+                    );
+                }
+            }
+
+            for (field, pattern) in patterns {
+                validate_pointer(pattern).with_context(|| {
+                    format!(
+                        "Invalid {} pattern '{}' in {}",
+                        field,
+                        pattern,
+                        rule.describe()
+                    )
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load several policies, concatenating their rules in order. Each is read from
+    /// its [`PolicySource`].
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self, JiffsError> {
+        let mut rules = Vec::new();
+
+        for path in paths {
+            rules.extend(Self::from_source(path.as_ref())?.rules);
+        }
+
+        let config = Config {
+            include: Vec::new(),
+            definitions: BTreeMap::new(),
+            defaults: RuleDefaults::default(),
+            rules,
+        };
+        config.check_unique_ids().map_err(JiffsError::config)?;
 
         Ok(config)
     }
+
+    /// Ensure no two rules share an id, whether configured or derived. Rules without an
+    /// id only share one when they are defined identically.
+    fn check_unique_ids(&self) -> Result<()> {
+        let mut seen: HashMap<String, &Rule> = HashMap::new();
+
+        for rule in &self.rules {
+            let id = rule.id();
+            if let Some(existing) = seen.insert(id.clone(), rule) {
+                anyhow::bail!(
+                    "Duplicate rule id '{}' defined in {:?} and {:?}{}",
+                    id,
+                    existing.source.as_deref().unwrap_or(Path::new("<unknown>")),
+                    rule.source.as_deref().unwrap_or(Path::new("<unknown>")),
+                    if rule.id.is_none() {
+                        "; the rules are identical, remove one or give them ids"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -71,11 +1359,10 @@ rules:
             serde_json::Value::String("ApplicationSet".to_string())
         );
 
-        assert_eq!(rule.allowed_changes.len(), 1);
-        assert_eq!(
-            rule.allowed_changes[0],
-            "/spec/generators/*/values/revision"
-        );
+        let allowed_changes = rule.allowed_changes.as_ref().unwrap();
+        assert_eq!(allowed_changes.len(), 1);
+        assert_eq!(allowed_changes[0], "/spec/generators/*/values/revision");
+        assert!(rule.forbidden_changes.is_empty());
 
         assert!(rule.when_conditions.is_some());
         let when_conditions = rule.when_conditions.as_ref().unwrap();
@@ -89,4 +1376,702 @@ rules:
             serde_json::Value::String("development".to_string())
         );
     }
+
+    #[test]
+    fn test_rule_id_and_name() {
+        let yaml_content = r#"
+rules:
+  - id: appset-revision
+    name: ApplicationSet revision bumps
+    match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/values/revision
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /metadata/labels
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+
+        let config = Config::from_file(temp_file.path()).unwrap();
+
+        assert_eq!(config.rules[0].id(), "appset-revision");
+        assert_eq!(
+            config.rules[0].describe(),
+            "Rule 'ApplicationSet revision bumps'"
+        );
+
+        // Derived ids are stable, and rules that differ in anything get different ones
+        assert!(config.rules[1].id().starts_with("rule-"));
+        assert_eq!(config.rules[1].id(), config.rules[1].clone().id());
+        let unmatched = |allowed: &str| {
+            let rule: Rule =
+                serde_norway::from_str(&format!("match: []\nallowedChanges: [{}]\n", allowed))
+                    .unwrap();
+            rule.id()
+        };
+        assert_ne!(unmatched("/a"), unmatched("/b"));
+
+        // Every option of a condition is part of the derived id
+        let derived = |condition: &str| {
+            let rule: Rule = serde_norway::from_str(&format!(
+                "match:\n- {{path: /kind, value: Deployment{}}}\nallowedChanges: []\n",
+                condition
+            ))
+            .unwrap();
+            rule.id()
+        };
+        let plain = derived("");
+        for option in [
+            ", negate: true",
+            ", op: ne",
+            ", onMissing: pass",
+            ", matchMode: all",
+        ] {
+            assert_ne!(derived(option), plain, "{}", option);
+        }
+        assert_ne!(derived(", negate: true"), derived(", op: ne"));
+    }
+
+    #[test]
+    fn test_rule_severity() {
+        let yaml_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges: []
+    severity: warning
+  - match:
+    - path: kind
+      value: Application
+    allowedChanges: []
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+
+        let config = Config::from_file(temp_file.path()).unwrap();
+
+        assert_eq!(config.rules[0].severity, Severity::Warning);
+        assert_eq!(config.rules[1].severity, Severity::Error);
+        assert!(Severity::Info < Severity::Warning && Severity::Warning < Severity::Error);
+    }
+
+    #[test]
+    fn test_condition_operators() {
+        let yaml_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/image
+    when:
+    - path: /spec/replicas
+      op: ge
+      value: 3
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+
+        let config = Config::from_file(temp_file.path()).unwrap();
+        let rule = &config.rules[0];
+
+        assert_eq!(rule.match_conditions[0].op, Operator::Eq);
+        assert_eq!(rule.when_conditions.as_ref().unwrap()[0].op, Operator::Ge);
+    }
+
+    #[test]
+    fn test_ordering_operator_on_non_scalar_is_rejected() {
+        let yaml_content = r#"
+rules:
+  - match:
+    - path: /spec/enabled
+      op: gt
+      value: true
+    allowedChanges: []
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+
+        let err = Config::from_file(temp_file.path()).unwrap_err();
+        assert!(format!("{:#}", err).contains("requires a number or string value"));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected_at_load() {
+        let yaml_content = r#"
+rules:
+  - match:
+    - path: /metadata/name
+      op: regex
+      value: "^prod-(unclosed"
+    allowedChanges: []
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+
+        let err = Config::from_file(temp_file.path()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid regex on path '/metadata/name'"));
+    }
+
+    #[test]
+    fn test_length_conditions_are_parsed_and_validated() {
+        let config: Config = r#"
+rules:
+  - match:
+    - path: /spec/generators
+      length: {ge: 1, le: 3}
+    allowedChanges: []
+"#
+        .parse()
+        .unwrap();
+        let length = config.rules[0].match_conditions[0].length.as_ref().unwrap();
+        assert_eq!(length.to_string(), "length ge 1 and le 3");
+
+        let invalid = [
+            ("length: {}", "requires at least one bound"),
+            ("length: {ge: 1}\n      value: 2", "cannot be combined"),
+            ("length: {atLeast: 1}", "unknown field"),
+        ];
+        for (condition, message) in invalid {
+            let yaml = format!(
+                "rules:\n  - match:\n    - path: /spec/generators\n      {}\n    allowedChanges: []\n",
+                condition
+            );
+            let err = yaml.parse::<Config>().unwrap_err();
+            assert!(format!("{:#}", err).contains(message), "{:#}", err);
+        }
+    }
+
+    #[test]
+    fn test_from_files_concatenates_rules() {
+        let mut first = NamedTempFile::new().unwrap();
+        write!(
+            first,
+            "rules:\n  - id: first\n    match: []\n    allowedChanges: []\n"
+        )
+        .unwrap();
+
+        let mut second = NamedTempFile::new().unwrap();
+        write!(
+            second,
+            "rules:\n  - id: second\n    match: []\n    allowedChanges: []\n  - match: []\n    allowedChanges: []\n"
+        )
+        .unwrap();
+
+        let config = Config::from_files(&[first.path(), second.path()]).unwrap();
+
+        assert_eq!(config.rules.len(), 3);
+        assert_eq!(config.rules[0].id(), "first");
+        assert_eq!(config.rules[1].id(), "second");
+        assert_eq!(config.rules[0].source.as_deref(), Some(first.path()));
+        assert_eq!(config.rules[2].source.as_deref(), Some(second.path()));
+        assert_eq!(config.rules[2].source_line, Some(5));
+        assert_eq!(
+            config.rules[2].location(),
+            Some(format!("{}:5", second.path().display()))
+        );
+    }
+
+    #[test]
+    fn test_from_files_rejects_duplicate_ids() {
+        let mut first = NamedTempFile::new().unwrap();
+        write!(
+            first,
+            "rules:\n  - id: shared\n    match: []\n    allowedChanges: []\n"
+        )
+        .unwrap();
+
+        let mut second = NamedTempFile::new().unwrap();
+        write!(
+            second,
+            "rules:\n  - id: shared\n    match: []\n    allowedChanges: []\n"
+        )
+        .unwrap();
+
+        let err = Config::from_files(&[first.path(), second.path()]).unwrap_err();
+        let message = err.to_string();
+
+        assert!(message.contains("Duplicate rule id 'shared'"));
+        assert!(message.contains(&format!("{:?}", first.path())));
+        assert!(message.contains(&format!("{:?}", second.path())));
+
+        // Identical rules without ids derive the same id; different ones do not clash
+        let rules = |second: &str| {
+            format!(
+                "rules:\n  - match: []\n    allowedChanges: [/a]\n  - match: []\n    allowedChanges: [{}]\n",
+                second
+            )
+            .parse::<Config>()
+        };
+        assert!(rules("/b").is_ok());
+        let message = format!("{:#}", rules("/a").unwrap_err());
+        assert!(message.contains("the rules are identical"), "{}", message);
+    }
+
+    #[test]
+    fn test_include_resolves_relative_to_including_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("common")).unwrap();
+
+        fs::write(
+            dir.path().join("common/base.yaml"),
+            "rules:\n  - id: base\n    match: []\n    allowedChanges: []\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("policy.yaml"),
+            "include: [common/base.yaml]\nrules:\n  - id: local\n    match: []\n    allowedChanges: []\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(dir.path().join("policy.yaml")).unwrap();
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].id(), "base");
+        assert_eq!(config.rules[1].id(), "local");
+        assert_eq!(
+            config.rules[0].source.as_deref(),
+            Some(dir.path().join("common/base.yaml").as_path())
+        );
+    }
+
+    #[test]
+    fn test_include_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir.path().join("a.yaml"), "include: [b.yaml]\nrules: []\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\nrules: []\n").unwrap();
+
+        let err = Config::from_file(dir.path().join("a.yaml")).unwrap_err();
+        let message = format!("{:#}", err);
+
+        assert!(message.contains("Include cycle detected"));
+        assert!(message.contains("a.yaml -> "));
+        assert!(message.contains("b.yaml -> "));
+    }
+
+    #[test]
+    fn test_malformed_pointers_are_rejected() {
+        let parse = |yaml: &str| -> Config { serde_norway::from_str(yaml).unwrap() };
+
+        let valid = parse(
+            r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/clusters/values/revision
+    - /metadata/annotations/example.com~1team
+    - /spec/**
+    - /items/item*/name
+"#,
+        );
+        assert!(valid.validate().is_ok());
+
+        let empty_segment = parse(
+            r#"
+rules:
+  - name: typo
+    match: []
+    allowedChanges:
+    - spec/generators//revision
+"#,
+        );
+        let message = format!("{:#}", empty_segment.validate().unwrap_err());
+        assert!(message.contains("allowedChanges pattern 'spec/generators//revision'"));
+        assert!(message.contains("Rule 'typo'"));
+
+        let bad_condition = parse(
+            r#"
+rules:
+  - match: []
+    allowedChanges: []
+    when:
+    - path: /spec//env
+      value: development
+"#,
+        );
+        let message = format!("{:#}", bad_condition.validate().unwrap_err());
+        assert!(message.contains("Invalid path '/spec//env'"));
+
+        for pattern in ["/spec/a**", "/spec/~2", "/spec/"] {
+            let config = parse(&format!(
+                "rules:\n  - match: []\n    forbiddenChanges: ['{}']\n",
+                pattern
+            ));
+            assert!(config.validate().is_err(), "{} should be rejected", pattern);
+        }
+    }
+
+    #[test]
+    fn test_from_str_expands_anchors() {
+        let yaml_content = r#"
+# Paths every team may change
+rules:
+  - id: web
+    match:
+    - path: metadata/name
+      value: web
+    allowedChanges: &common
+    - /spec/replicas
+    - /spec/template/spec/containers/*/image
+  - id: worker
+    match:
+    - path: metadata/name
+      value: worker
+    allowedChanges: *common
+"#;
+
+        let config = Config::from_str(yaml_content).unwrap();
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].allowed_changes.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            config.rules[0].allowed_changes,
+            config.rules[1].allowed_changes
+        );
+        assert!(config.rules[0].source.is_none());
+    }
+
+    #[test]
+    fn test_from_str_validates() {
+        let err = Config::from_str("rules:\n  - match: []\n    allowedChanges: ['spec//x']\n")
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid policy"));
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        (name == "ENV").then(|| "staging".to_string())
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(interpolate("${ENV}", &lookup).unwrap(), "staging");
+        assert_eq!(
+            interpolate("/spec/${ENV}/${REGION:-eu}/x", &lookup).unwrap(),
+            "/spec/staging/eu/x"
+        );
+        assert_eq!(interpolate("${ENV:-dev}", &lookup).unwrap(), "staging");
+        assert_eq!(
+            interpolate("no $vars {here}", &lookup).unwrap(),
+            "no $vars {here}"
+        );
+
+        let err = interpolate("${REGION}", &lookup).unwrap_err();
+        assert!(err.to_string().contains("'REGION' is not set"));
+        assert!(interpolate("${ENV", &lookup).is_err());
+        assert!(interpolate("${1ENV}", &lookup).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_policy() {
+        let mut config: Config = serde_norway::from_str(
+            r#"
+rules:
+  - match:
+    - path: /metadata/labels/env
+      value: ${ENV}
+    - path: /spec/replicas
+      value: 3
+    - path: /metadata/namespaces
+      value: ["${ENV}", "shared"]
+    allowedChanges:
+    - /spec/${ENV}/image
+"#,
+        )
+        .unwrap();
+
+        config.interpolate(&lookup).unwrap();
+
+        let rule = &config.rules[0];
+        assert_eq!(rule.match_conditions[0].value, "staging");
+        assert_eq!(rule.match_conditions[1].value, 3);
+        assert_eq!(
+            rule.match_conditions[2].value,
+            serde_json::json!(["staging", "shared"])
+        );
+        assert_eq!(
+            rule.allowed_changes.as_deref(),
+            Some(&[AllowedChange::from("/spec/staging/image")][..])
+        );
+    }
+
+    #[test]
+    fn test_condition_refs() {
+        let config: Config = r#"
+definitions:
+  prod:
+  - path: /metadata/labels/env
+    value: prod
+rules:
+  - id: app
+    matchRef: prod
+    match:
+    - path: /kind
+      value: Deployment
+    whenRef: prod
+    allowedChanges:
+    - /spec/replicas
+  - id: other
+    match:
+    - path: /kind
+      value: Service
+    whenRef: prod
+    when:
+    - path: /spec/type
+      value: ClusterIP
+    allowedChanges: []
+"#
+        .parse()
+        .unwrap();
+
+        let app = &config.rules[0];
+        let paths = |conditions: &[PathValue]| {
+            conditions
+                .iter()
+                .map(|c| c.path.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(&app.match_conditions),
+            vec!["/metadata/labels/env", "/kind"]
+        );
+        assert_eq!(
+            paths(app.when_conditions.as_deref().unwrap()),
+            vec!["/metadata/labels/env"]
+        );
+        assert!(app.match_ref.is_none() && app.when_ref.is_none());
+
+        let other = &config.rules[1];
+        assert_eq!(
+            paths(other.when_conditions.as_deref().unwrap()),
+            vec!["/metadata/labels/env", "/spec/type"]
+        );
+
+        let err = "rules:\n  - id: app\n    whenRef: staging\n    allowedChanges: []\n"
+            .parse::<Config>()
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Unknown definition 'staging' in whenRef"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_policy_sources() {
+        assert_eq!(PolicySource::of(Path::new("-")), PolicySource::Stdin);
+        assert_eq!(
+            PolicySource::of(Path::new("https://policies.example.com/prod.yaml")),
+            PolicySource::Url("https://policies.example.com/prod.yaml")
+        );
+        assert_eq!(
+            PolicySource::of(Path::new("policies/-.yaml")),
+            PolicySource::File(Path::new("policies/-.yaml"))
+        );
+    }
+
+    /// Serve a single HTTP response on a local port, returning its URL
+    #[cfg(feature = "remote-policy")]
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        use std::io::{BufRead, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/policy.yaml", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        url
+    }
+
+    #[cfg(feature = "remote-policy")]
+    #[test]
+    fn test_fetch_policy() {
+        let url = serve_once("200 OK", "rules:\n  - id: remote\n    match: []\n");
+        let config = Config::from_source(Path::new(&url)).unwrap();
+        assert_eq!(config.rules[0].id(), "remote");
+
+        let url = serve_once("404 Not Found", "missing");
+        let err = Config::from_source(Path::new(&url)).unwrap_err();
+        assert!(matches!(err, JiffsError::Config(_)), "{:?}", err);
+        assert!(
+            format!("{:#}", err).ends_with("HTTP status 404"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[cfg(not(feature = "remote-policy"))]
+    #[test]
+    fn test_fetch_policy_requires_feature() {
+        let err = Config::from_source(Path::new("https://example.com/policy.yaml")).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("without the remote-policy feature"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_rule_lines() {
+        let content = "# policy\nrules:\n\n- id: a\n  match: []\n  allowedChanges:\n  - /spec\n# next\n- id: b\n  match: []\ninclude: []\n";
+        assert_eq!(rule_lines(content), vec![4, 9]);
+
+        let content = "include: []\nrules: # all rules\n  - id: a\n    match:\n    - path: kind\n  -\n    id: b\n";
+        assert_eq!(rule_lines(content), vec![3, 6]);
+
+        // Flow-style lists have no per-rule lines
+        assert!(rule_lines("rules: [{id: a, match: []}]\n").is_empty());
+    }
+
+    #[test]
+    fn test_allowed_change_ops() {
+        let config: Config = r#"
+rules:
+  - match: []
+    allowedChanges:
+    - /spec/image
+    - path: /spec/*
+      ops: [modify, remove, modify]
+    - path: /metadata/labels/*
+"#
+        .parse()
+        .unwrap();
+
+        let allowed = config.rules[0].allowed_changes.as_ref().unwrap();
+        assert_eq!(allowed[0], "/spec/image");
+        assert_eq!(allowed[1].ops, vec![ChangeOp::Modify, ChangeOp::Remove]);
+        assert_eq!(allowed[1].to_string(), "/spec/* (modify, remove)");
+        assert!(allowed[2].allows_all_ops());
+
+        // Entries allowing every op serialize back to plain strings
+        let yaml = serde_norway::to_string(&allowed).unwrap();
+        assert_eq!(
+            yaml,
+            "- /spec/image\n- path: /spec/*\n  ops:\n  - modify\n  - remove\n- /metadata/labels/*\n"
+        );
+
+        let err = "rules:\n  - match: []\n    allowedChanges:\n    - path: /spec\n      ops: []\n"
+            .parse::<Config>()
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("has no ops"));
+        assert!(
+            "rules:\n  - match: []\n    allowedChanges:\n    - path: /spec\n      ops: [rename]\n"
+                .parse::<Config>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_allowed_values() {
+        let config: Config = r#"
+rules:
+  - match: []
+    allowedChanges:
+    - path: /spec/image
+      ops: [modify]
+      allowedValues: [{glob: "app:*"}, {regex: "^v[0-9]+$"}, 3, "*"]
+"#
+        .parse()
+        .unwrap();
+
+        let allowed = &config.rules[0].allowed_changes.as_ref().unwrap()[0];
+        assert!(allowed.accepts(&serde_json::json!("app:1.2")));
+        assert!(allowed.accepts(&serde_json::json!("v12")));
+        assert!(allowed.accepts(&serde_json::json!(3)));
+        assert!(!allowed.accepts(&serde_json::json!("other:1.2")));
+        assert!(!allowed.accepts(&serde_json::json!("3")));
+        // Plain strings are exact even when they look like a glob
+        assert!(allowed.accepts(&serde_json::json!("*")));
+        assert!(!allowed.accepts(&serde_json::json!("anything")));
+        assert_eq!(
+            allowed.to_string(),
+            "/spec/image (modify; values: glob \"app:*\", /^v[0-9]+$/, 3, \"*\")"
+        );
+
+        let yaml = serde_norway::to_string(&allowed).unwrap();
+        assert!(yaml.contains("allowedValues:"));
+        assert_eq!(
+            serde_norway::from_str::<AllowedChange>(&yaml).unwrap(),
+            *allowed
+        );
+
+        let invalid = |values: &str| {
+            format!(
+                "rules:\n  - match: []\n    allowedChanges:\n    - path: /spec\n      allowedValues: {}\n",
+                values
+            )
+            .parse::<Config>()
+            .unwrap_err()
+        };
+        assert!(format!("{:#}", invalid("[]")).contains("has no allowedValues"));
+        assert!(format!("{:#}", invalid("[{regex: '('}]")).contains("Invalid allowedValues entry"));
+        assert!(format!("{:#}", invalid("[{glob: '['}]")).contains("Invalid allowedValues entry"));
+    }
+
+    #[test]
+    fn test_rule_defaults() {
+        let config: Config = r#"
+defaults:
+  severity: warning
+  allowDeletion: true
+rules:
+  - &base
+    match: []
+  - match: []
+    severity: error
+    allowDeletion: false
+  - <<: *base
+    allowNewFiles: false
+"#
+        .parse()
+        .unwrap();
+
+        let settings: Vec<_> = config
+            .rules
+            .iter()
+            .map(|rule| (rule.severity, rule.allow_deletion, rule.allow_new_files))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                (Severity::Warning, true, true),
+                (Severity::Error, false, true),
+                (Severity::Warning, true, false),
+            ]
+        );
+
+        let err = "defaults:\n  severty: warning\nrules: []\n"
+            .parse::<Config>()
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown field `severty`"));
+    }
 }
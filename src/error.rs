@@ -0,0 +1,92 @@
+use thiserror::Error;
+
+/// Errors returned by the public API, classified so callers can tell a failing git
+/// command from an invalid policy or unparseable content. Each variant keeps the full
+/// context chain of the underlying error.
+#[derive(Debug, Error)]
+pub enum JiffsError {
+    /// Running git or reading the files to diff failed
+    #[error(transparent)]
+    Git(anyhow::Error),
+    /// A policy could not be read, parsed or validated
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// A path or change pattern in a policy is not a valid JSON Pointer
+    #[error(transparent)]
+    InvalidPointer(anyhow::Error),
+    /// Changed file content could not be parsed as YAML or JSON
+    #[error(transparent)]
+    Parse(anyhow::Error),
+    /// Validation failed for another reason, e.g. values that cannot be compared
+    #[error(transparent)]
+    Validation(anyhow::Error),
+}
+
+impl JiffsError {
+    /// Classify an error from computing a diff
+    pub(crate) fn git(err: anyhow::Error) -> Self {
+        Self::classify(err, JiffsError::Git)
+    }
+
+    /// Classify an error from loading a policy
+    pub(crate) fn config(err: anyhow::Error) -> Self {
+        Self::classify(err, JiffsError::Config)
+    }
+
+    /// Classify an error from validating changes
+    pub(crate) fn validation(err: anyhow::Error) -> Self {
+        Self::classify(err, JiffsError::Validation)
+    }
+
+    /// Keep the kind of a `JiffsError` raised deeper in the chain, e.g. an invalid
+    /// pointer reported while loading a policy, and otherwise use `default`
+    fn classify(err: anyhow::Error, default: fn(anyhow::Error) -> Self) -> Self {
+        // `downcast` would also look through context layers and drop them
+        if err
+            .chain()
+            .next()
+            .is_some_and(|cause| cause.is::<JiffsError>())
+        {
+            return err
+                .downcast::<JiffsError>()
+                .expect("the outermost error is a JiffsError");
+        }
+
+        let kind: fn(anyhow::Error) -> Self = match err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<JiffsError>())
+        {
+            Some(JiffsError::Git(_)) => JiffsError::Git,
+            Some(JiffsError::Config(_)) => JiffsError::Config,
+            Some(JiffsError::InvalidPointer(_)) => JiffsError::InvalidPointer,
+            Some(JiffsError::Parse(_)) => JiffsError::Parse,
+            Some(JiffsError::Validation(_)) => JiffsError::Validation,
+            None => default,
+        };
+
+        kind(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_classify_keeps_inner_kind_and_context() {
+        let inner: anyhow::Result<()> =
+            Err(JiffsError::InvalidPointer(anyhow::anyhow!("must start with '/'")).into());
+        let err = JiffsError::config(inner.context("Invalid policy").unwrap_err());
+
+        assert!(matches!(err, JiffsError::InvalidPointer(_)));
+        assert_eq!(format!("{:#}", err), "Invalid policy: must start with '/'");
+
+        let err = JiffsError::config(anyhow::anyhow!("Failed to parse YAML config"));
+        assert!(matches!(err, JiffsError::Config(_)));
+
+        // An error that already is a JiffsError is not wrapped again
+        let err = JiffsError::validation(JiffsError::Parse(anyhow::anyhow!("bad")).into());
+        assert!(matches!(err, JiffsError::Parse(_)));
+    }
+}
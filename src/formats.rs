@@ -0,0 +1,160 @@
+//! Manifest formats other than YAML/JSON, each behind the Cargo feature of the same
+//! name and converted into the JSON data model the validator works on.
+
+#[cfg(any(feature = "toml", feature = "hcl"))]
+use anyhow::Context;
+use anyhow::Result;
+use serde_json::Value;
+
+/// File extensions parsed as TOML
+#[cfg(feature = "toml")]
+pub const TOML_EXTENSIONS: &[&str] = &["toml"];
+#[cfg(not(feature = "toml"))]
+pub const TOML_EXTENSIONS: &[&str] = &[];
+
+/// File extensions parsed as HCL
+#[cfg(feature = "hcl")]
+pub const HCL_EXTENSIONS: &[&str] = &["hcl", "tf"];
+#[cfg(not(feature = "hcl"))]
+pub const HCL_EXTENSIONS: &[&str] = &[];
+
+/// Parse `content` by its file extension, or `None` when the extension is not one of
+/// [`TOML_EXTENSIONS`] or [`HCL_EXTENSIONS`]
+pub fn from_str(extension: &str, content: &str) -> Option<Result<Value>> {
+    #[cfg(feature = "toml")]
+    if TOML_EXTENSIONS.contains(&extension) {
+        return Some(toml_from_str(content).context("Failed to parse as TOML"));
+    }
+    #[cfg(feature = "hcl")]
+    if HCL_EXTENSIONS.contains(&extension) {
+        return Some(hcl_from_str(content).context("Failed to parse as HCL"));
+    }
+
+    let _ = (extension, content);
+    None
+}
+
+/// Parse a TOML document. Dates and times have no JSON equivalent and are kept as
+/// their string form; NaN and infinite floats are rejected.
+#[cfg(feature = "toml")]
+pub fn toml_from_str(content: &str) -> Result<Value> {
+    let table: toml::Table = toml::from_str(content)?;
+    toml_to_json(toml::Value::Table(table), 0)
+}
+
+#[cfg(feature = "toml")]
+fn toml_to_json(value: toml::Value, depth: usize) -> Result<Value> {
+    if depth > crate::json_path::MAX_DEPTH {
+        anyhow::bail!(
+            "Document is nested deeper than {} levels",
+            crate::json_path::MAX_DEPTH
+        );
+    }
+
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow::anyhow!("{} has no JSON equivalent", f))?,
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(|value| toml_to_json(value, depth + 1))
+                .collect::<Result<_>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| Ok((key, toml_to_json(value, depth + 1)?)))
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+/// Parse an HCL body, e.g. a Terraform file. Blocks become objects keyed by their
+/// type and labels, and expressions such as `var.name` are kept as `${...}` strings.
+#[cfg(feature = "hcl")]
+pub fn hcl_from_str(content: &str) -> Result<Value> {
+    Ok(hcl::from_str(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str_by_extension() {
+        assert!(from_str("yaml", "a: 1").is_none());
+        assert_eq!(
+            from_str("toml", "a = 1").map(Result::unwrap),
+            cfg!(feature = "toml").then(|| serde_json::json!({ "a": 1 }))
+        );
+        assert_eq!(
+            from_str("tf", "a = 1").map(Result::unwrap),
+            cfg!(feature = "hcl").then(|| serde_json::json!({ "a": 1 }))
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() {
+        let value = toml_from_str(
+            r#"
+title = "web"
+created = 1979-05-27T07:32:00Z
+
+[spec]
+limits = { cpu = "1", memory.max = "512Mi" }
+
+[[spec.ports]]
+port = 80
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "title": "web",
+                "created": "1979-05-27T07:32:00Z",
+                "spec": {
+                    "limits": { "cpu": "1", "memory": { "max": "512Mi" } },
+                    "ports": [{ "port": 80 }]
+                }
+            })
+        );
+
+        assert!(toml_from_str("a = 1\na = 2\n").is_err());
+        assert!(toml_from_str("a = nan\n").is_err());
+
+        let deep = format!("a = {}{}\n", "[".repeat(10_000), "]".repeat(10_000));
+        assert!(toml_from_str(&deep).is_err());
+    }
+
+    #[cfg(feature = "hcl")]
+    #[test]
+    fn test_hcl() {
+        let value = hcl_from_str(
+            r#"
+resource "aws_instance" "web" {
+  instance_type = "t3.micro"
+  ami           = var.ami
+  tags = {
+    Name = "web"
+  }
+}
+"#,
+        )
+        .unwrap();
+
+        let web = &value["resource"]["aws_instance"]["web"];
+        assert_eq!(web["instance_type"], "t3.micro");
+        assert_eq!(web["ami"], "${var.ami}");
+        assert_eq!(web["tags"]["Name"], "web");
+
+        assert!(hcl_from_str("resource {").is_err());
+    }
+}
@@ -1,13 +1,33 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
-use std::path::Path;
-use std::process::Command;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+use crate::error::JiffsError;
+use crate::glob::GlobSet;
+use crate::ignore::IgnoreFile;
+use crate::json_path::ChangeMap;
+use crate::validator::Validator;
+
+#[cfg(feature = "libgit")]
+mod libgit;
+
+/// The changed files to validate, keyed by path. Besides [`GitDiff::new`] and
+/// [`GitDiff::from_name_status`], a diff can be built directly from in-memory
+/// [`FileChange`]s, in which case validating it never runs git or reads files.
+#[derive(Debug, Clone, Default)]
 pub struct GitDiff {
     pub changed_files: HashMap<String, FileChange>,
+    /// Changed files left out because a side exceeds [`DiffOptions::max_file_bytes`], sorted
+    pub oversized_files: Vec<String>,
+    /// Changed files left out because a side is not valid UTF-8, e.g. binary files, sorted
+    pub non_utf8_files: Vec<String>,
 }
 
+/// The content of a changed file before and after the change. Content is `None` when
+/// the file does not exist on that side.
 #[derive(Debug, Clone)]
 pub struct FileChange {
     pub base_content: Option<String>,
@@ -20,34 +40,419 @@ pub enum ChangeType {
     Added,
     Modified,
     Deleted,
+    Renamed { from: String },
+    Copied { from: String },
+}
+
+impl std::fmt::Display for ChangeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeType::Added => write!(f, "added"),
+            ChangeType::Modified => write!(f, "modified"),
+            ChangeType::Deleted => write!(f, "deleted"),
+            ChangeType::Renamed { .. } => write!(f, "renamed"),
+            ChangeType::Copied { .. } => write!(f, "copied"),
+        }
+    }
+}
+
+/// What the base is compared against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffMode {
+    /// The working tree, including unstaged changes
+    #[default]
+    Worktree,
+    /// The staged index only, e.g. for pre-commit hooks
+    Staged,
+}
+
+/// How symlinks in the working tree (or a `--diff-from-stdin` content tree) are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkMode {
+    /// Read the file the link points to, validating it as the target
+    #[default]
+    Follow,
+    /// Read the link text itself, as git stores symlink blobs
+    LinkText,
+}
+
+/// Options controlling how the diff is computed
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    pub mode: DiffMode,
+    /// Commit to compare the base against, reading both sides from git. Takes precedence
+    /// over `mode`.
+    pub head: Option<String>,
+    /// Diff against the merge base of the base and the head (HEAD unless `head` is given),
+    /// like `git diff <base>...HEAD`
+    pub merge_base: bool,
+    /// Only include files under one of these directories, on top of the other filters
+    pub path_prefixes: Vec<String>,
+    /// Only include files whose path ends with one of these suffixes
+    pub only_suffixes: Vec<String>,
+    /// Only include files matching one of these globs
+    pub include: GlobSet,
+    /// Skip files matching one of these globs, even if they are included
+    pub exclude: GlobSet,
+    /// Skip files ignored by a `.jiffsignore` file, even if they are included
+    pub ignore: IgnoreFile,
+    /// Repository to run git in and read working tree files from, defaulting to the
+    /// current directory
+    pub repo: Option<PathBuf>,
+    /// Skip files larger than this many bytes on either side, without reading them
+    pub max_file_bytes: Option<u64>,
+    /// Git executable to run, defaulting to `git` on `PATH`
+    pub git_binary: Option<PathBuf>,
+    /// How symlinks among the files read from disk are read
+    pub symlinks: SymlinkMode,
+    /// Kill a git command still running after this long and fail, instead of waiting
+    pub git_timeout: Option<Duration>,
+    /// How many times a git command that timed out or failed transiently, e.g. on lock
+    /// contention, is retried, with exponential backoff
+    pub git_retries: u32,
+    /// Diff exactly these repository-relative paths instead of asking git which files
+    /// changed, e.g. the staged files a pre-commit hook is given. Other filters still apply.
+    pub files: Vec<String>,
+}
+
+impl DiffOptions {
+    /// Root of the repository being validated
+    pub fn repo_dir(&self) -> &Path {
+        self.repo.as_deref().unwrap_or(Path::new("."))
+    }
+
+    /// Whether a changed file is in scope. Suffixes and include globs are alternatives;
+    /// when neither is given every file is included. Path prefixes must also match when
+    /// given, and excludes and ignored files always win.
+    pub fn is_selected(&self, file_path: &str) -> bool {
+        if self.exclude.is_match(file_path) || self.ignore.is_ignored(file_path) {
+            return false;
+        }
+
+        if !self.path_prefixes.is_empty()
+            && !self
+                .path_prefixes
+                .iter()
+                .any(|prefix| is_under(file_path, prefix))
+        {
+            return false;
+        }
+
+        if self.only_suffixes.is_empty() && self.include.is_empty() {
+            return true;
+        }
+
+        self.only_suffixes
+            .iter()
+            .any(|suffix| file_path.ends_with(suffix))
+            || self.include.is_match(file_path)
+    }
+}
+
+/// Collect the paths of everything but directories below `dir`, relative to the
+/// scanned root and `/`-separated, skipping `.git`
+fn list_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    for entry in entries {
+        let entry =
+            entry.with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_path = format!("{}{}", prefix, name);
+
+        if entry.file_type()?.is_dir() {
+            if name != ".git" {
+                list_files(&entry.path(), &format!("{}/", file_path), files)?;
+            }
+        } else {
+            files.push(file_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` lies inside the directory `prefix`, respecting directory boundaries
+fn is_under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_start_matches("./").trim_matches('/');
+    if prefix.is_empty() || prefix == "." {
+        return true;
+    }
+
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with('/'))
 }
 
 impl GitDiff {
-    pub fn new(base_sha: &str, only_suffixes: &[String]) -> Result<Self> {
-        let changed_files = get_changed_files(base_sha, only_suffixes)?;
+    pub fn new(base_sha: &str, options: &DiffOptions) -> Result<Self, JiffsError> {
+        let base_sha = if options.merge_base {
+            get_merge_base(options, base_sha, options.head.as_deref().unwrap_or("HEAD"))
+                .map_err(JiffsError::git)?
+        } else {
+            base_sha.to_string()
+        };
+
+        get_changed_files(&base_sha, options).map_err(JiffsError::git)
+    }
+
+    /// Build a diff without running git, from precomputed `git diff --name-status` output
+    /// (NUL- or newline-separated) and two directory trees holding the file contents at
+    /// the base and at the current state. Files missing from a tree have no content there.
+    pub fn from_name_status(
+        name_status: &str,
+        base_dir: &Path,
+        current_dir: &Path,
+        options: &DiffOptions,
+    ) -> Result<Self, JiffsError> {
+        let mut diff = GitDiff::default();
+
+        for (file_path, change_type) in select_changes(name_status, options) {
+            let base_path = match &change_type {
+                ChangeType::Added => None,
+                ChangeType::Renamed { from } | ChangeType::Copied { from } => {
+                    Some(base_dir.join(from))
+                }
+                _ => Some(base_dir.join(&file_path)),
+            };
+            let current_path =
+                (change_type != ChangeType::Deleted).then(|| current_dir.join(&file_path));
+
+            if let Some(max) = options.max_file_bytes
+                && [&base_path, &current_path]
+                    .into_iter()
+                    .flatten()
+                    .any(|path| file_size(path, options.symlinks).is_some_and(|size| size > max))
+            {
+                diff.oversized_files.push(file_path);
+                continue;
+            }
+
+            let read = |path: &Option<PathBuf>| match path {
+                Some(path) => read_file_if_exists(path, options.symlinks).map_err(JiffsError::git),
+                None => Ok(None),
+            };
+            let base_content = read(&base_path)?;
+            let current_content = read(&current_path)?;
+
+            diff.insert(file_path, base_content, current_content, change_type);
+        }
+
+        diff.oversized_files.sort();
+        diff.non_utf8_files.sort();
+
+        Ok(diff)
+    }
 
-        Ok(GitDiff { changed_files })
+    /// Build a diff without git from every selected file below `dir`, each as added with
+    /// its current content, e.g. to audit a checked-out tree. Paths are relative to
+    /// `dir`, and `.git` directories are skipped.
+    pub fn from_dir(dir: &Path, options: &DiffOptions) -> Result<Self, JiffsError> {
+        let mut diff = GitDiff::default();
+
+        let mut files = Vec::new();
+        list_files(dir, "", &mut files).map_err(JiffsError::git)?;
+        files.sort();
+
+        for file_path in files {
+            if !options.is_selected(&file_path) {
+                continue;
+            }
+
+            // Links to directories are not descended into, so a link cannot loop
+            let path = dir.join(&file_path);
+            let Some(metadata) = metadata(&path, options.symlinks) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                continue;
+            }
+
+            if options
+                .max_file_bytes
+                .is_some_and(|max| metadata.len() > max)
+            {
+                diff.oversized_files.push(file_path);
+                continue;
+            }
+
+            let content = read_file_if_exists(&path, options.symlinks).map_err(JiffsError::git)?;
+            diff.insert(file_path, None, content, ChangeType::Added);
+        }
+
+        Ok(diff)
+    }
+
+    /// Add a changed file from its raw content, or record it as not UTF-8
+    fn insert(
+        &mut self,
+        file_path: String,
+        base_content: Option<Vec<u8>>,
+        current_content: Option<Vec<u8>>,
+        change_type: ChangeType,
+    ) {
+        let decode = |content: Option<Vec<u8>>| content.map(String::from_utf8).transpose();
+
+        match (decode(base_content), decode(current_content)) {
+            (Ok(base_content), Ok(current_content)) => {
+                self.changed_files.insert(
+                    file_path,
+                    FileChange {
+                        base_content,
+                        current_content,
+                        change_type,
+                    },
+                );
+            }
+            _ => self.non_utf8_files.push(file_path),
+        }
+    }
+
+    /// The JSON Pointer changes of every changed YAML/JSON file, independent of any
+    /// policy, e.g. to render them alongside the verdicts. Keys are the paths violations
+    /// use, so each document of a multi-document file has its own entry. Files that
+    /// cannot be parsed are left out.
+    pub fn compute_changes(&self) -> Result<HashMap<String, ChangeMap>, JiffsError> {
+        let mut changes = HashMap::new();
+
+        for file_path in self.changed_file_paths() {
+            let documents = Validator::document_changes(file_path, &self.changed_files[file_path])
+                .map_err(JiffsError::validation)?;
+            changes.extend(documents.into_iter().flatten());
+        }
+
+        Ok(changes)
     }
 
     pub fn get_file_change(&self, path: &str) -> Option<&FileChange> {
         self.changed_files.get(path)
     }
 
+    /// Paths of the changed files, sorted
     pub fn changed_file_paths(&self) -> Vec<&String> {
-        self.changed_files.keys().collect()
+        let mut paths: Vec<&String> = self.changed_files.keys().collect();
+        paths.sort();
+        paths
+    }
+}
+
+fn get_changed_files(base_sha: &str, options: &DiffOptions) -> Result<GitDiff> {
+    let mode = options.mode;
+    let head = options.head.as_deref();
+    let mut diff = GitDiff::default();
+
+    let mut selected = if options.files.is_empty() {
+        diff_name_status(base_sha, options)?
+    } else {
+        list_changes(base_sha, options)?
+    };
+
+    // Blobs of a change: the base, then the current side when it is read from git.
+    // Renamed and copied files are compared against their source path at the base.
+    let blob_specs = |file_path: &str, change_type: &ChangeType| {
+        let mut specs = Vec::new();
+        match change_type {
+            ChangeType::Added => {}
+            ChangeType::Renamed { from } | ChangeType::Copied { from } => {
+                specs.push(format!("{}:{}", base_sha, from))
+            }
+            _ => specs.push(format!("{}:{}", base_sha, file_path)),
+        }
+
+        if *change_type != ChangeType::Deleted {
+            match (head, mode) {
+                (Some(head), _) => specs.push(format!("{}:{}", head, file_path)),
+                // The blob staged in the index, which may differ from the working copy
+                (None, DiffMode::Staged) => specs.push(format!(":{}", file_path)),
+                (None, DiffMode::Worktree) => {}
+            }
+        }
+
+        specs
+    };
+
+    // Sizes are looked up before any content is read, so oversized files never are
+    if let Some(max) = options.max_file_bytes {
+        let specs: Vec<String> = selected
+            .iter()
+            .flat_map(|(file_path, change_type)| blob_specs(file_path, change_type))
+            .collect();
+        let mut sizes = get_blob_sizes(options, &specs)?.into_iter();
+
+        selected.retain(|(file_path, change_type)| {
+            // Consume every size of the file so the next file starts at its own
+            let count = blob_specs(file_path, change_type).len();
+            let file_sizes: Vec<Option<u64>> = sizes.by_ref().take(count).collect();
+            let blobs_too_large = file_sizes.iter().flatten().any(|size| *size > max);
+            let worktree_too_large = head.is_none()
+                && mode == DiffMode::Worktree
+                && *change_type != ChangeType::Deleted
+                && file_size(&options.repo_dir().join(file_path), options.symlinks)
+                    .is_some_and(|size| size > max);
+
+            if blobs_too_large || worktree_too_large {
+                diff.oversized_files.push(file_path.clone());
+            }
+            !(blobs_too_large || worktree_too_large)
+        });
+    }
+
+    // Every blob is requested up front so they can be read through a single git process
+    let specs: Vec<String> = selected
+        .iter()
+        .flat_map(|(file_path, change_type)| blob_specs(file_path, change_type))
+        .collect();
+
+    let mut blobs = get_blobs(options, &specs)?.into_iter();
+
+    for (file_path, change_type) in selected {
+        let base_content = match &change_type {
+            ChangeType::Added => None,
+            _ => blobs.next().flatten(),
+        };
+
+        let current_content = if change_type != ChangeType::Deleted {
+            match (head, mode) {
+                (None, DiffMode::Worktree) => {
+                    read_file_if_exists(&options.repo_dir().join(&file_path), options.symlinks)?
+                }
+                _ => blobs.next().flatten(),
+            }
+        } else {
+            None
+        };
+
+        diff.insert(file_path, base_content, current_content, change_type);
     }
+
+    diff.oversized_files.sort();
+    diff.non_utf8_files.sort();
+
+    Ok(diff)
 }
 
-fn get_changed_files(
-    base_sha: &str,
-    only_suffixes: &[String],
-) -> Result<HashMap<String, FileChange>> {
-    let mut result = HashMap::new();
+/// Ask git which selected files changed since the base, and how
+fn diff_name_status(base_sha: &str, options: &DiffOptions) -> Result<Vec<(String, ChangeType)>> {
+    let head = options.head.as_deref();
 
-    let output = Command::new("git")
-        .args(["diff", "--name-status", base_sha])
-        .output()
-        .context("Failed to execute git diff")?;
+    #[cfg(feature = "libgit")]
+    if let Some(head) = head
+        && let Some(repo) = libgit::open(options)
+    {
+        return libgit::diff_commits(&repo, base_sha, head, options);
+    }
+
+    let mut args = vec!["diff"];
+    if options.mode == DiffMode::Staged && head.is_none() {
+        args.push("--cached");
+    }
+    // Renames are detected regardless of `diff.renames`, so a renamed file's content is
+    // compared against its source instead of being reported as a deletion and an addition
+    args.extend(["--name-status", "--find-renames", "-z", base_sha]);
+    args.extend(head);
+
+    let output =
+        run(options, git(options).args(&args), None).context("Failed to execute git diff")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -59,82 +464,524 @@ fn get_changed_files(
     let diff_output =
         String::from_utf8(output.stdout).context("Git diff output is not valid UTF-8")?;
 
-    for line in diff_output.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
+    Ok(select_changes(&diff_output, options))
+}
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
+/// How each of [`DiffOptions::files`] that is selected changed since the base, judged
+/// by whether it exists on either side. Files missing on both sides are left out.
+fn list_changes(base_sha: &str, options: &DiffOptions) -> Result<Vec<(String, ChangeType)>> {
+    let head = options.head.as_deref();
 
-        if parts.len() < 2 {
-            continue;
+    if !is_commit(options, base_sha)? {
+        anyhow::bail!("Unknown base revision: {}", base_sha);
+    }
+
+    let files: Vec<&String> = options
+        .files
+        .iter()
+        .filter(|file_path| options.is_selected(file_path))
+        .collect();
+
+    // Existence on each side is read from the object sizes: the base, then the current
+    // side unless it is the working tree
+    let mut specs = Vec::new();
+    for file_path in &files {
+        specs.push(format!("{}:{}", base_sha, file_path));
+        match (head, options.mode) {
+            (Some(head), _) => specs.push(format!("{}:{}", head, file_path)),
+            (None, DiffMode::Staged) => specs.push(format!(":{}", file_path)),
+            (None, DiffMode::Worktree) => {}
         }
+    }
+    let mut sizes = get_blob_sizes(options, &specs)?.into_iter();
 
-        let status = parts[0];
-        let file_path = parts[1];
+    let mut selected = Vec::new();
+    for file_path in files {
+        let in_base = sizes.next().flatten().is_some();
+        let in_current = match (head, options.mode) {
+            (None, DiffMode::Worktree) => {
+                metadata(&options.repo_dir().join(file_path), options.symlinks)
+                    .is_some_and(|metadata| !metadata.is_dir())
+            }
+            _ => sizes.next().flatten().is_some(),
+        };
 
-        if !only_suffixes.is_empty()
-            && !only_suffixes
-                .iter()
-                .any(|suffix| file_path.ends_with(suffix))
-        {
+        let change_type = match (in_base, in_current) {
+            (true, true) => ChangeType::Modified,
+            (false, true) => ChangeType::Added,
+            (true, false) => ChangeType::Deleted,
+            (false, false) => continue,
+        };
+        selected.push((file_path.clone(), change_type));
+    }
+
+    Ok(selected)
+}
+
+/// Whether `rev` names a commit
+fn is_commit(options: &DiffOptions, rev: &str) -> Result<bool> {
+    #[cfg(feature = "libgit")]
+    if let Some(repo) = libgit::open(options) {
+        return Ok(libgit::is_commit(&repo, rev));
+    }
+
+    let output = run(
+        options,
+        git(options)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{}^{{commit}}", rev)),
+        None,
+    )
+    .context("Failed to execute git rev-parse")?;
+
+    Ok(output.status.success())
+}
+
+/// Parse `--name-status` output into the selected paths and how each changed
+fn select_changes(name_status: &str, options: &DiffOptions) -> Vec<(String, ChangeType)> {
+    let mut selected = Vec::new();
+
+    for entry in parse_name_status(name_status) {
+        if !options.is_selected(&entry.path) {
             continue;
         }
 
-        let change_type = match status {
-            "A" => ChangeType::Added,
-            "M" => ChangeType::Modified,
-            "D" => ChangeType::Deleted,
+        let change_type = match (entry.status.chars().next(), entry.source) {
+            (Some('A'), _) => ChangeType::Added,
+            (Some('M'), _) => ChangeType::Modified,
+            (Some('D'), _) => ChangeType::Deleted,
+            (Some('R'), Some(from)) => ChangeType::Renamed { from },
+            (Some('C'), Some(from)) => ChangeType::Copied { from },
             _ => ChangeType::Modified,
         };
 
-        let base_content = if change_type != ChangeType::Added {
-            get_file_content_at_ref(base_sha, file_path)?
+        selected.push((entry.path, change_type));
+    }
+
+    selected
+}
+
+/// A git command running in the repository, using the configured git executable
+fn git(options: &DiffOptions) -> Command {
+    let binary = options.git_binary.as_deref().unwrap_or(Path::new("git"));
+    let mut command = Command::new(binary);
+    command.current_dir(options.repo_dir());
+    command
+}
+
+/// Delay before the first retry of a git command, doubled for each further retry
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Git stderr fragments marking a failure worth retrying, e.g. a lock held by a
+/// concurrent git process or a hiccup of a network-backed filesystem
+const TRANSIENT_ERRORS: &[&str] = &[
+    ".lock': File exists",
+    "Resource temporarily unavailable",
+    "Stale file handle",
+    "Connection reset",
+    "Connection timed out",
+];
+
+/// A git command killed after running longer than [`DiffOptions::git_timeout`]
+#[derive(Debug, thiserror::Error)]
+#[error("git {command} timed out after {timeout:?}")]
+struct TimedOut {
+    command: String,
+    timeout: Duration,
+}
+
+/// Run a git command to completion, writing `input` to its stdin. Runs that time out
+/// or fail transiently are retried up to [`DiffOptions::git_retries`] times; the last
+/// failing output is returned so callers can report git's stderr.
+fn run(options: &DiffOptions, command: &mut Command, input: Option<&[u8]>) -> Result<Output> {
+    let mut attempt = 0;
+    loop {
+        let result = run_once(options, command, input);
+        let transient = match &result {
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                !output.status.success() && TRANSIENT_ERRORS.iter().any(|e| stderr.contains(e))
+            }
+            Err(err) => err.is::<TimedOut>(),
+        };
+        if !transient || attempt >= options.git_retries {
+            return result;
+        }
+
+        std::thread::sleep(RETRY_BACKOFF * 2u32.pow(attempt.min(8)));
+        attempt += 1;
+    }
+}
+
+fn run_once(options: &DiffOptions, command: &mut Command, input: Option<&[u8]>) -> Result<Output> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
         } else {
-            None
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write and read on other threads so neither a full pipe nor a hanging git can
+    // block us past the timeout
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => {
+            let input = input.to_vec();
+            Some(std::thread::spawn(move || stdin.write_all(&input)))
+        }
+        _ => None,
+    };
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = match options.git_timeout {
+        Some(timeout) => {
+            let deadline = Instant::now() + timeout;
+            let mut poll = Duration::from_millis(1);
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+                if Instant::now() >= deadline {
+                    // The pipe threads are left to finish once the pipes close
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    let name = command.get_args().next().unwrap_or_default();
+                    return Err(TimedOut {
+                        command: name.to_string_lossy().into_owned(),
+                        timeout,
+                    }
+                    .into());
+                }
+                std::thread::sleep(poll.min(deadline - Instant::now()));
+                poll = (poll * 2).min(Duration::from_millis(50));
+            }
+        }
+        None => child.wait()?,
+    };
+
+    let join = |handle: std::thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("Failed to read git output"))?
+            .context("Failed to read git output")
+    };
+    let output = Output {
+        status,
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    };
+    if let Some(writer) = writer {
+        writer
+            .join()
+            .map_err(|_| anyhow::anyhow!("Failed to write to git"))?
+            .context("Failed to write to git")?;
+    }
+
+    Ok(output)
+}
+
+/// Read a child's pipe to the end on another thread
+fn drain(
+    pipe: Option<impl Read + Send + 'static>,
+) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buffer)?;
+        }
+        Ok(buffer)
+    })
+}
+
+/// Find the best common ancestor of the given ref and HEAD
+fn get_merge_base(options: &DiffOptions, git_ref: &str, head: &str) -> Result<String> {
+    #[cfg(feature = "libgit")]
+    if let Some(repo) = libgit::open(options) {
+        return libgit::merge_base(&repo, git_ref, head);
+    }
+
+    let output = run(
+        options,
+        git(options).args(["merge-base", git_ref, head]),
+        None,
+    )
+    .context("Failed to execute git merge-base")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Git merge-base command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let merge_base =
+        String::from_utf8(output.stdout).context("Git merge-base output is not valid UTF-8")?;
+
+    Ok(merge_base.trim().to_string())
+}
+
+/// Name of the branch checked out in the repository, `None` on a detached HEAD
+pub fn current_branch(options: &DiffOptions) -> Result<Option<String>, JiffsError> {
+    #[cfg(feature = "libgit")]
+    if let Some(repo) = libgit::open(options) {
+        return libgit::current_branch(&repo).map_err(JiffsError::git);
+    }
+
+    let output = run(
+        options,
+        git(options).args(["rev-parse", "--abbrev-ref", "HEAD"]),
+        None,
+    )
+    .context("Failed to execute git rev-parse")
+    .map_err(JiffsError::git)?;
+
+    if !output.status.success() {
+        return Err(JiffsError::git(anyhow::anyhow!(
+            "Git rev-parse command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((branch != "HEAD").then_some(branch))
+}
+
+/// A single entry of `git diff --name-status -z` output
+#[derive(Debug, PartialEq)]
+struct NameStatusEntry {
+    status: String,
+    path: String,
+    source: Option<String>,
+}
+
+/// Parse NUL-separated `git diff --name-status -z` output. Paths are taken verbatim,
+/// so names containing spaces or tabs are preserved. Renames and copies carry both
+/// the source and the destination path. Output without NULs is read as plain
+/// `git diff --name-status`, with tab-separated fields on each line.
+fn parse_name_status(output: &str) -> Vec<NameStatusEntry> {
+    if output.contains('\0') {
+        parse_name_status_fields(output.split('\0'))
+    } else {
+        parse_name_status_fields(output.lines().flat_map(|line| line.split('\t')))
+    }
+}
+
+fn parse_name_status_fields<'a>(fields: impl Iterator<Item = &'a str>) -> Vec<NameStatusEntry> {
+    let mut entries = Vec::new();
+    let mut fields = fields.filter(|field| !field.is_empty());
+
+    while let Some(status) = fields.next() {
+        let has_source = status.starts_with('R') || status.starts_with('C');
+
+        let Some(first) = fields.next() else {
+            break;
         };
 
-        let current_content = if change_type != ChangeType::Deleted {
-            get_current_file_content(file_path)?
+        let entry = if has_source {
+            let Some(second) = fields.next() else {
+                break;
+            };
+
+            NameStatusEntry {
+                status: status.to_string(),
+                path: second.to_string(),
+                source: Some(first.to_string()),
+            }
         } else {
-            None
+            NameStatusEntry {
+                status: status.to_string(),
+                path: first.to_string(),
+                source: None,
+            }
         };
 
-        result.insert(
-            file_path.to_string(),
-            FileChange {
-                base_content,
-                current_content,
-                change_type,
-            },
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// Read blobs given as `<rev>:<path>` specs, in order. Objects that do not exist are
+/// `None`, like a failing `git show`.
+fn get_blobs(options: &DiffOptions, specs: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+    #[cfg(feature = "libgit")]
+    if let Some(repo) = libgit::open(options) {
+        return libgit::get_blobs(&repo, specs);
+    }
+
+    // `git cat-file --batch` reads one spec per line, so paths containing a newline
+    // fall back to one `git show` each
+    if specs.iter().any(|spec| spec.contains('\n')) {
+        return specs
+            .iter()
+            .map(|spec| {
+                let (git_ref, file_path) = spec.split_once(':').unwrap_or(("", spec));
+                get_file_content_at_ref(options, git_ref, file_path)
+            })
+            .collect();
+    }
+
+    if specs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let input = specs.join("\n") + "\n";
+    let output = run(
+        options,
+        git(options).args(["cat-file", "--batch"]),
+        Some(input.as_bytes()),
+    )
+    .context("Failed to execute git cat-file")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Git cat-file command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let mut stdout = BufReader::new(output.stdout.as_slice());
+    let mut blobs = Vec::with_capacity(specs.len());
+
+    for spec in specs {
+        let mut header = String::new();
+        stdout
+            .read_line(&mut header)
+            .context("Failed to read git cat-file output")?;
+
+        if header.is_empty() {
+            anyhow::bail!("git cat-file exited before returning {}", spec);
+        }
+
+        // Objects that cannot be resolved are reported as `<spec> missing` (or `ambiguous`)
+        let header = header.trim_end_matches('\n');
+        if let Some(status) = header.strip_prefix(spec.as_str())
+            && matches!(status, " missing" | " ambiguous")
+        {
+            blobs.push(None);
+            continue;
+        }
+
+        // Found objects are announced as `<oid> <type> <size>`
+        let size = header
+            .rsplit(' ')
+            .next()
+            .and_then(|size| size.parse::<usize>().ok())
+            .with_context(|| format!("Unexpected git cat-file output: {}", header))?;
+
+        // The content is followed by a newline
+        let mut content = vec![0; size + 1];
+        stdout
+            .read_exact(&mut content)
+            .context("Failed to read git cat-file output")?;
+        content.pop();
+
+        blobs.push(Some(content));
+    }
+
+    Ok(blobs)
+}
+
+/// Sizes in bytes of the objects named by `<ref>:<path>` specs, `None` for missing ones
+fn get_blob_sizes(options: &DiffOptions, specs: &[String]) -> Result<Vec<Option<u64>>> {
+    #[cfg(feature = "libgit")]
+    if let Some(repo) = libgit::open(options) {
+        return libgit::get_blob_sizes(&repo, specs);
+    }
+
+    if specs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // `git cat-file --batch-check` reads one spec per line, so paths containing a
+    // newline fall back to one `git cat-file -s` each
+    if specs.iter().any(|spec| spec.contains('\n')) {
+        return specs
+            .iter()
+            .map(|spec| {
+                let output = run(options, git(options).args(["cat-file", "-s", spec]), None)
+                    .context("Failed to execute git cat-file")?;
+
+                Ok(String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+            })
+            .collect();
+    }
+
+    let input = specs.join("\n") + "\n";
+    let output = run(
+        options,
+        git(options).args(["cat-file", "--batch-check=%(objectsize)"]),
+        Some(input.as_bytes()),
+    )
+    .context("Failed to execute git cat-file")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Git cat-file command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
         );
     }
 
-    Ok(result)
+    // Missing objects are reported as `<spec> missing` instead of a size
+    let sizes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.parse().ok())
+        .collect::<Vec<_>>();
+    if sizes.len() != specs.len() {
+        anyhow::bail!("Unexpected git cat-file output");
+    }
+
+    Ok(sizes)
 }
 
-fn get_file_content_at_ref(git_ref: &str, file_path: &str) -> Result<Option<String>> {
-    let output = Command::new("git")
-        .args(["show", &format!("{}:{}", git_ref, file_path)])
-        .output()
-        .context("Failed to execute git show")?;
+/// Metadata of a file, or of the link itself when symlinks are read as link text.
+/// `None` when it does not exist, e.g. a followed link whose target is missing.
+fn metadata(path: &Path, symlinks: SymlinkMode) -> Option<std::fs::Metadata> {
+    match symlinks {
+        SymlinkMode::Follow => std::fs::metadata(path).ok(),
+        SymlinkMode::LinkText => std::fs::symlink_metadata(path).ok(),
+    }
+}
+
+/// Size of a file in bytes, `None` when it does not exist
+fn file_size(path: &Path, symlinks: SymlinkMode) -> Option<u64> {
+    metadata(path, symlinks).map(|metadata| metadata.len())
+}
+
+fn get_file_content_at_ref(
+    options: &DiffOptions,
+    git_ref: &str,
+    file_path: &str,
+) -> Result<Option<Vec<u8>>> {
+    let output = run(
+        options,
+        git(options).args(["show", &format!("{}:{}", git_ref, file_path)]),
+        None,
+    )
+    .context("Failed to execute git show")?;
 
     if !output.status.success() {
         return Ok(None);
     }
 
-    let content = String::from_utf8(output.stdout).context("File content is not valid UTF-8")?;
-
-    Ok(Some(content))
+    Ok(Some(output.stdout))
 }
 
-fn get_current_file_content(file_path: &str) -> Result<Option<String>> {
-    if !Path::new(file_path).exists() {
+fn read_file_if_exists(path: &Path, symlinks: SymlinkMode) -> Result<Option<Vec<u8>>> {
+    let Some(metadata) = metadata(path, symlinks) else {
         return Ok(None);
+    };
+
+    if metadata.is_symlink() {
+        let target = std::fs::read_link(path)
+            .with_context(|| format!("Failed to read symlink: {}", path.display()))?;
+        return Ok(Some(target.into_os_string().into_encoded_bytes()));
     }
 
-    let content = std::fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path))?;
+    let content =
+        std::fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
 
     Ok(Some(content))
 }
@@ -150,6 +997,15 @@ mod tests {
         assert_eq!(ChangeType::Deleted, ChangeType::Deleted);
     }
 
+    #[test]
+    fn test_change_type_display() {
+        let renamed = ChangeType::Renamed {
+            from: "old.yaml".to_string(),
+        };
+        assert_eq!(renamed.to_string(), "renamed");
+        assert_eq!(ChangeType::Added.to_string(), "added");
+    }
+
     #[test]
     fn test_file_change_creation() {
         let file_change = FileChange {
@@ -162,4 +1018,145 @@ mod tests {
         assert!(file_change.current_content.is_some());
         assert_eq!(file_change.change_type, ChangeType::Modified);
     }
+
+    #[test]
+    fn test_parse_name_status_preserves_spaces() {
+        let output = "M\0config files/app.yaml\0A\0apps/nested dir/values.yaml\0D\0old.yaml\0";
+        let entries = parse_name_status(output);
+
+        assert_eq!(
+            entries,
+            vec![
+                NameStatusEntry {
+                    status: "M".to_string(),
+                    path: "config files/app.yaml".to_string(),
+                    source: None,
+                },
+                NameStatusEntry {
+                    status: "A".to_string(),
+                    path: "apps/nested dir/values.yaml".to_string(),
+                    source: None,
+                },
+                NameStatusEntry {
+                    status: "D".to_string(),
+                    path: "old.yaml".to_string(),
+                    source: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_name_status_rename_keeps_entries_aligned() {
+        let output = "R100\0old name.yaml\0new name.yaml\0M\0other.yaml\0";
+        let entries = parse_name_status(output);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "new name.yaml");
+        assert_eq!(entries[0].source.as_deref(), Some("old name.yaml"));
+        assert_eq!(entries[1].path, "other.yaml");
+    }
+
+    #[test]
+    fn test_parse_name_status_plain_lines() {
+        let output = "M\tconfig files/app.yaml\nR087\told.yaml\tnew.yaml\n";
+        let entries = parse_name_status(output);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "config files/app.yaml");
+        assert_eq!(entries[1].path, "new.yaml");
+        assert_eq!(entries[1].source.as_deref(), Some("old.yaml"));
+    }
+
+    #[test]
+    fn test_diff_from_name_status_reads_content_trees() {
+        let base = tempfile::tempdir().unwrap();
+        let current = tempfile::tempdir().unwrap();
+
+        std::fs::write(base.path().join("app.yaml"), "revision: 1\n").unwrap();
+        std::fs::write(current.path().join("app.yaml"), "revision: 2\n").unwrap();
+        std::fs::write(base.path().join("old.yaml"), "kind: Old\n").unwrap();
+        std::fs::write(current.path().join("new.yaml"), "kind: New\n").unwrap();
+
+        let name_status = "M\0app.yaml\0D\0old.yaml\0A\0new.yaml\0";
+        let diff = GitDiff::from_name_status(
+            name_status,
+            base.path(),
+            current.path(),
+            &DiffOptions::default(),
+        )
+        .unwrap();
+
+        let modified = &diff.changed_files["app.yaml"];
+        assert_eq!(modified.change_type, ChangeType::Modified);
+        assert_eq!(modified.base_content.as_deref(), Some("revision: 1\n"));
+        assert_eq!(modified.current_content.as_deref(), Some("revision: 2\n"));
+
+        let deleted = &diff.changed_files["old.yaml"];
+        assert_eq!(deleted.change_type, ChangeType::Deleted);
+        assert_eq!(deleted.base_content.as_deref(), Some("kind: Old\n"));
+        assert!(deleted.current_content.is_none());
+
+        let added = &diff.changed_files["new.yaml"];
+        assert!(added.base_content.is_none());
+        assert_eq!(added.current_content.as_deref(), Some("kind: New\n"));
+    }
+
+    #[test]
+    fn test_diff_from_dir_adds_every_selected_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("apps/web")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+
+        std::fs::write(dir.path().join("apps/web/values.yaml"), "replicas: 2\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "# Apps\n").unwrap();
+        std::fs::write(dir.path().join(".git/config.yaml"), "core: {}\n").unwrap();
+
+        let options = DiffOptions {
+            only_suffixes: vec![".yaml".to_string()],
+            ..Default::default()
+        };
+        let diff = GitDiff::from_dir(dir.path(), &options).unwrap();
+
+        assert_eq!(diff.changed_file_paths(), ["apps/web/values.yaml"]);
+        let added = &diff.changed_files["apps/web/values.yaml"];
+        assert_eq!(added.change_type, ChangeType::Added);
+        assert!(added.base_content.is_none());
+        assert_eq!(added.current_content.as_deref(), Some("replicas: 2\n"));
+    }
+
+    #[test]
+    fn test_file_selection() {
+        let options = DiffOptions {
+            only_suffixes: vec![".yml".to_string()],
+            include: GlobSet::new(&["apps/**/*.yaml"]).unwrap(),
+            exclude: GlobSet::new(&["tests/**", "apps/legacy/**"]).unwrap(),
+            ..Default::default()
+        };
+
+        assert!(options.is_selected("apps/web/values.yaml"));
+        assert!(options.is_selected("other/config.yml"));
+        assert!(!options.is_selected("other/config.yaml"));
+        assert!(!options.is_selected("tests/fixtures/app.yml"));
+        assert!(!options.is_selected("apps/legacy/values.yaml"));
+
+        assert!(DiffOptions::default().is_selected("anything.txt"));
+    }
+
+    #[test]
+    fn test_path_prefix_selection() {
+        let options = DiffOptions {
+            path_prefixes: vec!["clusters/prod".to_string(), "./shared/".to_string()],
+            only_suffixes: vec![".yaml".to_string()],
+            ..Default::default()
+        };
+
+        assert!(options.is_selected("clusters/prod/app.yaml"));
+        assert!(options.is_selected("clusters/prod/nested/app.yaml"));
+        assert!(options.is_selected("shared/base.yaml"));
+        assert!(!options.is_selected("clusters/prod-staging/app.yaml"));
+        assert!(!options.is_selected("clusters/prod"));
+        assert!(!options.is_selected("clusters/prod/README.md"));
+        assert!(!options.is_selected("other/app.yaml"));
+    }
 }
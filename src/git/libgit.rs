@@ -0,0 +1,243 @@
+//! Reading the repository in-process with gix, with the `libgit` feature. Which files
+//! changed against the working tree or the index, repositories gix cannot open, and
+//! every command when [`DiffOptions::git_binary`] is given, still use the git executable.
+
+use anyhow::{Context, Result};
+use gix::bstr::{BStr, ByteSlice};
+
+use super::{ChangeType, DiffOptions};
+
+/// The repository [`DiffOptions::repo_dir`] belongs to, `None` when gix cannot open it
+/// or a git executable is configured
+pub(super) fn open(options: &DiffOptions) -> Option<gix::Repository> {
+    if options.git_binary.is_some() {
+        return None;
+    }
+
+    gix::discover(options.repo_dir()).ok()
+}
+
+/// The object a `<rev>:<path>` or `:<path>` spec names, `None` when it does not resolve
+fn resolve(repo: &gix::Repository, spec: &str) -> Option<gix::ObjectId> {
+    repo.rev_parse_single(BStr::new(spec))
+        .ok()
+        .map(|id| id.detach())
+}
+
+/// Read blobs given as `<rev>:<path>` specs, in order. Specs that do not name a blob
+/// are `None`, like a failing `git show`.
+pub(super) fn get_blobs(repo: &gix::Repository, specs: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+    specs
+        .iter()
+        .map(|spec| {
+            let Some(id) = resolve(repo, spec) else {
+                return Ok(None);
+            };
+            let object = repo
+                .find_object(id)
+                .with_context(|| format!("Failed to read {}", spec))?;
+
+            Ok((object.kind == gix::object::Kind::Blob).then(|| object.detach().data))
+        })
+        .collect()
+}
+
+/// Sizes in bytes of the blobs named by `<rev>:<path>` specs, `None` for missing ones
+pub(super) fn get_blob_sizes(repo: &gix::Repository, specs: &[String]) -> Result<Vec<Option<u64>>> {
+    specs
+        .iter()
+        .map(|spec| {
+            let Some(id) = resolve(repo, spec) else {
+                return Ok(None);
+            };
+            let header = repo
+                .find_header(id)
+                .with_context(|| format!("Failed to read {}", spec))?;
+
+            Ok((header.kind() == gix::object::Kind::Blob).then(|| header.size()))
+        })
+        .collect()
+}
+
+/// Whether `rev` names a commit
+pub(super) fn is_commit(repo: &gix::Repository, rev: &str) -> bool {
+    resolve(repo, &format!("{}^{{commit}}", rev)).is_some()
+}
+
+/// Find the best common ancestor of two commits
+pub(super) fn merge_base(repo: &gix::Repository, one: &str, two: &str) -> Result<String> {
+    let commit = |rev: &str| {
+        resolve(repo, &format!("{}^{{commit}}", rev))
+            .with_context(|| format!("Unknown revision: {}", rev))
+    };
+
+    let merge_base = repo
+        .merge_base(commit(one)?, commit(two)?)
+        .with_context(|| format!("Failed to find the merge base of {} and {}", one, two))?
+        .with_context(|| format!("{} and {} have no merge base", one, two))?;
+
+    Ok(merge_base.to_string())
+}
+
+/// The selected files that differ between the trees of two commits, and how, with
+/// renames detected like `git diff --find-renames`
+pub(super) fn diff_commits(
+    repo: &gix::Repository,
+    base: &str,
+    head: &str,
+    options: &DiffOptions,
+) -> Result<Vec<(String, ChangeType)>> {
+    let tree = |rev: &str| -> Result<gix::Tree<'_>> {
+        let id = resolve(repo, &format!("{}^{{tree}}", rev))
+            .with_context(|| format!("Unknown revision: {}", rev))?;
+        Ok(repo.find_tree(id)?)
+    };
+    let (base_tree, head_tree) = (tree(base)?, tree(head)?);
+
+    let diff_options =
+        gix::diff::Options::default().with_rewrites(Some(gix::diff::Rewrites::default()));
+    let changes = repo
+        .diff_tree_to_tree(&base_tree, &head_tree, diff_options)
+        .with_context(|| format!("Failed to diff {} and {}", base, head))?;
+
+    let path = |location: &BStr| location.to_str_lossy().into_owned();
+    let mut selected = Vec::new();
+    for change in changes {
+        // Trees are descended into, so only the files they contain are reported
+        if change.entry_mode().is_tree() {
+            continue;
+        }
+
+        use gix::object::tree::diff::ChangeDetached as Change;
+        let (file_path, change_type) = match change {
+            Change::Addition { location, .. } => (path(location.as_bstr()), ChangeType::Added),
+            Change::Deletion { location, .. } => (path(location.as_bstr()), ChangeType::Deleted),
+            Change::Modification { location, .. } => {
+                (path(location.as_bstr()), ChangeType::Modified)
+            }
+            Change::Rewrite {
+                source_location,
+                location,
+                copy,
+                ..
+            } => {
+                let from = path(source_location.as_bstr());
+                let change_type = if copy {
+                    ChangeType::Copied { from }
+                } else {
+                    ChangeType::Renamed { from }
+                };
+                (path(location.as_bstr()), change_type)
+            }
+        };
+
+        if options.is_selected(&file_path) {
+            selected.push((file_path, change_type));
+        }
+    }
+
+    selected.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(selected)
+}
+
+/// Name of the branch checked out in the repository, `None` on a detached HEAD
+pub(super) fn current_branch(repo: &gix::Repository) -> Result<Option<String>> {
+    let head = repo.head_name().context("Failed to read HEAD")?;
+    Ok(head.map(|name| name.shorten().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_reads_the_repository_without_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        git(dir, &["init", "-q", "-b", "main"]);
+        git(dir, &["config", "user.email", "test@example.com"]);
+        git(dir, &["config", "user.name", "test"]);
+        std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\nreplicas: 3\n").unwrap();
+        std::fs::write(dir.join("old.yaml"), "a: 1\nb: 2\nc: 3\nd: 4\n").unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", "base"]);
+        git(dir, &["switch", "-q", "-c", "feature"]);
+        std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\nreplicas: 3\n").unwrap();
+        git(dir, &["mv", "old.yaml", "new.yaml"]);
+        git(dir, &["commit", "-q", "-am", "change"]);
+        std::fs::write(dir.join("app.yaml"), "revision: 2.0.0\n").unwrap();
+        git(dir, &["add", "app.yaml"]);
+
+        let options = DiffOptions {
+            repo: Some(dir.to_path_buf()),
+            ..Default::default()
+        };
+        let repo = open(&options).unwrap();
+
+        let specs = [
+            "main:app.yaml",
+            "HEAD:app.yaml",
+            ":app.yaml",
+            "main:missing",
+        ]
+        .map(String::from);
+        let blobs = get_blobs(&repo, &specs).unwrap();
+        let blobs: Vec<_> = blobs
+            .iter()
+            .map(|blob| {
+                blob.as_deref()
+                    .map(|b| String::from_utf8_lossy(b).into_owned())
+            })
+            .collect();
+        assert_eq!(
+            blobs,
+            [
+                Some("revision: 1.0.0\nreplicas: 3\n".to_string()),
+                Some("revision: 1.1.0\nreplicas: 3\n".to_string()),
+                Some("revision: 2.0.0\n".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            get_blob_sizes(&repo, &specs).unwrap(),
+            [Some(28), Some(28), Some(16), None]
+        );
+
+        assert_eq!(
+            diff_commits(&repo, "main", "feature", &options).unwrap(),
+            [
+                ("app.yaml".to_string(), ChangeType::Modified),
+                (
+                    "new.yaml".to_string(),
+                    ChangeType::Renamed {
+                        from: "old.yaml".to_string()
+                    }
+                ),
+            ]
+        );
+
+        let main = resolve(&repo, "main").unwrap().to_string();
+        assert_eq!(merge_base(&repo, "main", "feature").unwrap(), main);
+        assert!(is_commit(&repo, "main"));
+        assert!(!is_commit(&repo, "nope"));
+        assert_eq!(current_branch(&repo).unwrap().as_deref(), Some("feature"));
+
+        // A configured git executable is used instead
+        let options = DiffOptions {
+            git_binary: Some("git".into()),
+            ..options
+        };
+        assert!(open(&options).is_none());
+    }
+}
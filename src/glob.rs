@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use globset::GlobBuilder;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A shell-style glob matched against `/`-separated paths, backed by `globset`
+///
+/// - `*` matches any sequence of characters except `/`
+/// - `?` matches a single character except `/`
+/// - `**` as a whole segment (`**/`, `/**/` or `/**`) matches any number of
+///   directories, including none; within a segment it acts like `*`
+/// - `\` escapes the character after it
+/// - `[abc]`, `[a-z]` and `[!abc]` match a single character from (or not from) a set
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    matcher: globset::GlobSet,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut builder = globset::GlobSetBuilder::new();
+        builder.add(compile(pattern)?);
+        // globset leaves the directory itself out of a trailing `/**`
+        if let Some(dir) = pattern.strip_suffix("/**").filter(|dir| !dir.is_empty()) {
+            builder.add(compile(dir)?);
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            matcher: builder
+                .build()
+                .with_context(|| format!("Invalid glob pattern: {}", pattern))?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.matcher.is_match(path)
+    }
+}
+
+/// A set of globs that matches a path if any of its globs does. In policies it is
+/// written as a list of patterns, compiled when the policy is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct GlobSet {
+    globs: Vec<Glob>,
+}
+
+impl GlobSet {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self> {
+        let globs = patterns
+            .iter()
+            .map(|pattern| Glob::new(pattern.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { globs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.globs.iter().any(|glob| glob.is_match(path))
+    }
+
+    /// The patterns the set was built from, in order
+    pub fn patterns(&self) -> Vec<&str> {
+        self.globs.iter().map(Glob::as_str).collect()
+    }
+}
+
+impl Serialize for GlobSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.patterns().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GlobSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let patterns = Vec::<String>::deserialize(deserializer)?;
+        Self::new(&patterns).map_err(|err| serde::de::Error::custom(format!("{:#}", err)))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for GlobSet {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "GlobSet".into()
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        Vec::<String>::json_schema(generator)
+    }
+}
+
+fn compile(pattern: &str) -> Result<globset::Glob> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Invalid glob pattern: {}", pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn test_single_star_stays_within_segment() {
+        assert!(matches("*.yaml", "app.yaml"));
+        assert!(!matches("*.yaml", "apps/app.yaml"));
+        assert!(matches("apps/*/values.yaml", "apps/web/values.yaml"));
+        assert!(!matches("apps/*/values.yaml", "apps/web/prod/values.yaml"));
+        assert!(matches("app?.yaml", "app1.yaml"));
+        assert!(!matches("app.yaml", "appXyaml"));
+    }
+
+    #[test]
+    fn test_double_star() {
+        assert!(matches("apps/**/values.yaml", "apps/values.yaml"));
+        assert!(matches("apps/**/values.yaml", "apps/web/prod/values.yaml"));
+        assert!(matches("**/*.yaml", "app.yaml"));
+        assert!(matches("**/*.yaml", "a/b/app.yaml"));
+        assert!(matches("tests/**", "tests/fixtures/app.yaml"));
+        assert!(matches("tests/**", "tests"));
+        assert!(!matches("tests/**", "testsuite/app.yaml"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(matches("env-[ab].yaml", "env-a.yaml"));
+        assert!(!matches("env-[ab].yaml", "env-c.yaml"));
+        assert!(matches("env-[!ab].yaml", "env-c.yaml"));
+        assert!(matches("v[0-9].yaml", "v7.yaml"));
+        assert!(Glob::new("env-[ab.yaml").is_err());
+    }
+
+    #[test]
+    fn test_literals_and_dotfiles() {
+        // Leading dots are not special, and `\\` escapes a metacharacter
+        assert!(matches("*.yaml", ".hidden.yaml"));
+        assert!(matches("apps/\\*.yaml", "apps/*.yaml"));
+        assert!(!matches("apps/\\*.yaml", "apps/web.yaml"));
+        // `**` inside a segment does not cross directories
+        assert!(matches("apps/a**.yaml", "apps/abc.yaml"));
+        assert!(!matches("apps/a**.yaml", "apps/a/b.yaml"));
+    }
+
+    #[test]
+    fn test_glob_set() {
+        let set = GlobSet::new(&["*.json", "apps/**"]).unwrap();
+
+        assert!(set.is_match("a.json"));
+        assert!(set.is_match("apps/x/y.yaml"));
+        assert!(!set.is_match("other/y.yaml"));
+        assert!(GlobSet::default().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use ::ignore::gitignore::{Gitignore, GitignoreBuilder};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Name of the ignore file looked up at the repository root
+pub const IGNORE_FILE_NAME: &str = ".jiffsignore";
+
+/// Patterns in gitignore syntax excluding changed files from validation, matched with
+/// the `ignore` crate exactly as git would
+///
+/// - Blank lines and lines starting with `#` are skipped
+/// - `!` re-includes paths an earlier pattern ignored, unless a parent directory is ignored
+/// - A trailing `/` only matches directories
+/// - A pattern with a `/` at the start or in the middle is anchored to the ignore file's
+///   directory; otherwise it matches at any depth
+/// - Ignoring a directory ignores everything below it
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    /// Directory of the ignore file relative to the repository root, without slashes
+    /// at either end, or empty for the root
+    base: String,
+    matcher: Gitignore,
+}
+
+impl Default for IgnoreFile {
+    fn default() -> Self {
+        Self {
+            base: String::new(),
+            matcher: Gitignore::empty(),
+        }
+    }
+}
+
+impl IgnoreFile {
+    /// Read an ignore file whose patterns are relative to `base`, a directory relative
+    /// to the repository root
+    pub fn from_file(path: &Path, base: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ignore file: {:?}", path))?;
+
+        Self::parse(&content, base).with_context(|| format!("Invalid ignore file: {:?}", path))
+    }
+
+    pub fn parse(content: &str, base: &str) -> Result<Self> {
+        // Paths are matched relative to the ignore file's directory, so the matcher's
+        // own root never needs stripping
+        let mut builder = GitignoreBuilder::new("");
+        for line in content.lines() {
+            builder.add_line(None, line)?;
+        }
+
+        Ok(Self {
+            base: base.trim_matches('/').to_string(),
+            matcher: builder.build()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matcher.is_empty()
+    }
+
+    /// Whether a file, given relative to the repository root, is ignored
+    pub fn is_ignored(&self, file_path: &str) -> bool {
+        let relative = if self.base.is_empty() {
+            file_path
+        } else {
+            match file_path
+                .strip_prefix(self.base.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(relative) => relative,
+                None => return false,
+            }
+        };
+
+        // Like git, nothing below an ignored directory can be re-included, so the
+        // directories are checked from the top before the file itself
+        let directories = relative.match_indices('/').map(|(end, _)| &relative[..end]);
+        for directory in directories {
+            if self.matcher.matched(directory, true).is_ignore() {
+                return true;
+            }
+        }
+
+        self.matcher.matched(relative, false).is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unanchored_patterns_match_at_any_depth() {
+        let ignore = IgnoreFile::parse("# generated\n*.sealed.yaml\n\nbuild/\n", "").unwrap();
+
+        assert!(ignore.is_ignored("secret.sealed.yaml"));
+        assert!(ignore.is_ignored("apps/web/secret.sealed.yaml"));
+        assert!(ignore.is_ignored("build/app.yaml"));
+        assert!(ignore.is_ignored("apps/build/app.yaml"));
+        assert!(!ignore.is_ignored("apps/web/values.yaml"));
+        // A trailing slash only matches directories
+        assert!(
+            !IgnoreFile::parse("build/\n", "")
+                .unwrap()
+                .is_ignored("build")
+        );
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let ignore = IgnoreFile::parse("/generated.yaml\napps/*/out.yaml\n", "").unwrap();
+
+        assert!(ignore.is_ignored("generated.yaml"));
+        assert!(!ignore.is_ignored("apps/generated.yaml"));
+        assert!(ignore.is_ignored("apps/web/out.yaml"));
+        assert!(!ignore.is_ignored("other/apps/web/out.yaml"));
+    }
+
+    #[test]
+    fn test_negation() {
+        let ignore =
+            IgnoreFile::parse("*.yaml\n!keep.yaml\nsecrets/\n!secrets/keep.yaml\n", "").unwrap();
+
+        assert!(ignore.is_ignored("app.yaml"));
+        assert!(!ignore.is_ignored("keep.yaml"));
+        // Files below an ignored directory cannot be re-included
+        assert!(ignore.is_ignored("secrets/keep.yaml"));
+    }
+
+    #[test]
+    fn test_git_semantics() {
+        // A directory pattern anchored by a leading or middle slash
+        let ignore = IgnoreFile::parse("/build/\napps/gen\n", "").unwrap();
+        assert!(ignore.is_ignored("build/app.yaml"));
+        assert!(!ignore.is_ignored("apps/build/app.yaml"));
+        assert!(ignore.is_ignored("apps/gen/app.yaml"));
+        assert!(!ignore.is_ignored("other/apps/gen/app.yaml"));
+
+        // Ignoring a directory's contents rather than the directory allows re-including
+        let ignore = IgnoreFile::parse("secrets/*\n!secrets/keep.yaml\n", "").unwrap();
+        assert!(ignore.is_ignored("secrets/token.yaml"));
+        assert!(!ignore.is_ignored("secrets/keep.yaml"));
+
+        // `**` in each position, and escaped metacharacters
+        let ignore = IgnoreFile::parse(
+            "**/out/*.yaml\ncharts/**/gen.yaml\nlogs/**\n\\#notes.yaml\n",
+            "",
+        )
+        .unwrap();
+        assert!(ignore.is_ignored("a/b/out/app.yaml"));
+        assert!(ignore.is_ignored("charts/gen.yaml"));
+        assert!(ignore.is_ignored("charts/web/prod/gen.yaml"));
+        assert!(ignore.is_ignored("logs/deep/app.yaml"));
+        assert!(ignore.is_ignored("#notes.yaml"));
+        assert!(!ignore.is_ignored("a/out/deeper/app.yaml"));
+    }
+
+    #[test]
+    fn test_patterns_are_relative_to_the_base() {
+        let ignore = IgnoreFile::parse("/out.yaml\n", "clusters/prod").unwrap();
+
+        assert!(ignore.is_ignored("clusters/prod/out.yaml"));
+        assert!(!ignore.is_ignored("out.yaml"));
+        assert!(!ignore.is_ignored("clusters/prod-staging/out.yaml"));
+    }
+}
@@ -1,28 +1,330 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use json_patch::diff;
 use jsonptr::Pointer;
 use regex::Regex;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::config::{Granularity, LengthBounds, MatchMode, OnMissing, Operator, PathValue};
+use crate::version::Version;
 
 pub type ChangeMap = HashMap<String, (Option<Value>, Option<Value>)>;
 
+/// Deepest nesting of objects and arrays that is traversed, matching the limit of the
+/// YAML and JSON parsers, so crafted documents fail cleanly instead of overflowing the stack
+pub const MAX_DEPTH: usize = 128;
+
+/// What a change did to the value at its path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Addition,
+    Removal,
+    Replacement,
+}
+
+impl ChangeKind {
+    /// Classify a change by which of its old and new values are present
+    pub fn of((old, new): &(Option<Value>, Option<Value>)) -> Self {
+        match (old, new) {
+            (None, _) => ChangeKind::Addition,
+            (Some(_), None) => ChangeKind::Removal,
+            (Some(_), Some(_)) => ChangeKind::Replacement,
+        }
+    }
+}
+
+/// A change pattern that may only authorize some kinds of change, or some new values
+pub trait ChangePattern: AsRef<str> {
+    fn covers(&self, _kind: ChangeKind) -> bool {
+        true
+    }
+
+    fn accepts(&self, _value: &Value) -> bool {
+        true
+    }
+}
+
+impl ChangePattern for String {}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Addition => write!(f, "addition"),
+            ChangeKind::Removal => write!(f, "removal"),
+            ChangeKind::Replacement => write!(f, "modification"),
+        }
+    }
+}
+
+/// Why a condition does not hold for a document
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionFailure {
+    /// The path resolves to no value
+    PathNotFound,
+    /// The path resolves, but not to a value the condition accepts
+    ValueMismatch { found: Vec<Value> },
+}
+
+impl fmt::Display for ConditionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConditionFailure::PathNotFound => write!(f, "path not found"),
+            ConditionFailure::ValueMismatch { found } => {
+                let found: Vec<String> = found.iter().map(Value::to_string).collect();
+                write!(f, "found {}", found.join(", "))
+            }
+        }
+    }
+}
+
 pub struct JsonPathMatcher;
 
+/// Collects why changes were accepted or rejected, for `--explain` output. A disabled
+/// collector discards notes without formatting them.
+#[derive(Debug, Default)]
+pub struct Explanation {
+    enabled: bool,
+    depth: usize,
+    lines: Vec<String>,
+}
+
+impl Explanation {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Record a line at the given depth, relative to the current indentation
+    pub fn note(&mut self, depth: usize, line: impl FnOnce() -> String) {
+        if self.enabled {
+            self.lines
+                .push(format!("{}{}", "  ".repeat(self.depth + depth), line()));
+        }
+    }
+
+    /// Nest subsequent notes one level deeper
+    pub fn indent(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn dedent(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
 impl JsonPathMatcher {
-    /// Check if a JSON document matches all the given path-value conditions
-    pub fn matches_conditions(json: &Value, conditions: &[crate::config::PathValue]) -> bool {
-        conditions
-            .iter()
-            .all(|condition| Self::matches_condition(json, &condition.path, &condition.value))
+    /// Check if a JSON document matches all the given path-value conditions. A condition
+    /// whose path holds wildcards holds when any value it resolves to compares to the
+    /// expected value, or every value with `matchMode: all`. Values that cannot be
+    /// compared, e.g. a string ordered against a number, are an error.
+    pub fn matches_conditions(
+        json: &Value,
+        conditions: &[crate::config::PathValue],
+    ) -> Result<bool> {
+        for condition in conditions {
+            let holds = match Self::missing_outcome(json, &condition.path, condition) {
+                Some(outcome) => outcome.unwrap_or(true),
+                None => {
+                    Self::matches_path_condition(json, &condition.path, condition)?
+                        != condition.negate
+                }
+            };
+            if !holds {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Why a condition does not hold for a document, or `None` when it holds. Agrees
+    /// with [`Self::matches_conditions`] on every condition.
+    pub fn condition_failure(
+        json: &Value,
+        condition: &crate::config::PathValue,
+    ) -> Result<Option<ConditionFailure>> {
+        if Self::matches_conditions(json, std::slice::from_ref(condition))? {
+            return Ok(None);
+        }
+
+        let found = Self::get_values_at_path(json, &condition.path).unwrap_or_default();
+        Ok(Some(if found.is_empty() {
+            ConditionFailure::PathNotFound
+        } else {
+            ConditionFailure::ValueMismatch { found }
+        }))
+    }
+
+    /// The outcome of a condition whose path does not resolve in the document, or
+    /// `None` when the path is present. `Some(None)` means the condition is skipped.
+    /// `onMissing` decides the comparison, which `negate` then inverts as usual. An
+    /// `exists` condition is always decided here, by whether the path is present.
+    fn missing_outcome(
+        json: &Value,
+        path: &str,
+        condition: &crate::config::PathValue,
+    ) -> Option<Option<bool>> {
+        let present = Self::get_values_at_path(json, path).is_ok_and(|values| !values.is_empty());
+        if let Some(exists) = condition.exists {
+            return Some(Some((present == exists) != condition.negate));
+        }
+        if present {
+            return None;
+        }
+
+        Some(match condition.on_missing {
+            OnMissing::Fail => Some(condition.negate),
+            OnMissing::Pass => Some(!condition.negate),
+            OnMissing::Skip => None,
+        })
     }
 
     /// Check if a JSON document matches a single path-value condition (supports wildcards)
-    pub fn matches_condition(json: &Value, path: &str, expected_value: &Value) -> bool {
-        match Self::get_values_at_path(json, path) {
-            Ok(values) => values.iter().any(|v| v == expected_value),
-            Err(_) => false,
+    pub fn matches_condition(json: &Value, path: &str, expected_value: &Value) -> Result<bool> {
+        Self::matches_condition_with_op(json, path, Operator::Eq, expected_value)
+    }
+
+    /// Check if any value at a path (supports wildcards) compares to the expected value
+    /// using the given operator. Values that cannot be compared are an error.
+    pub fn matches_condition_with_op(
+        json: &Value,
+        path: &str,
+        op: Operator,
+        expected_value: &Value,
+    ) -> Result<bool> {
+        let Ok(values) = Self::get_values_at_path(json, path) else {
+            return Ok(false);
+        };
+
+        for value in &values {
+            if Self::compare(value, op, expected_value)
+                .with_context(|| format!("Condition on {} cannot be evaluated", path))?
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Check if any value at a path (supports wildcards), or every value with
+    /// `matchMode: all`, satisfies a condition's comparison, ignoring `negate` and
+    /// `onMissing`. `all` needs at least one value. Values that cannot be compared are
+    /// an error.
+    fn matches_path_condition(json: &Value, path: &str, condition: &PathValue) -> Result<bool> {
+        let Ok(values) = Self::get_values_at_path(json, path) else {
+            return Ok(false);
+        };
+        if condition.match_mode == MatchMode::All && values.is_empty() {
+            return Ok(false);
+        }
+
+        for value in &values {
+            let holds = Self::condition_holds(value, condition)
+                .with_context(|| format!("Condition {} cannot be evaluated", condition))?;
+            match condition.match_mode {
+                MatchMode::Any if holds => return Ok(true),
+                MatchMode::All if !holds => return Ok(false),
+                _ => {}
+            }
+        }
+
+        Ok(condition.match_mode == MatchMode::All)
+    }
+
+    /// Evaluate a condition's comparison against a value found at its path
+    fn condition_holds(actual: &Value, condition: &PathValue) -> Result<bool> {
+        match &condition.length {
+            Some(length) => Self::matches_length(actual, length),
+            None => Self::compare(actual, condition.op, &condition.value),
+        }
+    }
+
+    /// Check the number of elements of an array or object against length bounds
+    pub fn matches_length(actual: &Value, length: &LengthBounds) -> Result<bool> {
+        let len = match actual {
+            Value::Array(items) => items.len(),
+            Value::Object(fields) => fields.len(),
+            other => anyhow::bail!(
+                "Cannot take the length of {}: not an array or object",
+                other
+            ),
+        };
+
+        for (op, bound) in length.bounds() {
+            if !Self::compare(&Value::from(len), op, &Value::from(bound))? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Test a value against a regex. Strings are matched as-is; other values are
+    /// matched against their JSON text, e.g. `3`, `true` or `null`.
+    fn matches_regex(actual: &Value, pattern: &Value) -> Result<bool> {
+        let Some(pattern) = pattern.as_str() else {
+            anyhow::bail!("Regex must be a string, got {}", pattern);
+        };
+        let regex = Regex::new(pattern)?;
+
+        Ok(match actual {
+            Value::String(text) => regex.is_match(text),
+            other => regex.is_match(&other.to_string()),
+        })
+    }
+
+    /// Compare an actual value against an expected value. Numbers compare numerically and
+    /// strings lexicographically; ordering any other combination of values is an error.
+    pub fn compare(actual: &Value, op: Operator, expected: &Value) -> Result<bool> {
+        match op {
+            Operator::Eq => return Ok(actual == expected),
+            Operator::Ne => return Ok(actual != expected),
+            Operator::Regex => return Self::matches_regex(actual, expected),
+            _ => {}
         }
+
+        let ordering = match (actual, expected) {
+            (Value::Number(a), Value::Number(b)) => a
+                .as_f64()
+                .zip(b.as_f64())
+                .and_then(|(a, b)| a.partial_cmp(&b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+
+        let Some(ordering) = ordering else {
+            anyhow::bail!(
+                "Cannot compare {} {} {}: operator requires two numbers or two strings",
+                actual,
+                op,
+                expected
+            );
+        };
+
+        Ok(match op {
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Eq | Operator::Ne | Operator::Regex => unreachable!(),
+        })
     }
 
     /// Get all values at a given JSON path (supports wildcards) using JSON Pointer expansion
@@ -34,7 +336,7 @@ impl JsonPathMatcher {
             format!("/{}", path)
         };
 
-        if normalized_path.contains('*') {
+        if Self::has_wildcards(&normalized_path) {
             Self::expand_wildcard_paths(json, &normalized_path)
         } else {
             match Self::get_value_at_json_pointer(json, &normalized_path) {
@@ -44,13 +346,34 @@ impl JsonPathMatcher {
         }
     }
 
-    /// Expand wildcard paths by finding all matching array indices
+    /// Whether a path holds `*`, `**` or an index range, and so may resolve to several values
+    fn has_wildcards(path: &str) -> bool {
+        path.contains('*')
+            || path
+                .split('/')
+                .any(|segment| IndexRange::parse(segment).is_some())
+    }
+
+    /// Expand wildcard paths by finding all matching array indices and object keys.
+    /// Each matching location is reported once, in document order, even when several
+    /// expansions reach it (e.g. `/**/**/name`).
     fn expand_wildcard_paths(json: &Value, wildcard_path: &str) -> Result<Vec<Value>> {
         let mut results = Vec::new();
-        let path_parts: Vec<&str> = wildcard_path.split('/').filter(|s| !s.is_empty()).collect();
+        let path_parts: Vec<String> = wildcard_path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(decode_segment)
+            .collect();
+        let path_parts: Vec<&str> = path_parts.iter().map(String::as_str).collect();
 
         Self::find_wildcard_matches(json, &path_parts, 0, "", &mut results)?;
-        Ok(results)
+
+        let mut seen = HashSet::new();
+        Ok(results
+            .into_iter()
+            .filter(|(path, _)| seen.insert(path.clone()))
+            .map(|(_, value)| value)
+            .collect())
     }
 
     /// Recursively find all paths that match the wildcard pattern
@@ -59,17 +382,50 @@ impl JsonPathMatcher {
         path_parts: &[&str],
         part_index: usize,
         current_path: &str,
-        results: &mut Vec<Value>,
+        results: &mut Vec<(String, Value)>,
     ) -> Result<()> {
+        if current_path.matches('/').count() > MAX_DEPTH {
+            anyhow::bail!("Document is nested deeper than {} levels", MAX_DEPTH);
+        }
+
         if part_index >= path_parts.len() {
-            results.push(current.clone());
+            results.push((current_path.to_string(), current.clone()));
 
             return Ok(());
         }
 
         let part = path_parts[part_index];
 
-        if part == "*" {
+        if part == "**" {
+            // Match zero segments, then descend into every child keeping the `**` active
+            Self::find_wildcard_matches(
+                current,
+                path_parts,
+                part_index + 1,
+                current_path,
+                results,
+            )?;
+
+            match current {
+                Value::Object(obj) => {
+                    for (key, value) in obj {
+                        let new_path = format!("{}/{}", current_path, encode_segment(key));
+                        Self::find_wildcard_matches(
+                            value, path_parts, part_index, &new_path, results,
+                        )?;
+                    }
+                }
+                Value::Array(arr) => {
+                    for (index, item) in arr.iter().enumerate() {
+                        let new_path = format!("{}/{}", current_path, index);
+                        Self::find_wildcard_matches(
+                            item, path_parts, part_index, &new_path, results,
+                        )?;
+                    }
+                }
+                _ => {}
+            }
+        } else if part == "*" {
             match current {
                 Value::Array(arr) => {
                     for (index, item) in arr.iter().enumerate() {
@@ -83,12 +439,39 @@ impl JsonPathMatcher {
                         )?;
                     }
                 }
+                Value::Object(obj) => {
+                    for (key, value) in obj {
+                        let new_path = format!("{}/{}", current_path, encode_segment(key));
+                        Self::find_wildcard_matches(
+                            value,
+                            path_parts,
+                            part_index + 1,
+                            &new_path,
+                            results,
+                        )?;
+                    }
+                }
                 _ => {
                     return Ok(());
                 }
             }
+        } else if let Some(range) = IndexRange::parse(part) {
+            if let Value::Array(arr) = current {
+                for (index, item) in arr.iter().enumerate() {
+                    if range.contains(index) {
+                        let new_path = format!("{}/{}", current_path, index);
+                        Self::find_wildcard_matches(
+                            item,
+                            path_parts,
+                            part_index + 1,
+                            &new_path,
+                            results,
+                        )?;
+                    }
+                }
+            }
         } else {
-            let new_path = format!("{}/{}", current_path, part);
+            let new_path = format!("{}/{}", current_path, encode_segment(part));
 
             match current {
                 Value::Object(obj) => {
@@ -124,32 +507,82 @@ impl JsonPathMatcher {
         Ok(())
     }
 
-    /// Check if any changes in the diff match the allowed change patterns
-    pub fn has_allowed_changes_only(
+    /// Check if any changes in the diff match the allowed change patterns. An allowed
+    /// change is still unauthorized when its `when` conditions do not all hold, or when
+    /// its `unless` conditions do.
+    pub fn has_allowed_changes_only<P: ChangePattern + fmt::Display>(
+        base_json: &Value,
+        current_json: &Value,
+        allowed_patterns: &[P],
+        when_conditions: Option<&[crate::config::PathValue]>,
+        unless_conditions: &[crate::config::PathValue],
+    ) -> Result<bool> {
+        Self::has_allowed_changes_only_explained(
+            base_json,
+            current_json,
+            allowed_patterns,
+            when_conditions,
+            unless_conditions,
+            &mut Explanation::disabled(),
+        )
+    }
+
+    /// Like [`Self::has_allowed_changes_only`], recording the pattern each change matched
+    /// and how each `when` and `unless` condition evaluated. Every change is explained, so
+    /// evaluation does not stop at the first unauthorized change.
+    pub fn has_allowed_changes_only_explained<P: ChangePattern + fmt::Display>(
         base_json: &Value,
         current_json: &Value,
-        allowed_patterns: &[String],
+        allowed_patterns: &[P],
         when_conditions: Option<&[crate::config::PathValue]>,
+        unless_conditions: &[crate::config::PathValue],
+        explanation: &mut Explanation,
     ) -> Result<bool> {
         let changes = Self::get_all_changes(base_json, current_json)?;
+        let mut change_paths: Vec<&String> = changes.keys().collect();
+        change_paths.sort();
 
-        for change_path in changes.keys() {
-            if !Self::path_matches_any_pattern(change_path, allowed_patterns) {
-                return Ok(false);
-            }
+        let mut all_allowed = true;
 
-            let when_conditions_met = if let Some(when_conditions) = when_conditions {
-                Self::when_conditions_met(current_json, change_path, when_conditions)?
-            } else {
-                true
+        for change_path in change_paths {
+            let Some(pattern) = Self::matching_change_pattern_at(
+                change_path,
+                &changes[change_path],
+                allowed_patterns,
+                Granularity::Leaf,
+            ) else {
+                explanation.note(0, || {
+                    format!("{}: not matched by any allowed pattern", change_path)
+                });
+                all_allowed = false;
+                continue;
             };
 
-            if !when_conditions_met {
-                return Ok(false);
+            explanation.note(0, || format!("{}: allowed by {}", change_path, pattern));
+
+            if let Some(when_conditions) = when_conditions
+                && !Self::when_conditions_met_explained(
+                    current_json,
+                    change_path,
+                    when_conditions,
+                    explanation,
+                )?
+            {
+                all_allowed = false;
+                continue;
+            }
+
+            if Self::unless_conditions_met_explained(
+                current_json,
+                change_path,
+                unless_conditions,
+                explanation,
+            )? {
+                all_allowed = false;
             }
         }
 
-        Ok(true)
+        Ok(all_allowed)
     }
 
     /// Check if when conditions are met for a specific change
@@ -159,74 +592,339 @@ impl JsonPathMatcher {
         change_path: &str,
         when_conditions: &[crate::config::PathValue],
     ) -> Result<bool> {
-        // Extract array indices from the exact JSON Pointer path (e.g., "/spec/generators/0/values")
-        let change_indices = Self::extract_indices_from_json_pointer(change_path);
-
-        // For each when condition, check if it matches at the same array indices
-        for when_condition in when_conditions {
-            let when_path_resolved =
-                Self::resolve_wildcard_path_with_indices(&when_condition.path, &change_indices);
-
-            if !Self::check_condition_at_json_pointer(
-                json,
-                &when_path_resolved,
-                &when_condition.value,
-            )? {
-                return Ok(false);
-            }
-        }
+        Self::when_conditions_met_explained(
+            json,
+            change_path,
+            when_conditions,
+            &mut Explanation::disabled(),
+        )
+    }
 
-        Ok(true)
+    /// Like [`Self::when_conditions_met`], recording whether each condition held
+    pub fn when_conditions_met_explained(
+        json: &Value,
+        change_path: &str,
+        when_conditions: &[crate::config::PathValue],
+        explanation: &mut Explanation,
+    ) -> Result<bool> {
+        Ok(
+            Self::conditions_met(json, change_path, when_conditions, "when", explanation)?
+                .unwrap_or(true),
+        )
+    }
+
+    /// Check if the `unless` conditions of a change all hold, which makes the change
+    /// unauthorized. Conditions skipped because their path is missing are ignored, and
+    /// `unless` does not hold when every condition was skipped, or none is given.
+    pub fn unless_conditions_met(
+        json: &Value,
+        change_path: &str,
+        unless_conditions: &[crate::config::PathValue],
+    ) -> Result<bool> {
+        Self::unless_conditions_met_explained(
+            json,
+            change_path,
+            unless_conditions,
+            &mut Explanation::disabled(),
+        )
     }
 
-    /// Extract array indices from a JSON Pointer path like "/spec/generators/0/values/revision"
-    /// Returns a list of (segment, index) pairs for array access
-    fn extract_indices_from_json_pointer(path: &str) -> Vec<(String, usize)> {
-        let mut indices = Vec::new();
-        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    /// Like [`Self::unless_conditions_met`], recording whether each condition held
+    pub fn unless_conditions_met_explained(
+        json: &Value,
+        change_path: &str,
+        unless_conditions: &[crate::config::PathValue],
+        explanation: &mut Explanation,
+    ) -> Result<bool> {
+        Ok(
+            Self::conditions_met(json, change_path, unless_conditions, "unless", explanation)?
+                .unwrap_or(false),
+        )
+    }
+
+    /// Whether every condition holds for a change, aligning wildcards with its path and
+    /// noting each outcome under `label`. `None` when every condition was skipped.
+    fn conditions_met(
+        json: &Value,
+        change_path: &str,
+        conditions: &[crate::config::PathValue],
+        label: &str,
+        explanation: &mut Explanation,
+    ) -> Result<Option<bool>> {
+        let mut all_met = None;
+
+        // For each condition, check if it matches at the same array indices
+        for condition in conditions {
+            let path_resolved = Self::resolve_wildcard_path(&condition.path, change_path);
 
-        for i in 0..parts.len().saturating_sub(1) {
-            if let Ok(index) = parts[i + 1].parse::<usize>() {
-                indices.push((parts[i].to_string(), index));
+            let met = match Self::missing_outcome(json, &path_resolved, condition) {
+                Some(Some(outcome)) => outcome,
+                Some(None) => {
+                    explanation.note(1, || {
+                        format!(
+                            "{} {} (at {}): skipped, path missing",
+                            label, condition.path, path_resolved
+                        )
+                    });
+                    continue;
+                }
+                // Wildcards that could not be aligned with the change (e.g. `**`) match anywhere
+                None if Self::has_wildcards(&path_resolved) => {
+                    Self::matches_path_condition(json, &path_resolved, condition)?
+                        != condition.negate
+                }
+                None => {
+                    Self::check_condition_at_json_pointer(json, &path_resolved, condition)?
+                        != condition.negate
+                }
+            };
+
+            explanation.note(1, || {
+                format!("{} {} (at {}): {}", label, condition, path_resolved, met)
+            });
+
+            all_met = Some(all_met.unwrap_or(true) && met);
+            // Without an explanation to complete, the first failing condition decides
+            if !met && !explanation.is_enabled() {
+                break;
             }
         }
 
-        indices
+        Ok(all_met)
     }
 
-    /// Resolve a wildcard path using specific indices from a change path
-    /// e.g., "/spec/generators/*/values" with indices from "/spec/generators/0/values/revision"
-    /// becomes "/spec/generators/0/values"
-    fn resolve_wildcard_path_with_indices(
-        wildcard_path: &str,
-        indices: &[(String, usize)],
-    ) -> String {
-        let mut result = wildcard_path.to_string();
+    /// Resolve the wildcards of a `when` path along the longest prefix it shares with
+    /// the change path, so only the indices of arrays both paths pass through are reused.
+    /// e.g. "/spec/generators/*/selector/env" with "/spec/generators/1/template/image"
+    /// becomes "/spec/generators/1/selector/env". Wildcards after the paths diverge,
+    /// and `**`, are left in place. `when` paths may omit the leading `/`, which the
+    /// resolved pointer always has.
+    fn resolve_wildcard_path(wildcard_path: &str, change_path: &str) -> String {
+        let wildcard_path = wildcard_path.strip_prefix('/').unwrap_or(wildcard_path);
+        let mut parts: Vec<String> = std::iter::once(String::new())
+            .chain(wildcard_path.split('/').map(decode_segment))
+            .collect();
+        let change_parts: Vec<String> = change_path.split('/').map(decode_segment).collect();
+
+        for (part, change_part) in parts.iter_mut().skip(1).zip(change_parts.iter().skip(1)) {
+            if part == change_part {
+                continue;
+            }
+
+            let index = change_part.parse::<usize>().ok();
+            let in_range = |range: IndexRange| index.is_some_and(|index| range.contains(index));
+            if index.is_some() && (part == "*" || IndexRange::parse(part).is_some_and(in_range)) {
+                *part = change_part.clone();
+                continue;
+            }
 
-        for (segment_name, index) in indices {
-            let wildcard_pattern = format!("/{}/*", segment_name);
-            let replacement = format!("/{}/{}", segment_name, index);
-            result = result.replace(&wildcard_pattern, &replacement);
+            break;
         }
 
-        result
+        parts
+            .iter()
+            .map(|part| encode_segment(part))
+            .collect::<Vec<_>>()
+            .join("/")
     }
 
     /// Check a condition directly using JSON Pointer (no wildcards)
     fn check_condition_at_json_pointer(
         json: &Value,
         json_pointer_path: &str,
-        expected_value: &Value,
+        condition: &PathValue,
     ) -> Result<bool> {
         match Self::get_value_at_json_pointer(json, json_pointer_path) {
-            Ok(actual_value) => Ok(actual_value == *expected_value),
+            Ok(actual_value) => Self::condition_holds(&actual_value, condition)
+                .with_context(|| format!("Condition {} cannot be evaluated", condition)),
             Err(_) => Ok(false), // Path doesn't exist, condition fails
         }
     }
 
-    /// Get all changes between base and current JSON using json-patch
+    /// Check that a change to a versioned path sets a valid semantic version that is not
+    /// lower than the previous one. Returns the reason when the change is rejected.
+    pub fn check_version_change(old: Option<&Value>, new: Option<&Value>) -> Option<&'static str> {
+        let new = new?;
+
+        let Some(new_version) = new.as_str().and_then(|s| s.parse::<Version>().ok()) else {
+            return Some("invalid semantic version");
+        };
+
+        let old_version = old
+            .and_then(Value::as_str)
+            .and_then(|s| s.parse::<Version>().ok());
+
+        match old_version {
+            Some(old_version) if new_version < old_version => Some("version downgrade"),
+            _ => None,
+        }
+    }
+
+    /// Fail when objects and arrays in `json` are nested more than `max_depth` levels
+    /// deep. The document is walked without recursion, so any depth is checked safely.
+    pub fn check_depth(json: &Value, max_depth: usize) -> Result<()> {
+        let mut stack = vec![(json, 0)];
+
+        while let Some((value, depth)) = stack.pop() {
+            let children: Box<dyn Iterator<Item = &Value>> = match value {
+                Value::Object(obj) => Box::new(obj.values()),
+                Value::Array(arr) => Box::new(arr.iter()),
+                _ => continue,
+            };
+            if depth >= max_depth {
+                anyhow::bail!("Document is nested deeper than {} levels", max_depth);
+            }
+            stack.extend(children.map(|child| (child, depth + 1)));
+        }
+
+        Ok(())
+    }
+
+    /// Get all changes between base and current JSON using json-patch. Documents nested
+    /// deeper than [`MAX_DEPTH`] are rejected rather than walked.
     pub fn get_all_changes(base_json: &Value, current_json: &Value) -> Result<ChangeMap> {
-        let json_patch::Patch(operations) = diff(base_json, current_json);
+        Self::check_depth(base_json, MAX_DEPTH)?;
+        Self::check_depth(current_json, MAX_DEPTH)?;
+        let patch = diff(base_json, current_json);
+
+        Ok(Self::changes_from_patch(base_json, patch))
+    }
+
+    /// Like [`Self::get_all_changes`], pairing the elements of arrays matched by a pattern
+    /// in `array_keys` by the value of the given field instead of by position, so
+    /// reordering or inserting elements only reports what actually changed. Paired and
+    /// added elements are reported at their index in the current document, removed ones
+    /// at their index in the base. Arrays whose elements do not all carry a distinct
+    /// scalar key are diffed by position.
+    pub fn get_all_changes_keyed(
+        base_json: &Value,
+        current_json: &Value,
+        array_keys: &BTreeMap<String, String>,
+    ) -> Result<ChangeMap> {
+        if array_keys.is_empty() {
+            return Self::get_all_changes(base_json, current_json);
+        }
+        Self::check_depth(base_json, MAX_DEPTH)?;
+        Self::check_depth(current_json, MAX_DEPTH)?;
+
+        let mut changes = HashMap::new();
+        Self::collect_keyed_changes(base_json, current_json, "", array_keys, &mut changes);
+
+        Ok(changes)
+    }
+
+    fn collect_keyed_changes(
+        base: &Value,
+        current: &Value,
+        pointer: &str,
+        array_keys: &BTreeMap<String, String>,
+        changes: &mut ChangeMap,
+    ) {
+        match (base, current) {
+            (Value::Object(base_map), Value::Object(current_map)) => {
+                for (key, base_value) in base_map {
+                    let path = format!("{}/{}", pointer, encode_segment(key));
+                    match current_map.get(key) {
+                        Some(current_value) => Self::collect_keyed_changes(
+                            base_value,
+                            current_value,
+                            &path,
+                            array_keys,
+                            changes,
+                        ),
+                        None => {
+                            changes.insert(path, (Some(base_value.clone()), None));
+                        }
+                    }
+                }
+                for (key, current_value) in current_map {
+                    if !base_map.contains_key(key) {
+                        let path = format!("{}/{}", pointer, encode_segment(key));
+                        changes.insert(path, (None, Some(current_value.clone())));
+                    }
+                }
+            }
+            (Value::Array(base_items), Value::Array(current_items)) => {
+                let keyed = array_keys
+                    .iter()
+                    .find(|(pattern, _)| Self::path_matches_pattern(pointer, pattern))
+                    .and_then(|(_, field)| {
+                        Some((
+                            Self::element_keys(base_items, field)?,
+                            Self::element_keys(current_items, field)?,
+                        ))
+                    });
+
+                let Some((base_keys, current_keys)) = keyed else {
+                    Self::collect_patch_changes(base, current, pointer, changes);
+                    return;
+                };
+
+                for (index, current_item) in current_items.iter().enumerate() {
+                    let path = format!("{}/{}", pointer, index);
+                    match base_keys.iter().position(|key| *key == current_keys[index]) {
+                        Some(base_index) => Self::collect_keyed_changes(
+                            &base_items[base_index],
+                            current_item,
+                            &path,
+                            array_keys,
+                            changes,
+                        ),
+                        None => {
+                            changes.insert(path, (None, Some(current_item.clone())));
+                        }
+                    }
+                }
+                for (index, base_item) in base_items.iter().enumerate() {
+                    if !current_keys.contains(&base_keys[index]) {
+                        // A removal at the index of an added element reads as a replacement
+                        let change = changes
+                            .entry(format!("{}/{}", pointer, index))
+                            .or_insert((None, None));
+                        change.0 = Some(base_item.clone());
+                    }
+                }
+            }
+            _ => Self::collect_patch_changes(base, current, pointer, changes),
+        }
+    }
+
+    /// The positional changes between two values, with their paths below `pointer`
+    fn collect_patch_changes(
+        base: &Value,
+        current: &Value,
+        pointer: &str,
+        changes: &mut ChangeMap,
+    ) {
+        let patch = diff(base, current);
+        for (path, change) in Self::changes_from_patch(base, patch) {
+            changes.insert(format!("{}{}", pointer, path), change);
+        }
+    }
+
+    /// The key field of every element, or `None` unless every element is an object
+    /// with a distinct scalar value there
+    fn element_keys<'a>(items: &'a [Value], field: &str) -> Option<Vec<&'a Value>> {
+        let keys = items
+            .iter()
+            .map(|item| {
+                item.get(field)
+                    .filter(|key| !(key.is_object() || key.is_array() || key.is_null()))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let distinct = keys
+            .iter()
+            .enumerate()
+            .all(|(i, key)| !keys[..i].contains(key));
+        distinct.then_some(keys)
+    }
+
+    /// Collect the changes described by a patch from the base to the current document.
+    /// The patch comes from [`diff`], which only emits additions, removals and
+    /// replacements; a moved array element shows up as replacements at both indices.
+    fn changes_from_patch(base_json: &Value, patch: json_patch::Patch) -> ChangeMap {
+        let json_patch::Patch(operations) = patch;
         let mut changes = HashMap::new();
 
         // Each operation in the patch represents one atomic change
@@ -251,15 +949,50 @@ impl JsonPathMatcher {
                         changes.insert(path, (Some(old_value), Some(replace_op.value)));
                     }
                 }
-                _ => continue,
+                json_patch::PatchOperation::Move(_)
+                | json_patch::PatchOperation::Copy(_)
+                | json_patch::PatchOperation::Test(_) => {
+                    unreachable!("json_patch::diff only emits add, remove and replace")
+                }
             }
         }
 
-        Ok(changes)
+        changes
     }
 
-    /// Get a value at a specific JSON Pointer path using the standard jsonptr library
-    fn get_value_at_json_pointer(json: &Value, pointer: &str) -> Result<Value> {
+    /// Get every leaf of a document as an addition, as if it were diffed against nothing
+    pub fn get_all_leaves(json: &Value) -> ChangeMap {
+        let mut leaves = HashMap::new();
+        Self::collect_leaves(json, String::new(), &mut leaves);
+
+        leaves
+    }
+
+    /// Recursively collect leaves (scalars and empty containers) under the given pointer
+    fn collect_leaves(json: &Value, pointer: String, leaves: &mut ChangeMap) {
+        match json {
+            Value::Object(obj) if !obj.is_empty() => {
+                for (key, value) in obj {
+                    Self::collect_leaves(
+                        value,
+                        format!("{}/{}", pointer, encode_segment(key)),
+                        leaves,
+                    );
+                }
+            }
+            Value::Array(arr) if !arr.is_empty() => {
+                for (index, value) in arr.iter().enumerate() {
+                    Self::collect_leaves(value, format!("{}/{}", pointer, index), leaves);
+                }
+            }
+            _ => {
+                leaves.insert(pointer, (None, Some(json.clone())));
+            }
+        }
+    }
+
+    /// Get a value at a specific JSON Pointer path using the standard jsonptr library
+    fn get_value_at_json_pointer(json: &Value, pointer: &str) -> Result<Value> {
         let ptr = Pointer::parse(pointer)
             .map_err(|e| anyhow::anyhow!("Invalid JSON pointer '{}': {}", pointer, e))?;
 
@@ -276,19 +1009,167 @@ impl JsonPathMatcher {
             .any(|pattern| Self::path_matches_pattern(path, pattern))
     }
 
+    /// The first pattern that matches the given path, if any
+    pub fn matching_pattern<'a, P: AsRef<str>>(path: &str, patterns: &'a [P]) -> Option<&'a P> {
+        patterns
+            .iter()
+            .find(|pattern| Self::path_matches_pattern(path, pattern.as_ref()))
+    }
+
+    /// The first pattern that matches the path or, at subtree granularity, one of the
+    /// path's ancestors
+    pub fn matching_pattern_at<'a, P: AsRef<str>>(
+        path: &str,
+        patterns: &'a [P],
+        granularity: Granularity,
+    ) -> Option<&'a P> {
+        patterns
+            .iter()
+            .find(|pattern| Self::pattern_matches_at(path, pattern.as_ref(), granularity))
+    }
+
+    /// Like [`Self::matching_pattern_at`], skipping patterns that do not cover the kind
+    /// of change or do not accept its new value
+    pub fn matching_change_pattern_at<'a, P: ChangePattern>(
+        path: &str,
+        change: &(Option<Value>, Option<Value>),
+        patterns: &'a [P],
+        granularity: Granularity,
+    ) -> Option<&'a P> {
+        let kind = ChangeKind::of(change);
+        patterns.iter().find(|pattern| {
+            pattern.covers(kind)
+                && change.1.as_ref().is_none_or(|value| pattern.accepts(value))
+                && Self::pattern_matches_at(path, pattern.as_ref(), granularity)
+        })
+    }
+
+    /// Whether a change at `path` touches what `pattern` matches: the path matches the
+    /// pattern or lies below a match, or it is an ancestor of a match, e.g. a removed
+    /// parent object
+    pub fn path_overlaps_pattern(path: &str, pattern: &str) -> bool {
+        if Self::pattern_matches_at(path, pattern, Granularity::Subtree) {
+            return true;
+        }
+
+        let segments: Vec<&str> = pattern.split('/').collect();
+        (1..segments.len()).any(|end| Self::path_matches_pattern(path, &segments[..end].join("/")))
+    }
+
+    fn pattern_matches_at(path: &str, pattern: &str, granularity: Granularity) -> bool {
+        Self::matches_segments(path, pattern, granularity == Granularity::Subtree)
+    }
+
     /// Check if a path matches a pattern (supports wildcards)
-    fn path_matches_pattern(path: &str, pattern: &str) -> bool {
-        let regex_pattern = pattern.replace('*', r"\d+");
+    /// `*` matches a single array index or object key, or within a segment any part of
+    /// one (`/data/*-config`), `[start:end]` an array index in the range, and `**` any
+    /// number of segments
+    pub fn path_matches_pattern(path: &str, pattern: &str) -> bool {
+        Self::matches_segments(path, pattern, false)
+    }
 
-        let regex = match Regex::new(&format!("^{}$", regex_pattern)) {
-            Ok(r) => r,
-            Err(_) => return false,
+    /// Match a path against a pattern segment by segment. With `subtree`, the path may
+    /// continue below a match.
+    fn matches_segments(path: &str, pattern: &str, subtree: bool) -> bool {
+        let mut path = path.split('/');
+        let mut pattern = pattern.split('/');
+
+        // Text before the leading `/`, normally empty, is compared verbatim
+        path.next() == pattern.next()
+            && Self::segments_match(
+                &path.collect::<Vec<_>>(),
+                &pattern.collect::<Vec<_>>(),
+                subtree,
+            )
+    }
+
+    fn segments_match(path: &[&str], pattern: &[&str], subtree: bool) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty() || subtree,
+            Some((&"**", rest)) => {
+                Self::segments_match(path, rest, subtree)
+                    || (!path.is_empty() && Self::segments_match(&path[1..], pattern, subtree))
+            }
+            Some((expected, rest)) => path.split_first().is_some_and(|(segment, path)| {
+                Self::segment_matches(segment, expected)
+                    && Self::segments_match(path, rest, subtree)
+            }),
+        }
+    }
+
+    fn segment_matches(segment: &str, pattern: &str) -> bool {
+        if pattern == "*" {
+            return !segment.is_empty();
+        }
+
+        if let Some(range) = IndexRange::parse(pattern) {
+            return segment
+                .parse::<usize>()
+                .is_ok_and(|index| range.contains(index));
+        }
+
+        let pieces: Vec<&str> = pattern.split('*').collect();
+        let [first, middle @ .., last] = pieces.as_slice() else {
+            return segment == pattern;
         };
 
-        regex.is_match(path)
+        // Within a segment, `*` matches any run of characters
+        let Some(mut rest) = segment.strip_prefix(first) else {
+            return false;
+        };
+        for piece in middle {
+            let Some(at) = rest.find(piece) else {
+                return false;
+            };
+            rest = &rest[at + piece.len()..];
+        }
+
+        rest.ends_with(last)
     }
 }
 
+/// A pattern segment selecting array indices from `start` up to, but excluding, `end`,
+/// written `[start:end]`. Either bound may be omitted: `[2:]` is every index from 2 on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl IndexRange {
+    /// Parse a `[start:end]` segment, `None` when the segment is not a range
+    pub fn parse(segment: &str) -> Option<Self> {
+        let (start, end) = segment
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .split_once(':')?;
+        let bound = |bound: &str| match bound {
+            "" => Some(None),
+            bound if bound.bytes().all(|b| b.is_ascii_digit()) => bound.parse().ok().map(Some),
+            _ => None,
+        };
+
+        Some(Self {
+            start: bound(start)?.unwrap_or(0),
+            end: bound(end)?,
+        })
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index >= self.start && self.end.is_none_or(|end| index < end)
+    }
+}
+
+/// Decode a JSON Pointer reference token (RFC 6901): `~1` becomes `/` and `~0` becomes `~`
+fn decode_segment(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+/// Encode a key as a JSON Pointer reference token (RFC 6901)
+fn encode_segment(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -338,21 +1219,66 @@ mod tests {
             }
         });
 
-        assert!(JsonPathMatcher::matches_condition(
-            &json,
-            "kind",
-            &json!("ApplicationSet")
-        ));
-        assert!(JsonPathMatcher::matches_condition(
-            &json,
-            "/spec/generators/*/selector/matchLabels/env",
-            &json!("development")
-        ));
-        assert!(!JsonPathMatcher::matches_condition(
+        assert!(
+            JsonPathMatcher::matches_condition(&json, "kind", &json!("ApplicationSet")).unwrap()
+        );
+        assert!(
+            JsonPathMatcher::matches_condition(
+                &json,
+                "/spec/generators/*/selector/matchLabels/env",
+                &json!("development")
+            )
+            .unwrap()
+        );
+        assert!(!JsonPathMatcher::matches_condition(&json, "kind", &json!("Application")).unwrap());
+    }
+
+    #[test]
+    fn test_condition_failure() {
+        let json = json!({
+            "kind": "ApplicationSet",
+            "spec": { "generators": [{ "env": "dev" }, { "env": "staging" }] }
+        });
+        let condition = |path: &str, value: Value| PathValue {
+            path: path.to_string(),
+            value,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            JsonPathMatcher::condition_failure(&json, &condition("kind", json!("ApplicationSet")))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            JsonPathMatcher::condition_failure(&json, &condition("/knd", json!("ApplicationSet")))
+                .unwrap(),
+            Some(ConditionFailure::PathNotFound)
+        );
+
+        let failure = JsonPathMatcher::condition_failure(
             &json,
-            "kind",
-            &json!("Application")
-        ));
+            &condition("/spec/generators/*/env", json!("prod")),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            failure,
+            ConditionFailure::ValueMismatch {
+                found: vec![json!("dev"), json!("staging")]
+            }
+        );
+        assert_eq!(failure.to_string(), "found \"dev\", \"staging\"");
+
+        // A condition that holds on a missing path is no failure
+        let absent = PathValue {
+            on_missing: OnMissing::Pass,
+            ..condition("/metadata/name", json!("app"))
+        };
+        assert_eq!(
+            JsonPathMatcher::condition_failure(&json, &absent).unwrap(),
+            None
+        );
     }
 
     #[test]
@@ -370,4 +1296,597 @@ mod tests {
             "/spec/generators/*/values/revision"
         ));
     }
+
+    #[test]
+    fn test_trailing_wildcard_covers_subtree_only_when_asked() {
+        let nested = "/spec/generators/0/values/revision";
+        let patterns = ["/spec/generators/*"];
+
+        // A trailing `*` names the element itself, not the fields inside it
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/0",
+            patterns[0]
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(nested, patterns[0]));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            nested,
+            "/spec/generators/**"
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            nested,
+            "/spec/generators/*/values/revision"
+        ));
+
+        assert!(
+            JsonPathMatcher::matching_pattern_at(nested, &patterns, Granularity::Leaf).is_none()
+        );
+        assert!(
+            JsonPathMatcher::matching_pattern_at(nested, &patterns, Granularity::Subtree).is_some()
+        );
+        assert!(
+            JsonPathMatcher::matching_pattern_at(
+                "/spec/template/name",
+                &patterns,
+                Granularity::Subtree
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_get_all_leaves() {
+        let json = json!({
+            "kind": "ApplicationSet",
+            "metadata": {
+                "annotations": {
+                    "app.kubernetes.io/name": "test"
+                }
+            },
+            "spec": {
+                "generators": [{ "values": { "revision": "main" } }],
+                "empty": {}
+            }
+        });
+
+        let leaves = JsonPathMatcher::get_all_leaves(&json);
+
+        assert_eq!(leaves.len(), 4);
+        assert_eq!(leaves["/kind"], (None, Some(json!("ApplicationSet"))));
+        assert!(leaves.contains_key("/metadata/annotations/app.kubernetes.io~1name"));
+        assert!(leaves.contains_key("/spec/generators/0/values/revision"));
+        assert_eq!(leaves["/spec/empty"], (None, Some(json!({}))));
+    }
+
+    #[test]
+    fn test_compare_operators() {
+        assert!(JsonPathMatcher::compare(&json!(3), Operator::Ge, &json!(3)).unwrap());
+        assert!(JsonPathMatcher::compare(&json!(4.5), Operator::Gt, &json!(3)).unwrap());
+        assert!(!JsonPathMatcher::compare(&json!(2), Operator::Ge, &json!(3)).unwrap());
+        assert!(JsonPathMatcher::compare(&json!("a"), Operator::Lt, &json!("b")).unwrap());
+        assert!(JsonPathMatcher::compare(&json!("a"), Operator::Ne, &json!(1)).unwrap());
+        assert!(JsonPathMatcher::compare(&json!(true), Operator::Gt, &json!(1)).is_err());
+        assert!(JsonPathMatcher::compare(&json!("3"), Operator::Lt, &json!(4)).is_err());
+
+        let json = json!({ "spec": { "replicas": 5 } });
+        assert!(
+            JsonPathMatcher::matches_condition_with_op(
+                &json,
+                "/spec/replicas",
+                Operator::Ge,
+                &json!(3)
+            )
+            .unwrap()
+        );
+        assert!(
+            !JsonPathMatcher::matches_condition_with_op(
+                &json,
+                "/spec/replicas",
+                Operator::Lt,
+                &json!(3)
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_regex_operator() {
+        let regex = |actual: Value, pattern: &str| {
+            JsonPathMatcher::compare(&actual, Operator::Regex, &json!(pattern)).unwrap()
+        };
+
+        assert!(regex(json!("prod-web"), "^prod-.*"));
+        assert!(!regex(json!("staging-web"), "^prod-"));
+        assert!(regex(json!(42), "^4\\d$"));
+        assert!(regex(json!(true), "^true$"));
+        assert!(regex(json!(null), "^null$"));
+
+        let json = json!({ "metadata": { "name": "prod-api" } });
+        assert!(
+            JsonPathMatcher::matches_condition_with_op(
+                &json,
+                "/metadata/name",
+                Operator::Regex,
+                &json!("^prod-")
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_version_change() {
+        let check =
+            |old: Value, new: Value| JsonPathMatcher::check_version_change(Some(&old), Some(&new));
+
+        assert_eq!(check(json!("1.2.3"), json!("1.2.4")), None);
+        assert_eq!(check(json!("1.2.3"), json!("1.2.3")), None);
+        assert_eq!(
+            check(json!("1.2.3"), json!("1.2.2")),
+            Some("version downgrade")
+        );
+        assert_eq!(
+            check(json!("1.2.3"), json!("main")),
+            Some("invalid semantic version")
+        );
+        assert_eq!(check(json!("main"), json!("1.0.0")), None);
+        assert_eq!(
+            JsonPathMatcher::check_version_change(None, Some(&json!("0.1.0"))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_recursive_wildcard() {
+        let json = json!({
+            "spec": {
+                "revision": "top",
+                "generators": [
+                    { "values": { "revision": "main" } },
+                    { "nested": { "values": { "revision": "develop" } } }
+                ]
+            }
+        });
+
+        let values = JsonPathMatcher::get_values_at_path(&json, "/spec/**/revision").unwrap();
+        assert_eq!(values.len(), 3);
+        assert!(values.contains(&json!("top")));
+        assert!(values.contains(&json!("main")));
+        assert!(values.contains(&json!("develop")));
+
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/revision",
+            "/spec/**/revision"
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/0/values/revision",
+            "/spec/**/revision"
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/1/nested/values/revision",
+            "/spec/**/revision"
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/0/values/other",
+            "/spec/**/revision"
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/metadata/revision",
+            "/spec/**/revision"
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_within_segment() {
+        // A whole-segment wildcard matches array indices and keys alike
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/3/image",
+            "/spec/generators/*/image"
+        ));
+
+        // Within a segment it matches any part of a key, but never crosses a `/`
+        let pattern = "/data/*-config/enabled";
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/data/app-config/enabled",
+            pattern
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/data/-config/enabled",
+            pattern
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/data/app-settings/enabled",
+            pattern
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/data/app/x-config/enabled",
+            pattern
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/items/item12",
+            "/items/item*"
+        ));
+    }
+
+    #[test]
+    fn test_path_overlaps_pattern() {
+        let overlaps = JsonPathMatcher::path_overlaps_pattern;
+
+        // The field itself, something below it, and any ancestor
+        assert!(overlaps("/metadata/name", "/metadata/name"));
+        assert!(overlaps("/spec/selector/app", "/spec/selector"));
+        assert!(overlaps("/metadata", "/metadata/name"));
+        assert!(overlaps("", "/metadata/name"));
+        assert!(overlaps("/spec/containers/0", "/spec/containers/*/name"));
+        assert!(overlaps("/a/b", "/a/**/name"));
+
+        assert!(!overlaps("/metadata/namespace", "/metadata/name"));
+        assert!(!overlaps("/metadata/labels", "/metadata/name"));
+        assert!(!overlaps("/spec", "/metadata/name"));
+    }
+
+    #[test]
+    fn test_index_ranges() {
+        let pattern = "/spec/generators/[0:3]/values/revision";
+        for (index, expected) in [(0, true), (2, true), (3, false), (10, false)] {
+            let path = format!("/spec/generators/{}/values/revision", index);
+            assert_eq!(
+                JsonPathMatcher::path_matches_pattern(&path, pattern),
+                expected,
+                "{}",
+                path
+            );
+        }
+        // Ranges only select array indices
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/first/values/revision",
+            pattern
+        ));
+
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/items/7",
+            "/items/[2:]"
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/items/1",
+            "/items/[2:]"
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/items/0",
+            "/items/[:1]"
+        ));
+
+        assert_eq!(
+            IndexRange::parse("[1:4]"),
+            Some(IndexRange {
+                start: 1,
+                end: Some(4)
+            })
+        );
+        assert_eq!(IndexRange::parse("[a:4]"), None);
+        assert_eq!(IndexRange::parse("[-1:]"), None);
+        assert_eq!(IndexRange::parse("[1]"), None);
+
+        let json = json!({"items": ["a", "b", "c", "d"]});
+        assert_eq!(
+            JsonPathMatcher::get_values_at_path(&json, "/items/[1:3]").unwrap(),
+            vec![json!("b"), json!("c")]
+        );
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("/items/[0:2]/env", "/items/1/image"),
+            "/items/1/env"
+        );
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("/items/[0:2]/env", "/items/3/image"),
+            "/items/[0:2]/env"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_values_are_deduplicated() {
+        let json = json!({"a": {"b": {"name": "x"}}});
+        assert_eq!(
+            JsonPathMatcher::get_values_at_path(&json, "/**/**/name").unwrap(),
+            vec![json!("x")]
+        );
+    }
+
+    #[test]
+    fn test_match_mode() {
+        let json = json!({"containers": [{"image": "app:1"}, {"image": "sidecar:1"}]});
+        let condition = |match_mode, value: &str| PathValue {
+            path: "/containers/*/image".to_string(),
+            value: json!(value),
+            op: Operator::Regex,
+            match_mode,
+            ..Default::default()
+        };
+        let matches = |condition: PathValue| {
+            JsonPathMatcher::matches_conditions(&json, std::slice::from_ref(&condition)).unwrap()
+        };
+
+        assert!(matches(condition(MatchMode::Any, "^app:")));
+        assert!(!matches(condition(MatchMode::All, "^app:")));
+        assert!(matches(condition(MatchMode::All, ":1$")));
+
+        // negate inverts the combined outcome: not every image is an app image
+        assert!(matches(PathValue {
+            negate: true,
+            ..condition(MatchMode::All, "^app:")
+        }));
+
+        // With no values, `all` is decided by onMissing like any other condition
+        let empty = json!({"containers": []});
+        assert!(
+            !JsonPathMatcher::matches_conditions(&empty, &[condition(MatchMode::All, ":1$")])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_wildcard_path() {
+        let change_path = "/spec/generators/1/values/revision";
+
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("/spec/generators/*/selector/env", change_path),
+            "/spec/generators/1/selector/env"
+        );
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("spec/generators/*/selector/env", change_path),
+            "/spec/generators/1/selector/env"
+        );
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("/spec/generators/**/env", change_path),
+            "/spec/generators/**/env"
+        );
+    }
+
+    #[test]
+    fn test_resolve_wildcard_path_after_divergence() {
+        // Only the generator index is shared; the selector's own array is unrelated to
+        // the container the change is in
+        let change_path = "/spec/generators/0/template/spec/containers/2/image";
+
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path(
+                "/spec/generators/*/selector/containers/*/name",
+                change_path
+            ),
+            "/spec/generators/0/selector/containers/*/name"
+        );
+        // Wildcards under a different parent are not aligned by name
+        assert_eq!(
+            JsonPathMatcher::resolve_wildcard_path("/metadata/generators/*/env", change_path),
+            "/metadata/generators/*/env"
+        );
+    }
+
+    #[test]
+    fn test_object_key_wildcard() {
+        let json = json!({
+            "spec": {
+                "generators": {
+                    "prod": { "values": { "revision": "1.0.0" } },
+                    "dev": { "values": { "revision": "main" } }
+                }
+            }
+        });
+
+        let values =
+            JsonPathMatcher::get_values_at_path(&json, "/spec/generators/*/values/revision")
+                .unwrap();
+        assert_eq!(values.len(), 2);
+        assert!(values.contains(&json!("1.0.0")));
+        assert!(values.contains(&json!("main")));
+
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/prod/values/revision",
+            "/spec/generators/*/values/revision"
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/spec/generators/prod/nested/values/revision",
+            "/spec/generators/*/values/revision"
+        ));
+    }
+
+    #[test]
+    fn test_path_matches_pattern_escapes_metacharacters() {
+        let pattern = "/metadata/annotations/app.kubernetes.io~1name";
+
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/metadata/annotations/app.kubernetes.io~1name",
+            pattern
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/metadata/annotations/appXkubernetesYio~1name",
+            pattern
+        ));
+        assert!(!JsonPathMatcher::path_matches_pattern(
+            "/metadata/labels/aab",
+            "/metadata/labels/a+b"
+        ));
+        assert!(JsonPathMatcher::path_matches_pattern(
+            "/metadata/labels/a+b",
+            "/metadata/labels/a+b"
+        ));
+    }
+
+    #[test]
+    fn test_escaped_segments() {
+        let json = json!({
+            "spec": {
+                "generators": [{
+                    "metadata": {
+                        "annotations": {
+                            "app.kubernetes.io/managed-by": "argocd",
+                            "a~b": "tilde"
+                        }
+                    },
+                    "values": { "revision": "main" }
+                }]
+            }
+        });
+
+        let values = JsonPathMatcher::get_values_at_path(
+            &json,
+            "/spec/generators/*/metadata/annotations/app.kubernetes.io~1managed-by",
+        )
+        .unwrap();
+        assert_eq!(values, vec![json!("argocd")]);
+
+        let values = JsonPathMatcher::get_values_at_path(
+            &json,
+            "/spec/generators/*/metadata/annotations/a~0b",
+        )
+        .unwrap();
+        assert_eq!(values, vec![json!("tilde")]);
+
+        let when = vec![crate::config::PathValue {
+            path: "/spec/generators/*/metadata/annotations/app.kubernetes.io~1managed-by"
+                .to_string(),
+            value: json!("argocd"),
+            ..Default::default()
+        }];
+        assert!(
+            JsonPathMatcher::when_conditions_met(
+                &json,
+                "/spec/generators/0/values/revision",
+                &when
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_moved_array_element_is_surfaced() {
+        let base = json!({ "spec": { "items": ["a", "b", "c"] } });
+        let current = json!({ "spec": { "items": ["b", "a", "c"] } });
+
+        let changes = JsonPathMatcher::get_all_changes(&base, &current).unwrap();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes["/spec/items/0"],
+            (Some(json!("a")), Some(json!("b")))
+        );
+        assert_eq!(
+            changes["/spec/items/1"],
+            (Some(json!("b")), Some(json!("a")))
+        );
+
+        // A moved object key is a removal at the old key and an addition at the new one
+        let base = json!({ "spec": { "old": { "image": "web" } } });
+        let current = json!({ "spec": { "new": { "image": "web" } } });
+
+        let changes = JsonPathMatcher::get_all_changes(&base, &current).unwrap();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes["/spec/old"],
+            (Some(json!({ "image": "web" })), None)
+        );
+        assert_eq!(
+            changes["/spec/new"],
+            (None, Some(json!({ "image": "web" })))
+        );
+    }
+
+    #[test]
+    fn test_keyed_array_changes() {
+        let array_keys = BTreeMap::from([("/spec/generators".to_string(), "name".to_string())]);
+        let base = json!({"spec": {"generators": [
+            {"name": "a", "rev": 1},
+            {"name": "b", "rev": 1},
+            {"name": "c", "rev": 1}
+        ]}});
+
+        // Reordering changes nothing
+        let reordered = json!({"spec": {"generators": [
+            {"name": "c", "rev": 1},
+            {"name": "a", "rev": 1},
+            {"name": "b", "rev": 1}
+        ]}});
+        assert!(
+            JsonPathMatcher::get_all_changes_keyed(&base, &reordered, &array_keys)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            !JsonPathMatcher::get_all_changes(&base, &reordered)
+                .unwrap()
+                .is_empty()
+        );
+
+        // An inserted element is one addition; edits are reported at the current index
+        let inserted = json!({"spec": {"generators": [
+            {"name": "new", "rev": 1},
+            {"name": "a", "rev": 1},
+            {"name": "b", "rev": 2}
+        ]}});
+        let changes =
+            JsonPathMatcher::get_all_changes_keyed(&base, &inserted, &array_keys).unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes["/spec/generators/0"],
+            (None, Some(json!({"name": "new", "rev": 1})))
+        );
+        assert_eq!(
+            changes["/spec/generators/2/rev"],
+            (Some(json!(1)), Some(json!(2)))
+        );
+        // "c" was removed from index 2
+        assert_eq!(
+            changes["/spec/generators/2"],
+            (Some(json!({"name": "c", "rev": 1})), None)
+        );
+
+        // Without distinct keys the array is diffed by position
+        let unkeyed = json!({"spec": {"generators": [{"rev": 2}]}});
+        assert_eq!(
+            JsonPathMatcher::get_all_changes_keyed(&base, &unkeyed, &array_keys).unwrap(),
+            JsonPathMatcher::get_all_changes(&base, &unkeyed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut deep = json!("leaf");
+        for _ in 0..MAX_DEPTH + 10 {
+            deep = json!({"a": deep});
+        }
+
+        assert!(JsonPathMatcher::check_depth(&deep, MAX_DEPTH).is_err());
+        assert!(JsonPathMatcher::check_depth(&deep, MAX_DEPTH + 10).is_ok());
+        assert!(JsonPathMatcher::check_depth(&json!({"a": [1]}), 1).is_err());
+        assert!(JsonPathMatcher::check_depth(&json!({"a": 1}), 1).is_ok());
+
+        let err = JsonPathMatcher::get_values_at_path(&deep, "/**/a").unwrap_err();
+        assert!(err.to_string().contains("nested deeper than"));
+        assert!(JsonPathMatcher::get_all_changes(&deep, &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_exists_conditions() {
+        let json = json!({"spec": {
+            "syncPolicy": {"automated": {}},
+            "containers": [{"name": "app"}, {"name": "sidecar", "image": null}]
+        }});
+        let exists = |path: &str, exists: bool| PathValue {
+            path: path.to_string(),
+            exists: Some(exists),
+            ..Default::default()
+        };
+
+        let holds = |condition: PathValue| {
+            JsonPathMatcher::matches_conditions(&json, &[condition]).unwrap()
+        };
+        assert!(holds(exists("/spec/syncPolicy/automated", true)));
+        assert!(!holds(exists("/spec/syncPolicy/automated", false)));
+        assert!(holds(exists("/spec/syncPolicy/manual", false)));
+        // A null value exists, and a wildcard exists when it matches anything
+        assert!(holds(exists("/spec/containers/*/image", true)));
+        assert!(holds(exists("/spec/containers/*/ports", false)));
+        assert!(!holds(PathValue {
+            negate: true,
+            ..exists("/spec/syncPolicy/automated", true)
+        }));
+    }
 }
@@ -1,38 +1,397 @@
-use clap::Parser;
-use std::path::PathBuf;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, ValueEnum};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use crate::config::{PolicySource, Severity};
+use crate::git::{DiffMode, DiffOptions, GitDiff, SymlinkMode};
+use crate::glob::GlobSet;
+use crate::ignore::{IGNORE_FILE_NAME, IgnoreFile};
+use crate::validator::ValidatorOptions;
+
+pub mod baseline;
 pub mod config;
+pub mod error;
+pub mod formats;
 pub mod git;
+pub mod glob;
+pub mod ignore;
 pub mod json_path;
+pub mod lint;
+pub mod progress;
+pub mod report;
+pub mod schema;
 pub mod validator;
+pub mod version;
+pub mod watch;
+
+/// Default for `--max-file-bytes`: 10 MiB, far above any hand-written manifest
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default for `--git-retries`: enough to ride out a lock briefly held by another git
+pub const DEFAULT_GIT_RETRIES: u32 = 2;
+
+/// Exit code when no violation fails the run
+pub const EXIT_CLEAN: i32 = 0;
+/// Exit code when violations at or above `--fail-on` were found
+pub const EXIT_VIOLATIONS: i32 = 1;
+/// Exit code for invalid arguments, policies or file contents
+pub const EXIT_USAGE_ERROR: i32 = 2;
+/// Exit code when the diff could not be computed, e.g. because git failed
+pub const EXIT_GIT_ERROR: i32 = 3;
+/// Exit code when `--require-changes` is set and the diff contains no changed files
+pub const EXIT_NO_CHANGES: i32 = 4;
+/// Exit code when the `--output` report or `--coverage` file could not be written
+pub const EXIT_OUTPUT_ERROR: i32 = 5;
+/// Exit code when `--require-any-match` is set and no rule matched any parsed file
+pub const EXIT_NO_MATCH: i32 = 6;
 
 #[derive(Parser, Debug)]
 #[command(version, about = "Validate git diff changes against policy rules")]
 pub struct Args {
-    /// Base SHA to diff against
-    #[arg(long)]
-    pub base: String,
-    /// Path to policy YAML
-    #[arg(long)]
-    pub policy: PathBuf,
+    /// Base SHA to diff against [default: HEAD when files are given]
+    #[arg(long, required_unless_present_any = ["diff_from_stdin", "scan_dir", "check_policy", "print_schema", "files"])]
+    pub base: Option<String>,
+    /// Path to policy YAML, `-` for stdin or an http(s) URL (repeatable)
+    #[arg(long, required_unless_present_any = ["print_schema", "list_changed_files"])]
+    pub policy: Vec<PathBuf>,
+    /// Optional: validate only these files, relative to the repository, instead of asking git which files changed (e.g. as passed by pre-commit)
+    #[arg(conflicts_with = "diff_from_stdin")]
+    pub files: Vec<PathBuf>,
+    /// Optional: limit to files under this directory (repeatable). Example: --path-prefix clusters/prod
+    #[arg(long = "path-prefix")]
+    pub path_prefixes: Vec<String>,
     /// Optional: limit to files matching this suffix (repeatable). Example: --only-suffix .yaml --only-suffix .yml
     #[arg(long = "only-suffix")]
     pub only_suffixes: Vec<String>,
+    /// Optional: limit to files matching this glob (repeatable). Example: --include 'apps/**/values.yaml'
+    #[arg(long)]
+    pub include: Vec<String>,
+    /// Optional: skip files matching this glob, even if included (repeatable). Example: --exclude 'tests/**'
+    #[arg(long)]
+    pub exclude: Vec<String>,
+    /// Optional: gitignore-style file of paths to skip [default: .jiffsignore in the repository, if present]
+    #[arg(long)]
+    pub ignore_file: Option<PathBuf>,
+    /// Optional: skip changed files larger than this many bytes without reading them; 0 disables the limit
+    #[arg(long, default_value_t = DEFAULT_MAX_FILE_BYTES)]
+    pub max_file_bytes: u64,
+    /// Optional: git executable to run [default: $JIFFS_GIT, or git on PATH]
+    #[arg(long)]
+    pub git_binary: Option<PathBuf>,
+    /// Optional: kill a git command still running after this many seconds and fail; 0 waits indefinitely
+    #[arg(long, default_value_t = 0)]
+    pub git_timeout: u64,
+    /// Optional: retry a git command that timed out or failed transiently (e.g. on a held lock) this many times, with backoff
+    #[arg(long, default_value_t = DEFAULT_GIT_RETRIES)]
+    pub git_retries: u32,
+    /// Optional: validate the link text of symlinks read from disk, as git stores them, instead of the file they point to
+    #[arg(long)]
+    pub no_follow_symlinks: bool,
+    /// Optional: branch that rules with `branches` are matched against [default: the checked out branch]
+    #[arg(long)]
+    pub branch: Option<String>,
+    /// Optional: repository to validate, instead of the current directory
+    #[arg(long)]
+    pub repo: Option<PathBuf>,
+    /// Optional: compare the base against the staged index instead of the working tree
+    #[arg(long, conflicts_with = "worktree")]
+    pub staged: bool,
+    /// Optional: compare the base against the working tree (default)
+    #[arg(long)]
+    pub worktree: bool,
+    /// Optional: compare the base against this commit instead of the working tree, reading files from git
+    #[arg(long, conflicts_with_all = ["staged", "worktree", "diff_from_stdin"])]
+    pub head: Option<String>,
+    /// Optional: diff against the merge base of the base and HEAD (like `git diff <base>...HEAD`)
+    #[arg(long)]
+    pub merge_base: bool,
+    /// Optional: read `git diff --name-status [-z]` output from stdin instead of running git
+    #[arg(
+        long,
+        requires = "base_content_dir",
+        conflicts_with_all = ["base", "staged", "merge_base"]
+    )]
+    pub diff_from_stdin: bool,
+    /// Directory holding the files as they were at the base, for --diff-from-stdin
+    #[arg(long, requires = "diff_from_stdin")]
+    pub base_content_dir: Option<PathBuf>,
+    /// Directory holding the files as they are now, for --diff-from-stdin [default: the repository]
+    #[arg(long, requires = "diff_from_stdin")]
+    pub current_content_dir: Option<PathBuf>,
+    /// Optional: validate every file under this directory as newly added instead of diffing, e.g. to audit a checkout without git
+    #[arg(
+        long,
+        conflicts_with_all = ["base", "files", "staged", "head", "merge_base", "diff_from_stdin"]
+    )]
+    pub scan_dir: Option<PathBuf>,
+    /// Optional: report changed YAML/JSON files that match no policy rule as violations
+    #[arg(long)]
+    pub require_rule_match: bool,
+    /// Optional: report changed YAML/JSON files that cannot be parsed as violations
+    #[arg(long)]
+    pub fail_on_parse_error: bool,
+    /// Optional: treat documents nested deeper than this many levels as unparseable [default and maximum: 128]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=json_path::MAX_DEPTH as u64))]
+    pub max_depth: Option<u64>,
+    /// Optional: compare file contents with their exact line endings instead of reading CRLF as LF
+    #[arg(long)]
+    pub no_normalize_eol: bool,
+    /// Optional: stop after this many violations and report how many more were left out
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_violations: Option<u64>,
+    /// Optional: print, per file, how each change was checked against the matching rules
+    #[arg(long)]
+    pub explain: bool,
+    /// Optional: show how many files were validated so far on long runs (text output to a terminal only)
+    #[arg(long)]
+    pub progress: bool,
+    /// Optional: only load and lint the policy, printing a summary of its rules, without diffing
+    #[arg(long)]
+    pub check_policy: bool,
+    /// Optional: print the JSON Schema of the policy format, e.g. for editor completion, and exit
+    #[arg(long)]
+    pub print_schema: bool,
+    /// Optional: only print the changed files with their change type and sizes, without loading the policy or validating
+    #[arg(long, conflicts_with_all = ["check_policy", "print_schema", "watch"])]
+    pub list_changed_files: bool,
+    /// Optional: re-run whenever the policy or a file in the repository changes
+    #[arg(long, conflicts_with_all = ["diff_from_stdin", "write_baseline"])]
+    pub watch: bool,
     /// Optional: verbose output (prints all changed paths)
     #[arg(short, long, default_value_t = false)]
     pub verbose: bool,
+    /// Optional: print time spent in git, parsing and matching, and how much was read
+    #[arg(long)]
+    pub stats: bool,
+    /// Optional: print nothing on success and only the violations on failure
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Optional: output format for the validation results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Optional: write the --format output to this file, creating parent directories; the console then shows the text output
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Optional: write a JSON file mapping each rule id to the files it matched, creating parent directories
+    #[arg(long)]
+    pub coverage: Option<PathBuf>,
+    /// Optional: exit 0 even when violations are found; errors still exit non-zero
+    #[arg(long)]
+    pub no_exit: bool,
+    /// Optional: fail when no files changed, e.g. because the base SHA is wrong
+    #[arg(long)]
+    pub require_changes: bool,
+    /// Optional: fail when no rule matched any changed file that could be parsed, e.g. because the policy's match conditions are wrong
+    #[arg(long)]
+    pub require_any_match: bool,
+    /// Optional: JSON file of known violations to ignore, so only new violations are reported
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+    /// Optional: record the current violations in the --baseline file instead of reporting them
+    #[arg(long, requires = "baseline")]
+    pub write_baseline: bool,
+    /// Optional: lowest violation severity that causes a non-zero exit
+    #[arg(long, value_enum, default_value_t = Severity::Error)]
+    pub fail_on: Severity,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// A single JSON document describing the validation result
+    Json,
+    /// SARIF 2.1.0, e.g. for GitHub code scanning
+    Sarif,
+    /// JUnit XML with a test case per file that matched a rule
+    Junit,
+    /// GitHub Actions workflow commands, annotating violations on the pull request
+    Github,
+}
+
+impl Args {
+    /// The diff options selected on the command line
+    pub fn diff_options(&self) -> Result<DiffOptions> {
+        Ok(DiffOptions {
+            mode: if self.staged {
+                DiffMode::Staged
+            } else {
+                DiffMode::Worktree
+            },
+            head: self.head.clone(),
+            merge_base: self.merge_base,
+            path_prefixes: self.path_prefixes.clone(),
+            only_suffixes: self.only_suffixes.clone(),
+            include: GlobSet::new(&self.include)?,
+            exclude: GlobSet::new(&self.exclude)?,
+            ignore: self.ignore_file()?,
+            repo: self.repo.clone(),
+            max_file_bytes: (self.max_file_bytes > 0).then_some(self.max_file_bytes),
+            git_binary: self
+                .git_binary
+                .clone()
+                .or_else(|| std::env::var_os("JIFFS_GIT").map(PathBuf::from)),
+            symlinks: if self.no_follow_symlinks {
+                SymlinkMode::LinkText
+            } else {
+                SymlinkMode::Follow
+            },
+            git_timeout: (self.git_timeout > 0).then(|| Duration::from_secs(self.git_timeout)),
+            git_retries: self.git_retries,
+            files: self
+                .files
+                .iter()
+                .map(|path| {
+                    let path = path.strip_prefix(".").unwrap_or(path);
+                    path.to_string_lossy().into_owned()
+                })
+                .collect(),
+        })
+    }
+
+    /// Load `--ignore-file`, or `.jiffsignore` at the repository root when it exists.
+    /// Patterns are relative to the ignore file's directory when it lies inside the
+    /// repository, and to the repository root otherwise.
+    fn ignore_file(&self) -> Result<IgnoreFile> {
+        let repo = self.repo.as_deref().unwrap_or(Path::new("."));
+
+        let Some(path) = &self.ignore_file else {
+            let path = repo.join(IGNORE_FILE_NAME);
+            if !path.is_file() {
+                return Ok(IgnoreFile::default());
+            }
+            return IgnoreFile::from_file(&path, "");
+        };
+
+        let base = path
+            .canonicalize()
+            .ok()
+            .zip(repo.canonicalize().ok())
+            .and_then(|(file, repo)| {
+                let relative = file.parent()?.strip_prefix(repo).ok()?;
+                Some(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .unwrap_or_default();
+
+        IgnoreFile::from_file(path, &base)
+    }
+
+    /// Compute the diff selected on the command line, either by running git, from
+    /// name-status output on stdin or from the files under `--scan-dir`
+    pub fn git_diff(&self) -> Result<GitDiff> {
+        let options = self.diff_options()?;
+
+        if self.diff_from_stdin {
+            let mut name_status = String::new();
+            std::io::stdin()
+                .read_to_string(&mut name_status)
+                .context("Failed to read diff from stdin")?;
+
+            return Ok(GitDiff::from_name_status(
+                &name_status,
+                self.base_content_dir.as_deref().unwrap_or(Path::new(".")),
+                self.current_content_dir
+                    .as_deref()
+                    .unwrap_or(options.repo_dir()),
+                &options,
+            )?);
+        }
+
+        if let Some(dir) = &self.scan_dir {
+            return Ok(GitDiff::from_dir(dir, &options)?);
+        }
+
+        let base = match &self.base {
+            Some(base) => base,
+            None if !self.files.is_empty() => "HEAD",
+            None => bail!("--base is required"),
+        };
+
+        Ok(GitDiff::new(base, &options)?)
+    }
+
+    /// The validator options selected on the command line
+    pub fn validator_options(&self) -> ValidatorOptions {
+        ValidatorOptions {
+            require_rule_match: self.require_rule_match,
+            explain: self.explain && self.console_format() == OutputFormat::Text,
+            fail_on_parse_error: self.fail_on_parse_error,
+            manifest_suffixes: self.only_suffixes.clone(),
+            max_violations: self.max_violations.map(|max| max as usize),
+            max_depth: self.max_depth.map(|max| max as usize),
+            preserve_line_endings: self.no_normalize_eol,
+            // Drawn on stderr, but only when stdout is an interactive terminal too, and
+            // never mixed with the per-file output of --verbose and --explain
+            progress: self.progress
+                && self.console_format() == OutputFormat::Text
+                && !self.quiet
+                && !self.verbose
+                && !self.explain
+                && std::io::stdout().is_terminal()
+                && std::io::stderr().is_terminal(),
+            branch: self.branch.clone(),
+        }
+    }
+
+    /// Format of the results printed to the console: `--format`, or text when the
+    /// `--format` output goes to the `--output` file instead
+    pub fn console_format(&self) -> OutputFormat {
+        if self.output.is_some() {
+            OutputFormat::Text
+        } else {
+            self.format
+        }
+    }
+
+    /// `--branch`, or else the branch checked out in the repository. Without git, e.g.
+    /// with `--diff-from-stdin` or `--scan-dir`, the branch is only known when given.
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        if self.branch.is_some() || self.diff_from_stdin || self.scan_dir.is_some() {
+            return Ok(self.branch.clone());
+        }
+
+        Ok(git::current_branch(&self.diff_options()?)?)
+    }
 }
 
-/// Parse command line arguments and validate the policy file exists
-pub fn parse_args() -> Args {
+/// Parse command line arguments and validate the policy files exist
+pub fn parse_args() -> Result<Args> {
     let args = Args::parse();
 
-    if !args.policy.exists() || !args.policy.is_file() {
-        panic!(
-            "Policy file does not exist or is not a file: {:?}",
-            args.policy
+    let mut stdin_policies = 0;
+    for policy in &args.policy {
+        match PolicySource::of(policy) {
+            PolicySource::File(path) if !path.is_file() => {
+                bail!("Policy file does not exist or is not a file: {:?}", policy);
+            }
+            PolicySource::Stdin => stdin_policies += 1,
+            _ => {}
+        }
+    }
+
+    if stdin_policies > 1 {
+        bail!("--policy - can only be given once");
+    }
+    if stdin_policies == 1 && args.diff_from_stdin {
+        bail!("--policy - and --diff-from-stdin cannot both read stdin");
+    }
+    if stdin_policies == 1 && args.watch {
+        bail!("--watch cannot re-read a policy from stdin");
+    }
+
+    // Surface invalid globs as usage errors rather than failures computing the diff
+    args.diff_options()?;
+
+    if let Some(repo) = &args.repo
+        && !repo.is_dir()
+    {
+        bail!(
+            "Repository does not exist or is not a directory: {:?}",
+            repo
         );
     }
 
-    args
+    Ok(args)
 }
@@ -0,0 +1,209 @@
+use crate::config::{Config, Rule};
+use crate::json_path::{IndexRange, JsonPathMatcher};
+
+/// A suspicious but valid construct in a policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub rule_id: String,
+    pub message: String,
+}
+
+/// Look for policy constructs that load fine but are likely mistakes
+pub fn lint(config: &Config) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    for rule in &config.rules {
+        let mut warn = |message: String| {
+            warnings.push(LintWarning {
+                rule_id: rule.id(),
+                message,
+            })
+        };
+
+        for message in redundant_allowed_changes(rule) {
+            warn(message);
+        }
+        for message in unaligned_when_conditions(rule) {
+            warn(message);
+        }
+    }
+
+    warnings
+}
+
+/// `allowedChanges` patterns that only authorize changes another pattern already covers
+fn redundant_allowed_changes(rule: &Rule) -> Vec<String> {
+    let Some(patterns) = &rule.allowed_changes else {
+        return Vec::new();
+    };
+
+    let mut messages = Vec::new();
+
+    for (i, narrow) in patterns.iter().enumerate() {
+        // A pattern is tested as if it were a path; `**` only fits under another `**`
+        let covering = patterns.iter().enumerate().find(|(j, broad)| {
+            *j != i
+                && broad.path != narrow.path
+                && narrow.ops.iter().all(|op| broad.ops.contains(op))
+                && (broad.allowed_values.is_none() || broad.allowed_values == narrow.allowed_values)
+                && (!narrow.path.split('/').any(|s| s == "**")
+                    || broad.path.split('/').any(|s| s == "**"))
+                && JsonPathMatcher::matching_pattern_at(
+                    &narrow.path,
+                    std::slice::from_ref(&broad.path),
+                    rule.granularity,
+                )
+                .is_some()
+        });
+
+        if let Some((_, broad)) = covering {
+            messages.push(format!(
+                "allowedChanges pattern '{}' is already covered by '{}'",
+                narrow, broad
+            ));
+        }
+    }
+
+    messages
+}
+
+/// `when` and `unless` wildcards that no `allowedChanges` pattern shares. A `*` in a
+/// condition path is resolved to the index of the changed element only along the prefix
+/// the path shares with the change, so a wildcard after the paths diverge matches any
+/// element.
+fn unaligned_when_conditions(rule: &Rule) -> Vec<String> {
+    let Some(patterns) = &rule.allowed_changes else {
+        return Vec::new();
+    };
+    let conditions = rule
+        .when_conditions
+        .iter()
+        .flatten()
+        .map(|condition| ("when", condition))
+        .chain(rule.unless.iter().map(|condition| ("unless", condition)));
+
+    let is_index = |segment: &str| {
+        segment == "*" || segment.parse::<usize>().is_ok() || IndexRange::parse(segment).is_some()
+    };
+    let patterns: Vec<Vec<&str>> = patterns
+        .iter()
+        .map(|pattern| pattern.path.trim_start_matches('/').split('/').collect())
+        .collect();
+
+    let mut messages = Vec::new();
+
+    for (label, condition) in conditions {
+        let segments: Vec<&str> = condition.path.trim_start_matches('/').split('/').collect();
+
+        for (i, segment) in segments.iter().enumerate() {
+            if *segment != "*" && IndexRange::parse(segment).is_none() {
+                continue;
+            }
+
+            let aligned = patterns.iter().any(|pattern| {
+                pattern.len() > i
+                    && is_index(pattern[i])
+                    && segments[..i]
+                        .iter()
+                        .zip(pattern)
+                        .all(|(s, p)| s == p || (is_index(s) && is_index(p)))
+            });
+
+            if !aligned {
+                let key = if i > 0 { segments[i - 1] } else { "" };
+                messages.push(format!(
+                    "{} path '{}' has a wildcard under '{}' that no allowedChanges pattern shares the path to, so it matches any element rather than the changed one",
+                    label, condition.path, key
+                ));
+            }
+        }
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(yaml: &str) -> Vec<String> {
+        let config: Config = yaml.parse().unwrap();
+        lint(&config).into_iter().map(|w| w.message).collect()
+    }
+
+    #[test]
+    fn test_redundant_allowed_changes() {
+        let warnings = messages(
+            r#"
+rules:
+  - id: app
+    match: []
+    allowedChanges:
+    - /spec/*/image
+    - /spec/web/image
+    - /spec/**
+    - /metadata/labels/team
+    - path: /metadata/labels/env
+      ops: [modify]
+    - path: /metadata/labels/*
+      ops: [add]
+"#,
+        );
+
+        assert_eq!(
+            warnings,
+            vec![
+                "allowedChanges pattern '/spec/*/image' is already covered by '/spec/**'",
+                "allowedChanges pattern '/spec/web/image' is already covered by '/spec/*/image'",
+            ]
+        );
+
+        // At subtree granularity a pattern covers everything below it
+        let warnings = messages(
+            "rules:\n  - match: []\n    granularity: subtree\n    allowedChanges: [/spec, /spec/replicas]\n",
+        );
+        assert_eq!(
+            warnings,
+            vec!["allowedChanges pattern '/spec/replicas' is already covered by '/spec'"]
+        );
+    }
+
+    #[test]
+    fn test_unaligned_when_conditions() {
+        let aligned = r#"
+rules:
+  - match: []
+    allowedChanges:
+    - /spec/generators/*/clusters/values/revision
+    when:
+    - path: /spec/generators/*/clusters/selector/matchLabels/env
+      value: development
+    - path: /metadata/labels/env
+      value: development
+"#;
+        assert!(messages(aligned).is_empty());
+
+        let unaligned = aligned.replace(
+            "/spec/generators/*/clusters/selector",
+            "/spec/templates/*/clusters/selector",
+        );
+        let warnings = messages(&unaligned);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("wildcard under 'templates'"));
+
+        // A wildcard after the paths diverge is not aligned, even under a shared key name
+        let diverging = aligned.replace(
+            "/spec/generators/*/clusters/selector/matchLabels/env",
+            "/spec/selectors/generators/*/env",
+        );
+        let warnings = messages(&diverging);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("wildcard under 'generators'"));
+
+        // `unless` paths are aligned the same way
+        let unless = unaligned.replace("    when:\n", "    unless:\n");
+        let warnings = messages(&unless);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("unless path '/spec/templates/*/"));
+    }
+}
@@ -1,18 +1,145 @@
-use anyhow::Result;
-use jiffs::{config::Config, git::GitDiff, parse_args, validator::Validator};
+use anyhow::Context;
+use jiffs::{
+    Args, EXIT_CLEAN, EXIT_GIT_ERROR, EXIT_NO_CHANGES, EXIT_NO_MATCH, EXIT_OUTPUT_ERROR,
+    EXIT_USAGE_ERROR, EXIT_VIOLATIONS, OutputFormat,
+    baseline::Baseline,
+    config::{Config, PolicySource, Severity},
+    git::ChangeType,
+    lint, parse_args,
+    report::{github, junit, sarif},
+    schema,
+    validator::ValidationResult,
+    validator::Validator,
+    watch,
+};
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-fn main() -> Result<()> {
-    let args = parse_args();
+/// How long changes must settle before --watch re-runs
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// An error and the exit code it maps to
+type Failure = (i32, anyhow::Error);
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| fail(EXIT_USAGE_ERROR, err));
+
+    if args.print_schema {
+        let schema = schema::policy_schema().unwrap_or_else(|err| fail(EXIT_USAGE_ERROR, err));
+        println!("{}", to_json(&schema));
+        return;
+    }
+
+    if args.watch {
+        watch(&args);
+    }
+
+    let run = if args.list_changed_files {
+        list_changed_files
+    } else {
+        run
+    };
+    match run(&args) {
+        Ok(EXIT_CLEAN) => {}
+        Ok(code) => std::process::exit(code),
+        Err((code, err)) => fail(code, err),
+    }
+}
+
+/// Run once, then again after every change to the policy files or the repository,
+/// reporting errors without exiting
+fn watch(args: &Args) -> ! {
+    // Only policy files can be watched; remote policies are fetched again on every run
+    let mut paths: Vec<PathBuf> = args
+        .policy
+        .iter()
+        .filter(|policy| matches!(PolicySource::of(policy), PolicySource::File(_)))
+        .cloned()
+        .collect();
+    paths.push(
+        args.scan_dir
+            .clone()
+            .or_else(|| args.repo.clone())
+            .unwrap_or_else(|| PathBuf::from(".")),
+    );
+    let watcher = watch::Watcher::new(&paths).unwrap_or_else(|err| fail(EXIT_USAGE_ERROR, err));
+
+    loop {
+        // Clear the screen so only the latest results are shown
+        print!("\x1B[2J\x1B[1;1H");
+        if let Err((_, err)) = run(args) {
+            eprintln!("Error: {:#}", err);
+        }
+
+        println!();
+        println!("Watching for changes (press Ctrl-C to stop)...");
+        if let Err(err) = watcher.wait_for_change(WATCH_DEBOUNCE) {
+            fail(EXIT_USAGE_ERROR, err);
+        }
+    }
+}
+
+/// Validate once, returning the exit code
+fn run(args: &Args) -> Result<i32, Failure> {
+    // Informational lines are only printed for human-readable, non-quiet output
+    let text = args.console_format() == OutputFormat::Text && !args.quiet;
 
     // Load configuration
-    let config = Config::from_file(&args.policy)?;
-    println!("Loaded {} rule(s) from policy file", config.rules.len());
+    let config = Config::from_files(&args.policy).map_err(|err| (EXIT_USAGE_ERROR, err.into()))?;
+    if text {
+        println!(
+            "Loaded {} rule(s) from {} policy file(s)",
+            config.rules.len(),
+            args.policy.len()
+        );
+    }
+
+    if args.verbose && text {
+        for rule in &config.rules {
+            if let Some(location) = rule.location() {
+                println!("  {} from {}", rule.id(), location);
+            }
+        }
+    }
+
+    if args.check_policy {
+        print_policy_summary(&config);
+        return Ok(EXIT_CLEAN);
+    }
 
     // Get git diff
-    println!("Analyzing changes from base SHA: {}", args.base);
-    let git_diff = GitDiff::new(&args.base, &args.only_suffixes)?;
+    if text {
+        match &args.base {
+            Some(base) => println!("Analyzing changes from base SHA: {}", base),
+            None if args.diff_from_stdin => println!("Analyzing changes read from stdin"),
+            None if let Some(dir) = &args.scan_dir => {
+                println!("Scanning all files under {}", dir.display())
+            }
+            None => println!("Analyzing changes from base SHA: HEAD"),
+        }
+    }
+    let diffing = Instant::now();
+    let git_diff = args.git_diff().map_err(|err| (EXIT_GIT_ERROR, err))?;
+    let git_time = diffing.elapsed();
+
+    if args.require_changes && git_diff.changed_files.is_empty() {
+        return Err((
+            EXIT_NO_CHANGES,
+            anyhow::anyhow!("No changed files found; check that the base ref is correct"),
+        ));
+    }
+
+    if !args.quiet {
+        let oversized = format!(
+            "larger than {} bytes (see --max-file-bytes)",
+            args.max_file_bytes
+        );
+        print_skipped(args, &git_diff.oversized_files, &oversized);
+        print_skipped(args, &git_diff.non_utf8_files, "that are not valid UTF-8");
+    }
 
-    if args.verbose {
+    if args.verbose && text {
         println!("Found {} changed file(s):", git_diff.changed_files.len());
         for path in git_diff.changed_file_paths() {
             println!("  {}", path);
@@ -21,33 +148,373 @@ fn main() -> Result<()> {
     }
 
     // Validate changes
-    let validator = Validator::new(config);
-    let result = validator.validate(&git_diff, args.verbose)?;
+    let mut options = args.validator_options();
+    // The branch is only looked up when a rule depends on it
+    if config.rules.iter().any(|rule| !rule.branches.is_empty()) {
+        options.branch = args.current_branch().map_err(|err| (EXIT_GIT_ERROR, err))?;
+        if args.verbose && text {
+            match &options.branch {
+                Some(branch) => println!("Validating for branch: {}", branch),
+                None => println!("Branch unknown; rules with branches do not apply"),
+            }
+        }
+    }
+    let validator = Validator::with_options(config, options);
+    let mut result = validator
+        .validate(&git_diff, args.verbose && text)
+        .map_err(|err| (EXIT_USAGE_ERROR, err.into()))?;
+
+    for (file_path, lines) in &result.explanations {
+        println!("Explain: {}", file_path);
+        for line in lines {
+            println!("  {}", line);
+        }
+        println!();
+    }
+
+    if let Some(path) = &args.baseline {
+        if args.write_baseline {
+            let baseline = Baseline::from_result(&result);
+            baseline
+                .write(path)
+                .map_err(|err| (EXIT_USAGE_ERROR, err))?;
+            if !args.quiet {
+                println!(
+                    "Wrote {} known violation(s) to {}",
+                    baseline.violations.len(),
+                    path.display()
+                );
+            }
+            return Ok(EXIT_CLEAN);
+        }
+
+        let baseline = Baseline::from_file(path).map_err(|err| (EXIT_USAGE_ERROR, err))?;
+        let suppressed = baseline.apply(&mut result);
+        if text && suppressed > 0 {
+            println!(
+                "Suppressed {} known violation(s) listed in the baseline",
+                suppressed
+            );
+        }
+    }
 
     // Output results
-    println!("Validation Results:");
-    println!("  Files processed: {}", result.files_processed);
-    println!("  Files matched rules: {}", result.files_matched);
-    println!("  Violations found: {}", result.violations.len());
+    match args.console_format() {
+        OutputFormat::Text if args.quiet => {
+            if result.fails_at(args.fail_on) {
+                print!("{}", violations_report(&result));
+            }
+        }
+        format => print!("{}", report(&result, args, format)),
+    }
+
+    if let Some(path) = &args.output {
+        write_report(path, &report(&result, args, args.format))
+            .map_err(|err| (EXIT_OUTPUT_ERROR, err))?;
+    }
+
+    if let Some(path) = &args.coverage {
+        write_report(path, &format!("{}\n", to_json(&result.coverage())))
+            .map_err(|err| (EXIT_OUTPUT_ERROR, err))?;
+    }
+
+    if args.stats {
+        print_stats(&result, git_diff.changed_files.len(), git_time, text);
+    }
+
+    if args.require_any_match && result.files_parsed > 0 && result.files_matched == 0 {
+        return Err((
+            EXIT_NO_MATCH,
+            anyhow::anyhow!(
+                "No rule matched any of the {} parsed changed file(s); check the policy's match conditions and the base ref",
+                result.files_parsed
+            ),
+        ));
+    }
+
+    if text {
+        println!("{}", result.summary_line(args.fail_on));
+    }
+
+    if result.fails_at(args.fail_on) && !args.no_exit {
+        return Ok(EXIT_VIOLATIONS);
+    }
+
+    Ok(EXIT_CLEAN)
+}
+
+/// Print what the diff holds, without loading the policy, to check the base and filters
+fn list_changed_files(args: &Args) -> Result<i32, Failure> {
+    let git_diff = args.git_diff().map_err(|err| (EXIT_GIT_ERROR, err))?;
+    let bytes = |content: &Option<String>| content.as_ref().map(String::len);
+
+    if args.console_format() == OutputFormat::Json {
+        let files: Vec<serde_json::Value> = git_diff
+            .changed_file_paths()
+            .into_iter()
+            .map(|path| {
+                let change = &git_diff.changed_files[path];
+                let from = match &change.change_type {
+                    ChangeType::Renamed { from } | ChangeType::Copied { from } => Some(from),
+                    _ => None,
+                };
+                serde_json::json!({
+                    "path": path,
+                    "change_type": change.change_type.to_string(),
+                    "from": from,
+                    "base_bytes": bytes(&change.base_content),
+                    "current_bytes": bytes(&change.current_content),
+                })
+            })
+            .collect();
+        println!("{}", to_json(&files));
+    } else {
+        for path in git_diff.changed_file_paths() {
+            let change = &git_diff.changed_files[path];
+            let size = |content: &Option<String>| {
+                bytes(content).map_or("-".to_string(), |bytes| format!("{} bytes", bytes))
+            };
+            let from = match &change.change_type {
+                ChangeType::Renamed { from } | ChangeType::Copied { from } => {
+                    format!(" (from {})", from)
+                }
+                _ => String::new(),
+            };
+            println!(
+                "{:<8} {}{}  base: {}, current: {}",
+                change.change_type.to_string(),
+                path,
+                from,
+                size(&change.base_content),
+                size(&change.current_content)
+            );
+        }
+    }
+
+    if !args.quiet {
+        let oversized = format!(
+            "larger than {} bytes (see --max-file-bytes)",
+            args.max_file_bytes
+        );
+        print_skipped(args, &git_diff.oversized_files, &oversized);
+        print_skipped(args, &git_diff.non_utf8_files, "that are not valid UTF-8");
+    }
+
+    Ok(EXIT_CLEAN)
+}
+
+/// Report an error and exit with the given code
+fn fail(code: i32, err: anyhow::Error) -> ! {
+    eprintln!("Error: {:#}", err);
+    std::process::exit(code);
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("validation results serialize to JSON")
+}
+
+fn print_policy_summary(config: &Config) {
+    println!();
+    for rule in &config.rules {
+        match &rule.name {
+            Some(name) => println!("Rule {} ({})", rule.id(), name),
+            None => println!("Rule {}", rule.id()),
+        }
+        if !rule.files.is_empty() {
+            println!("  files: {}", rule.files.patterns().join(", "));
+        }
+        for condition in &rule.match_conditions {
+            println!("  match: {}", condition);
+        }
+        println!(
+            "  allowedChanges: {}, forbiddenChanges: {}, requiredChanges: {}",
+            rule.allowed_changes
+                .as_ref()
+                .map_or("any".to_string(), |patterns| patterns.len().to_string()),
+            rule.forbidden_changes.len(),
+            rule.required_changes.len()
+        );
+    }
+
+    let warnings = lint::lint(config);
+    if !warnings.is_empty() {
+        println!();
+        println!("Warnings:");
+        for warning in &warnings {
+            println!("  [{}] {}", warning.rule_id, warning.message);
+        }
+    }
+
+    println!();
+    println!("✅ Policy is well-formed");
+}
+
+/// Note files left out of the diff, to stderr for machine-readable output since skipped
+/// files are worth knowing about either way
+fn print_skipped(args: &Args, paths: &[String], why: &str) {
+    if paths.is_empty() {
+        return;
+    }
+
+    let text = args.console_format() == OutputFormat::Text;
+    let note = format!("Skipped {} file(s) {}", paths.len(), why);
+    if text {
+        println!("{}", note);
+    } else {
+        eprintln!("{}", note);
+    }
+    if args.verbose && text {
+        for path in paths {
+            println!("  {}", path);
+        }
+    }
+}
+
+/// Print timings and counts, to stderr when stdout holds machine-readable output
+fn print_stats(result: &ValidationResult, files_fetched: usize, git_time: Duration, text: bool) {
+    let stats = &result.stats;
+    if text {
+        println!();
+    }
+    let lines = [
+        "Statistics:".to_string(),
+        format!(
+            "  git: {:.3}s, {} file(s) fetched",
+            git_time.as_secs_f64(),
+            files_fetched
+        ),
+        format!(
+            "  parsing: {:.3}s, {} file(s), {} byte(s)",
+            stats.parse_time.as_secs_f64(),
+            stats.files_parsed,
+            stats.bytes_parsed
+        ),
+        format!("  matching: {:.3}s", stats.match_time.as_secs_f64()),
+    ];
+
+    for line in lines {
+        if text {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// The results in the given format, as printed or written to `--output`
+fn report(result: &ValidationResult, args: &Args, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Text => text_report(result, args.fail_on, args.verbose),
+        OutputFormat::Json => format!("{}\n", to_json(result)),
+        OutputFormat::Junit => junit::to_junit(result, args.fail_on),
+        OutputFormat::Github => github::to_annotations(result),
+        OutputFormat::Sarif => format!("{}\n", to_json(&sarif::to_sarif(result, &args.policy))),
+    }
+}
+
+/// Write a report to `path`, creating its parent directories
+fn write_report(path: &Path, report: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    std::fs::write(path, report)
+        .with_context(|| format!("Failed to write report to {}", path.display()))
+}
+
+fn text_report(result: &ValidationResult, fail_on: Severity, verbose: bool) -> String {
+    let mut out = String::new();
+    write_text(&mut out, result, fail_on, verbose).expect("writing to a String cannot fail");
+    out
+}
+
+fn violations_report(result: &ValidationResult) -> String {
+    let mut out = String::new();
+    write_violations(&mut out, result).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_text(
+    out: &mut String,
+    result: &ValidationResult,
+    fail_on: Severity,
+    verbose: bool,
+) -> fmt::Result {
+    writeln!(out, "Validation Results:")?;
+    writeln!(out, "  Files processed: {}", result.files_processed)?;
+    writeln!(out, "  Files matched rules: {}", result.files_matched)?;
+    writeln!(
+        out,
+        "  Violations found: {} ({} error(s), {} warning(s), {} info)",
+        result.violations.len(),
+        result.error_count,
+        result.warning_count,
+        result.info_count
+    )?;
+
+    if verbose && !result.rule_stats.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Rule statistics:")?;
+        for (rule_id, stats) in &result.rule_stats {
+            writeln!(
+                out,
+                "  {}: {} file(s) matched, {} violation(s)",
+                rule_id, stats.files_matched, stats.violations_count
+            )?;
+        }
+    }
 
     if !result.violations.is_empty() {
-        println!("\nViolations:");
-        for violation in &result.violations {
-            println!("  File: {}", violation.file_path);
-            println!("    Rule: {}", violation.rule_description);
-            println!("    Unauthorized changes:");
-            for change in &violation.unauthorized_changes {
-                println!("      - {}", change);
-            }
-            println!();
+        writeln!(out)?;
+        write_violations(out, result)?;
+    }
+
+    if !result.fails_at(fail_on) {
+        writeln!(
+            out,
+            "✅ All changes are valid according to the policy rules"
+        )
+    } else {
+        writeln!(out, "❌ Policy violations found")
+    }
+}
+
+fn write_violations(out: &mut String, result: &ValidationResult) -> fmt::Result {
+    writeln!(out, "Violations:")?;
+    for violation in &result.violations {
+        writeln!(
+            out,
+            "  [{}] File: {}",
+            violation.severity, violation.file_path
+        )?;
+        writeln!(out, "    Rule: {}", violation.rule_description)?;
+        writeln!(out, "    Rule ID: {}", violation.rule_id)?;
+        if let Some(source) = &violation.rule_source {
+            writeln!(out, "    Rule source: {}", source)?;
         }
+        writeln!(out, "    Unauthorized changes:")?;
+        for change in &violation.unauthorized_changes {
+            writeln!(out, "      - {}", change.summary())?;
+        }
+        writeln!(out)?;
     }
 
-    if result.is_valid {
-        println!("✅ All changes are valid according to the policy rules");
-        return Ok(());
+    if result.omitted_violations > 0 {
+        writeln!(out, "  ... and {} more", result.omitted_violations)?;
+    }
+    if result.truncated {
+        writeln!(
+            out,
+            "  Stopped at --max-violations; some files were not checked"
+        )?;
+    }
+    if result.omitted_violations > 0 || result.truncated {
+        writeln!(out)?;
     }
 
-    println!("❌ Policy violations found");
-    std::process::exit(1);
+    Ok(())
 }
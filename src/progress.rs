@@ -0,0 +1,36 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// A single-line `done/total` counter drawn on stderr, so piped stdout is never touched
+#[derive(Debug)]
+pub struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize, enabled: bool) -> Self {
+        let bar = if enabled {
+            ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stderr())
+                .with_style(
+                    ProgressStyle::with_template("{msg}: {pos}/{len}")
+                        .expect("the progress template is valid"),
+                )
+                .with_message(label)
+        } else {
+            ProgressBar::hidden()
+        };
+
+        Self { bar }
+    }
+
+    /// Record that `done` items are finished; redraws are rate-limited by indicatif
+    pub fn update(&mut self, done: usize) {
+        self.bar.set_position(done as u64);
+    }
+}
+
+impl Drop for Progress {
+    /// Erase the counter so that the output that follows starts on a clean line
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
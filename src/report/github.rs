@@ -0,0 +1,121 @@
+use crate::config::Severity;
+use crate::validator::{ValidationResult, Violation};
+
+/// Render each violation as a GitHub Actions workflow command, one per line, so it
+/// shows up as a file-level annotation on the pull request
+pub fn to_annotations(result: &ValidationResult) -> String {
+    result
+        .violations
+        .iter()
+        .map(|violation| annotation(violation) + "\n")
+        .collect()
+}
+
+fn annotation(violation: &Violation) -> String {
+    let mut message = violation.rule_description.clone();
+    for change in &violation.unauthorized_changes {
+        message.push_str("\n- ");
+        message.push_str(&change.summary());
+    }
+
+    format!(
+        "::{} file={},title={}::{}",
+        command(violation.severity),
+        escape_property(&violation.file_path),
+        escape_property(&violation.rule_id),
+        escape_data(&message)
+    )
+}
+
+/// Map a rule severity onto a workflow command
+fn command(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "notice",
+    }
+}
+
+/// Escape a command's message so line breaks cannot end the command early
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a command property value, which additionally may not contain `:` or `,`
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::UnauthorizedChange;
+
+    fn result(violations: Vec<Violation>) -> ValidationResult {
+        ValidationResult {
+            is_valid: false,
+            violations,
+            files_processed: 1,
+            files_parsed: 1,
+            files_matched: 1,
+            matched_files: Vec::new(),
+            error_count: 1,
+            warning_count: 0,
+            info_count: 0,
+            rule_stats: Default::default(),
+            omitted_violations: 0,
+            truncated: false,
+            stats: Default::default(),
+            explanations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_annotations_are_escaped() {
+        let violation = Violation {
+            file_path: "apps/web,prod:1.yaml".to_string(),
+            rule_id: "revisions".to_string(),
+            rule_name: None,
+            severity: Severity::Error,
+            rule_description: "100% of\nchanges".to_string(),
+            rule_source: None,
+            unauthorized_changes: vec![UnauthorizedChange {
+                path: "/spec/replicas".to_string(),
+                old: Some(serde_json::json!(1)),
+                new: Some(serde_json::json!(2)),
+                reason: None,
+            }],
+        };
+
+        assert_eq!(
+            to_annotations(&result(vec![violation])),
+            "::error file=apps/web%2Cprod%3A1.yaml,title=revisions::100%25 of%0Achanges%0A- /spec/replicas: 1 -> 2\n"
+        );
+    }
+
+    #[test]
+    fn test_severity_selects_command() {
+        let violation = |severity| Violation {
+            file_path: "a.yaml".to_string(),
+            rule_id: "rule".to_string(),
+            rule_name: None,
+            severity,
+            rule_description: "Rule".to_string(),
+            rule_source: None,
+            unauthorized_changes: Vec::new(),
+        };
+
+        let output = to_annotations(&result(vec![
+            violation(Severity::Warning),
+            violation(Severity::Info),
+        ]));
+
+        assert_eq!(
+            output,
+            "::warning file=a.yaml,title=rule::Rule\n::notice file=a.yaml,title=rule::Rule\n"
+        );
+    }
+}
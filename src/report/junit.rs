@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use crate::config::Severity;
+use crate::validator::{ValidationResult, Violation};
+
+/// Convert a validation result into a JUnit XML test suite. Every changed file that
+/// matched a rule is a test case; violations at or above `fail_on` are failures and
+/// lower-severity violations are reported as the test case's output.
+pub fn to_junit(result: &ValidationResult, fail_on: Severity) -> String {
+    let mut cases: BTreeMap<&str, Vec<&Violation>> = result
+        .matched_files
+        .iter()
+        .map(|file| (file.as_str(), Vec::new()))
+        .collect();
+
+    for violation in &result.violations {
+        cases
+            .entry(violation.file_path.as_str())
+            .or_default()
+            .push(violation);
+    }
+
+    let failures = cases
+        .values()
+        .filter(|violations| violations.iter().any(|v| v.severity >= fail_on))
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\">\n",
+        env!("CARGO_PKG_NAME"),
+        cases.len(),
+        failures
+    ));
+
+    for (file, violations) in cases {
+        xml.push_str(&format!(
+            "  <testcase name=\"{}\" classname=\"{}\"",
+            escape(file),
+            env!("CARGO_PKG_NAME")
+        ));
+
+        if violations.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+
+        xml.push_str(">\n");
+
+        let (failed, reported): (Vec<_>, Vec<_>) =
+            violations.into_iter().partition(|v| v.severity >= fail_on);
+
+        for violation in failed {
+            xml.push_str(&format!(
+                "    <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                escape(&violation.rule_description),
+                escape(&violation.rule_id),
+                escape(&details(violation))
+            ));
+        }
+
+        if !reported.is_empty() {
+            let output = reported
+                .iter()
+                .map(|v| format!("[{}] {}\n{}", v.severity, v.rule_description, details(v)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            xml.push_str(&format!(
+                "    <system-out>{}</system-out>\n",
+                escape(&output)
+            ));
+        }
+
+        xml.push_str("  </testcase>\n");
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// The unauthorized changes of a violation, one per line
+fn details(violation: &Violation) -> String {
+    violation
+        .unauthorized_changes
+        .iter()
+        .map(|change| format!("{}\n", change.summary()))
+        .collect()
+}
+
+/// Escape text for use in XML attributes and element content
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::UnauthorizedChange;
+
+    fn violation(file_path: &str, severity: Severity) -> Violation {
+        Violation {
+            file_path: file_path.to_string(),
+            rule_id: "revisions".to_string(),
+            rule_name: None,
+            severity,
+            rule_description: "Rule \"revisions\" allows <revision>".to_string(),
+            rule_source: None,
+            unauthorized_changes: vec![UnauthorizedChange {
+                path: "/spec/replicas".to_string(),
+                old: Some(serde_json::json!(1)),
+                new: Some(serde_json::json!(2)),
+                reason: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_matched_files_become_test_cases() {
+        let result = ValidationResult {
+            is_valid: false,
+            violations: vec![
+                violation("b.yaml", Severity::Error),
+                violation("c.yaml", Severity::Warning),
+            ],
+            files_processed: 4,
+            files_parsed: 4,
+            files_matched: 3,
+            matched_files: vec![
+                "a.yaml".to_string(),
+                "b.yaml".to_string(),
+                "c.yaml".to_string(),
+            ],
+            error_count: 1,
+            warning_count: 1,
+            info_count: 0,
+            rule_stats: Default::default(),
+            omitted_violations: 0,
+            truncated: false,
+            stats: Default::default(),
+            explanations: Vec::new(),
+        };
+
+        let xml = to_junit(&result, Severity::Error);
+
+        assert!(xml.contains("<testsuite name=\"jiffs\" tests=\"3\" failures=\"1\" errors=\"0\">"));
+        assert!(xml.contains("<testcase name=\"a.yaml\" classname=\"jiffs\"/>"));
+        assert!(xml.contains(
+            "<failure message=\"Rule &quot;revisions&quot; allows &lt;revision&gt;\" type=\"revisions\">/spec/replicas: 1 -&gt; 2\n</failure>"
+        ));
+        assert!(xml.contains("<system-out>[warning] Rule &quot;revisions&quot;"));
+        assert_eq!(xml.matches("<failure").count(), 1);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod github;
+pub mod junit;
+pub mod sarif;
@@ -0,0 +1,167 @@
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+use crate::config::Severity;
+use crate::validator::ValidationResult;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Convert a validation result into a SARIF 2.1.0 log with a single run
+pub fn to_sarif(result: &ValidationResult, policy_paths: &[PathBuf]) -> Value {
+    let mut rule_ids: Vec<String> = Vec::new();
+    let mut rules = Vec::new();
+    let mut results = Vec::new();
+
+    for violation in &result.violations {
+        let rule_id = violation.rule_id.clone();
+
+        let rule_index = match rule_ids.iter().position(|id| *id == rule_id) {
+            Some(index) => index,
+            None => {
+                rules.push(json!({
+                    "id": rule_id,
+                    "name": violation.rule_name,
+                    "shortDescription": {
+                        "text": violation.rule_description,
+                    },
+                }));
+                rule_ids.push(rule_id.clone());
+                rule_ids.len() - 1
+            }
+        };
+
+        results.push(json!({
+            "ruleId": rule_id,
+            "ruleIndex": rule_index,
+            "level": level(violation.severity),
+            "message": {
+                "text": violation.rule_description,
+            },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": {
+                        "uri": relative_uri(&violation.file_path),
+                        "uriBaseId": "%SRCROOT%",
+                    },
+                },
+            }],
+            "properties": {
+                "unauthorizedChanges": violation.unauthorized_changes,
+            },
+        }));
+    }
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+            "properties": {
+                "policies": policy_paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>(),
+            },
+        }],
+    })
+}
+
+/// Map a rule severity onto a SARIF result level
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+/// Percent-encode a repository path so it forms a valid relative URI reference
+fn relative_uri(path: &str) -> String {
+    let mut uri = String::with_capacity(path.len());
+
+    for byte in path.trim_start_matches("./").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::{UnauthorizedChange, Violation};
+
+    fn violation(file_path: &str, rule_id: &str) -> Violation {
+        Violation {
+            file_path: file_path.to_string(),
+            rule_id: rule_id.to_string(),
+            rule_name: None,
+            severity: Severity::Error,
+            rule_description: format!("Rule {}", rule_id),
+            rule_source: None,
+            unauthorized_changes: vec![UnauthorizedChange {
+                path: "/metadata/name".to_string(),
+                old: Some(serde_json::json!("a")),
+                new: Some(serde_json::json!("b")),
+                reason: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_rules_are_listed_once() {
+        let result = ValidationResult {
+            is_valid: false,
+            violations: vec![
+                violation("a.yaml", "first"),
+                violation("b.yaml", "first"),
+                violation("c.yaml", "second"),
+            ],
+            files_processed: 3,
+            files_parsed: 3,
+            files_matched: 3,
+            matched_files: Vec::new(),
+            error_count: 3,
+            warning_count: 0,
+            info_count: 0,
+            rule_stats: Default::default(),
+            omitted_violations: 0,
+            truncated: false,
+            stats: Default::default(),
+            explanations: Vec::new(),
+        };
+
+        let sarif = to_sarif(&result, &[PathBuf::from("rules.yaml")]);
+        let run = &sarif["runs"][0];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 2);
+        assert_eq!(run["results"].as_array().unwrap().len(), 3);
+        assert_eq!(run["results"][1]["ruleId"], "first");
+        assert_eq!(run["results"][2]["ruleIndex"], 1);
+        assert_eq!(run["results"][0]["message"]["text"], "Rule first");
+    }
+
+    #[test]
+    fn test_relative_uri() {
+        assert_eq!(relative_uri("apps/app.yaml"), "apps/app.yaml");
+        assert_eq!(
+            relative_uri("config files/app.yaml"),
+            "config%20files/app.yaml"
+        );
+        assert_eq!(relative_uri("./apps/app.yaml"), "apps/app.yaml");
+    }
+}
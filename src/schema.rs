@@ -0,0 +1,55 @@
+use anyhow::Result;
+use serde_json::Value;
+
+/// JSON Schema of the policy file format, for editor completion and validation,
+/// derived from the policy types in [`crate::config`]
+#[cfg(feature = "schema")]
+pub fn policy_schema() -> Result<Value> {
+    let mut schema = schemars::schema_for!(crate::config::Config);
+    schema.insert("title".to_string(), "jiffs policy".into());
+
+    Ok(serde_json::to_value(schema)?)
+}
+
+#[cfg(not(feature = "schema"))]
+pub fn policy_schema() -> Result<Value> {
+    anyhow::bail!("--print-schema requires jiffs to be built with the schema feature")
+}
+
+#[cfg(all(test, feature = "schema"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_schema() {
+        let schema = policy_schema().unwrap();
+        let defs = &schema["$defs"];
+
+        assert_eq!(schema["title"], "jiffs policy");
+        assert_eq!(schema["required"], serde_json::json!(["rules"]));
+        assert_eq!(
+            schema["properties"]["rules"]["items"]["$ref"],
+            "#/$defs/Rule"
+        );
+
+        // Fields are named as they are written in policies
+        let rule = &defs["Rule"]["properties"];
+        assert!(rule["allowedChanges"].is_object());
+        assert!(rule["match"].is_object());
+        assert!(rule.get("source").is_none());
+        assert_eq!(rule["files"]["type"], "array");
+        assert_eq!(rule["files"]["items"]["type"], "string");
+        assert_eq!(rule["allowNewFiles"]["default"], true);
+        assert_eq!(
+            defs["RuleDefaults"]["additionalProperties"],
+            serde_json::json!(false)
+        );
+
+        // Entries with a custom format are described by the form they are written in
+        let allowed_change = serde_json::to_string(&defs["AllowedChange"]).unwrap();
+        assert!(allowed_change.contains("allowedValues"));
+        let allowed_value = serde_json::to_string(&defs["AllowedValue"]).unwrap();
+        assert!(allowed_value.contains("\"glob\""));
+        assert!(allowed_value.contains("\"regex\""));
+    }
+}
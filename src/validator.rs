@@ -1,203 +1,945 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::config::{Config, Rule};
-use crate::git::{ChangeType, GitDiff};
-use crate::json_path::JsonPathMatcher;
+use crate::config::{AllowedChange, Config, Rule, Severity};
+use crate::error::JiffsError;
+use crate::formats;
+use crate::git::{ChangeType, FileChange, GitDiff};
+use crate::json_path::{
+    ChangeKind, ChangeMap, ChangePattern, Explanation, JsonPathMatcher, MAX_DEPTH,
+};
+use crate::progress::Progress;
 
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize)]
 pub struct ValidationResult {
     pub is_valid: bool,
     pub violations: Vec<Violation>,
     pub files_processed: usize,
+    /// Changed files whose content could be parsed, whether or not a rule matched them
+    pub files_parsed: usize,
     pub files_matched: usize,
+    /// Changed files that matched at least one rule, sorted
+    pub matched_files: Vec<String>,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub info_count: usize,
+    /// Per rule id, how many files the rule matched and how many violations it raised.
+    /// Every configured rule is listed, so rules that never match show up as zeros.
+    pub rule_stats: BTreeMap<String, RuleStats>,
+    /// Violations found beyond `--max-violations` and left out of `violations`
+    pub omitted_violations: usize,
+    /// Whether validation stopped at `--max-violations` before checking every file
+    pub truncated: bool,
+    /// Where validation spent its time, reported with `--stats`
+    #[serde(skip)]
+    pub stats: ValidationStats,
+    /// With [`ValidatorOptions::explain`], the decisions recorded for each checked file,
+    /// in the order the files were checked
+    #[serde(skip)]
+    pub explanations: Vec<(String, Vec<String>)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RuleStats {
+    pub files_matched: usize,
+    pub violations_count: usize,
+    /// The files the rule matched, sorted; written with `--coverage`
+    #[serde(skip)]
+    pub files: Vec<String>,
+}
+
+/// Time spent parsing changed files and matching them against the rules
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationStats {
+    pub parse_time: Duration,
+    pub match_time: Duration,
+    /// Changed files whose content was parsed
+    pub files_parsed: usize,
+    /// Size of the base and current content of the parsed files
+    pub bytes_parsed: usize,
+}
+
+impl ValidationResult {
+    /// Whether any violation is at or above the given severity
+    pub fn fails_at(&self, threshold: Severity) -> bool {
+        self.violations.iter().any(|v| v.severity >= threshold)
+    }
+
+    /// A single line summing up the result for log scrapers, e.g.
+    /// `jiffs: result=fail files=120 matched=15 violations=3 errors=2 warnings=1`. The
+    /// result is `fail` when a violation is at or above `fail_on`. The format is stable:
+    /// fields are only ever appended.
+    pub fn summary_line(&self, fail_on: Severity) -> String {
+        format!(
+            "jiffs: result={} files={} matched={} violations={} errors={} warnings={}",
+            if self.fails_at(fail_on) {
+                "fail"
+            } else {
+                "pass"
+            },
+            self.files_processed,
+            self.files_matched,
+            self.violations.len(),
+            self.error_count,
+            self.warning_count
+        )
+    }
+
+    /// Keep at most `max` violations, preferring the most severe ones so the result still
+    /// fails when it would have with every violation, and count the rest as omitted
+    pub fn truncate(&mut self, max: usize) {
+        if self.violations.len() <= max {
+            return;
+        }
+
+        let mut order: Vec<usize> = (0..self.violations.len()).collect();
+        order.sort_by_key(|&i| Reverse(self.violations[i].severity));
+        let keep: BTreeSet<usize> = order.into_iter().take(max).collect();
+
+        self.omitted_violations += self.violations.len() - max;
+        let mut index = 0;
+        self.violations.retain(|_| {
+            index += 1;
+            keep.contains(&(index - 1))
+        });
+        self.recount();
+    }
+
+    /// The files each rule matched, keyed by rule id. Every configured rule is listed,
+    /// so rules that matched nothing map to an empty list.
+    pub fn coverage(&self) -> BTreeMap<&str, &[String]> {
+        self.rule_stats
+            .iter()
+            .map(|(rule_id, stats)| (rule_id.as_str(), stats.files.as_slice()))
+            .collect()
+    }
+
+    /// Recompute the counts derived from the violations, e.g. after some were removed
+    pub fn recount(&mut self) {
+        let count = |severity| {
+            self.violations
+                .iter()
+                .filter(|v| v.severity == severity)
+                .count()
+        };
+        self.error_count = count(Severity::Error);
+        self.warning_count = count(Severity::Warning);
+        self.info_count = count(Severity::Info);
+        self.is_valid = self.error_count == 0;
+
+        for stats in self.rule_stats.values_mut() {
+            stats.violations_count = 0;
+        }
+        for violation in &self.violations {
+            if let Some(stats) = self.rule_stats.get_mut(&violation.rule_id) {
+                stats.violations_count += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Violation {
     pub file_path: String,
+    pub rule_id: String,
+    pub rule_name: Option<String>,
+    pub severity: Severity,
     pub rule_description: String,
-    pub unauthorized_changes: Vec<String>,
+    /// Where the rule is defined, as `file:line`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_source: Option<String>,
+    pub unauthorized_changes: Vec<UnauthorizedChange>,
+}
+
+/// A single change that a rule does not authorize
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnauthorizedChange {
+    /// JSON Pointer of the change, empty when the change concerns the whole file
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+    /// Why the change is unauthorized, when it is not simply outside the allow-list
+    pub reason: Option<String>,
+}
+
+impl From<ChangeExplanation> for UnauthorizedChange {
+    fn from(change: ChangeExplanation) -> Self {
+        Self {
+            path: change.path,
+            old: change.old,
+            new: change.new,
+            reason: change.reason,
+        }
+    }
+}
+
+impl UnauthorizedChange {
+    /// The change with its old and new values, marking absent values explicitly
+    pub fn summary(&self) -> String {
+        match (&self.old, &self.new) {
+            (None, None) => self.to_string(),
+            (old, new) => format!(
+                "{}: {} -> {}",
+                self,
+                display_value(old.as_ref()),
+                display_value(new.as_ref())
+            ),
+        }
+    }
+}
+
+/// Render an optional value for reports, marking absent values explicitly
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "(absent)".to_string(),
+    }
+}
+
+impl fmt::Display for UnauthorizedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.path.is_empty(), &self.reason) {
+            (true, Some(reason)) => write!(f, "{}", reason),
+            (false, Some(reason)) => write!(f, "{} ({})", self.path, reason),
+            (_, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// How a rule judges one change, see [`Validator::explain_file`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangeExplanation {
+    /// JSON Pointer of the change
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+    /// The forbidden, removals or allowed pattern that matched the change, if any
+    pub pattern: Option<String>,
+    /// Whether the rule's `when` conditions held, `None` when they were not evaluated
+    pub when_met: Option<bool>,
+    pub allowed: bool,
+    /// Why the change is rejected, when it is not simply outside the allow-list
+    pub reason: Option<String>,
+}
+
+/// How a rule judges every change between two documents, see [`Validator::explain_file`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileExplanation {
+    pub rule_id: String,
+    /// Every change, in path order
+    pub changes: Vec<ChangeExplanation>,
+    /// Required patterns that no change matched
+    pub missing_required: Vec<String>,
+    /// Whether every change is allowed and no required change is missing
+    pub is_valid: bool,
+}
+
+/// One YAML/JSON document of a changed file, parsed once and shared by every rule
+struct Document {
+    /// Path reported in violations; documents of multi-document files are suffixed
+    /// with `#<index>`
+    path: String,
+    /// Path of the file the document was read from
+    file_path: String,
+    change_type: ChangeType,
+    /// Base document, `None` when there is no base content and `Err` when it could
+    /// not be parsed. `Ok(None)` means the base has fewer documents.
+    base: Option<Result<Option<Value>>>,
+    current: Option<Value>,
+}
+
+/// Rule id reported for changed files that no rule matched
+pub const UNCOVERED_RULE_ID: &str = "uncovered-file";
+
+/// Rule id reported for changed YAML/JSON files that could not be parsed
+pub const PARSE_ERROR_RULE_ID: &str = "parse-error";
+
+/// File extensions treated as manifests when no suffixes are configured, besides
+/// [`formats::TOML_EXTENSIONS`] and [`formats::HCL_EXTENSIONS`]
+const MANIFEST_EXTENSIONS: &[&str] = &["yaml", "yml", "json"];
+
+/// Options that change how a diff is validated, independent of the policy itself
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorOptions {
+    /// Report added or modified YAML/JSON files that match no rule as violations
+    pub require_rule_match: bool,
+    /// Record, per file, how every change was checked against each matching rule, in
+    /// [`ValidationResult::explanations`]
+    pub explain: bool,
+    /// Report YAML/JSON files that cannot be parsed as violations instead of skipping them
+    pub fail_on_parse_error: bool,
+    /// Suffixes of files expected to be YAML/JSON, defaulting to `.yaml`, `.yml`
+    /// and `.json`
+    pub manifest_suffixes: Vec<String>,
+    /// Stop checking further files once this many violations were found, and report
+    /// only this many
+    pub max_violations: Option<usize>,
+    /// Draw a files processed / total counter on stderr while validating
+    pub progress: bool,
+    /// Branch being validated, which rules with `branches` must match to apply. Such
+    /// rules never apply when it is unknown.
+    pub branch: Option<String>,
+    /// Deepest nesting of objects and arrays accepted in a document, at most and by
+    /// default [`MAX_DEPTH`]. Deeper documents are treated as unparseable.
+    pub max_depth: Option<usize>,
+    /// Parse content with its line endings as they are, instead of reading CRLF as LF
+    /// so that values differing only in line endings compare equal
+    pub preserve_line_endings: bool,
+}
+
+impl ValidatorOptions {
+    /// The effective [`Self::max_depth`]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH)
+    }
+
+    /// Whether a file is expected to be YAML/JSON, as opposed to another file type
+    /// that is skipped because it cannot be parsed
+    pub fn is_manifest(&self, file_path: &str) -> bool {
+        let file_path = file_path.to_lowercase();
+
+        if self.manifest_suffixes.is_empty() {
+            MANIFEST_EXTENSIONS
+                .iter()
+                .chain(formats::TOML_EXTENSIONS)
+                .chain(formats::HCL_EXTENSIONS)
+                .any(|extension| file_path.ends_with(&format!(".{}", extension)))
+        } else {
+            self.manifest_suffixes
+                .iter()
+                .any(|suffix| file_path.ends_with(&suffix.to_lowercase()))
+        }
+    }
 }
 
 pub struct Validator {
     config: Config,
+    options: ValidatorOptions,
+    /// Whether each rule applies to [`ValidatorOptions::branch`]
+    on_branch: Vec<bool>,
 }
 
 impl Validator {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self::with_options(config, ValidatorOptions::default())
     }
 
-    pub fn validate(&self, git_diff: &GitDiff, verbose: bool) -> Result<ValidationResult> {
+    pub fn with_options(config: Config, options: ValidatorOptions) -> Self {
+        let on_branch = config
+            .rules
+            .iter()
+            .map(|rule| rule.applies_to_branch(options.branch.as_deref()))
+            .collect();
+
+        Self {
+            config,
+            options,
+            on_branch,
+        }
+    }
+
+    pub fn validate(
+        &self,
+        git_diff: &GitDiff,
+        verbose: bool,
+    ) -> Result<ValidationResult, JiffsError> {
+        let mut result = self
+            .validate_file_changes(&git_diff.changed_files, verbose)
+            .map_err(JiffsError::validation)?;
+
+        if self.options.fail_on_parse_error {
+            for file_path in &git_diff.non_utf8_files {
+                if self.options.is_manifest(file_path) {
+                    result.violations.push(Self::parse_error(
+                        file_path,
+                        "content is not valid UTF-8".to_string(),
+                    ));
+                }
+            }
+            result
+                .violations
+                .sort_by(|a, b| a.file_path.cmp(&b.file_path));
+            result.recount();
+        }
+
+        if let Some(max) = self.options.max_violations {
+            result.truncate(max);
+        }
+
+        Ok(result)
+    }
+
+    /// Validate in-memory changes keyed by file path. Nothing here runs git or reads
+    /// files, so policies can be checked against synthetic diffs.
+    pub fn validate_changes(
+        &self,
+        changes: &HashMap<String, FileChange>,
+    ) -> Result<ValidationResult, JiffsError> {
+        self.validate_file_changes(changes, false)
+            .map_err(JiffsError::validation)
+    }
+
+    fn validate_file_changes(
+        &self,
+        changes: &HashMap<String, FileChange>,
+        verbose: bool,
+    ) -> Result<ValidationResult> {
         let mut violations = Vec::new();
-        let mut files_matched = 0;
+        let mut matched_files = Vec::new();
+        let mut rule_stats: BTreeMap<String, RuleStats> = self
+            .config
+            .rules
+            .iter()
+            .map(|rule| (rule.id(), RuleStats::default()))
+            .collect();
+        let mut stats = ValidationStats::default();
+        let mut files_processed = 0;
+        let mut files_parsed = 0;
+        let mut truncated = false;
+        let mut explanations = Vec::new();
 
-        for (file_path, file_change) in &git_diff.changed_files {
-            if verbose {
-                println!("Processing file: {}", file_path);
+        // Files are checked in path order, so violations come out in the same order on
+        // every run and a capped run reports the same ones
+        let mut file_paths: Vec<&String> = changes.keys().collect();
+        file_paths.sort();
+        let mut progress = Progress::new("Validating files", changes.len(), self.options.progress);
+
+        for file_path in file_paths {
+            if self
+                .options
+                .max_violations
+                .is_some_and(|max| violations.len() >= max)
+            {
+                truncated = true;
+                break;
             }
 
-            // For deleted files, we need to check the base content to see if it would match rules
-            let json_for_rule_matching = if file_change.change_type == ChangeType::Deleted {
-                match &file_change.base_content {
-                    Some(content) => match Self::parse_yaml_or_json(content) {
-                        Ok(json) => json,
-                        Err(_) => {
-                            if verbose {
-                                println!("  Skipping non-YAML/JSON deleted file: {}", file_path);
-                            }
-                            continue;
-                        }
-                    },
-                    None => {
-                        if verbose {
-                            println!(
-                                "  No base content available for deleted file: {}",
-                                file_path
-                            );
-                        }
-                        continue;
-                    }
-                }
+            let file_change = &changes[file_path];
+            files_processed += 1;
+            let mut explanation = if self.options.explain {
+                Explanation::new()
             } else {
-                let current_content = match &file_change.current_content {
-                    Some(content) => content,
-                    None => continue,
-                };
+                Explanation::disabled()
+            };
 
-                match Self::parse_yaml_or_json(current_content) {
-                    Ok(json) => json,
-                    Err(_) => {
-                        if verbose {
-                            println!("  Skipping non-YAML/JSON file: {}", file_path);
-                        }
-                        continue;
-                    }
+            let matched_rules = self.validate_file(
+                file_path,
+                file_change,
+                verbose,
+                &mut explanation,
+                &mut violations,
+                &mut stats,
+            )?;
+
+            progress.update(files_processed);
+
+            if let Some(matched_rules) = matched_rules {
+                files_parsed += 1;
+                if !matched_rules.is_empty() {
+                    matched_files.push(file_path.clone());
+                }
+                for rule_id in matched_rules {
+                    let stats = rule_stats.entry(rule_id).or_default();
+                    stats.files_matched += 1;
+                    stats.files.push(file_path.clone());
                 }
+            }
+
+            if self.options.explain {
+                explanations.push((file_path.clone(), explanation.lines().to_vec()));
+            }
+        }
+
+        drop(progress);
+        matched_files.sort();
+        for violation in &mut violations {
+            violation
+                .unauthorized_changes
+                .sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        let mut result = ValidationResult {
+            is_valid: true,
+            violations,
+            files_processed,
+            files_parsed,
+            files_matched: matched_files.len(),
+            matched_files,
+            error_count: 0,
+            warning_count: 0,
+            info_count: 0,
+            rule_stats,
+            omitted_violations: 0,
+            truncated,
+            stats,
+            explanations,
+        };
+        result.recount();
+        if let Some(max) = self.options.max_violations {
+            result.truncate(max);
+        }
+
+        Ok(result)
+    }
+
+    /// Validate a single changed file against every matching rule, returning the ids
+    /// of the rules that matched it, or `None` when the file was skipped as unparseable
+    fn validate_file(
+        &self,
+        file_path: &str,
+        file_change: &FileChange,
+        verbose: bool,
+        explanation: &mut Explanation,
+        violations: &mut Vec<Violation>,
+        stats: &mut ValidationStats,
+    ) -> Result<Option<BTreeSet<String>>> {
+        if verbose {
+            println!("Processing file: {}", file_path);
+        }
+
+        let parsing = Instant::now();
+        let documents =
+            Self::documents(file_path, file_change, &self.options, verbose, explanation);
+        stats.parse_time += parsing.elapsed();
+
+        let documents = match documents {
+            Ok(documents) => {
+                stats.files_parsed += 1;
+                stats.bytes_parsed += [&file_change.base_content, &file_change.current_content]
+                    .into_iter()
+                    .flatten()
+                    .map(String::len)
+                    .sum::<usize>();
+                documents
+            }
+            Err(Some(err))
+                if self.options.fail_on_parse_error && self.options.is_manifest(file_path) =>
+            {
+                violations.push(Self::parse_error(file_path, format!("{:#}", err)));
+
+                return Ok(None);
+            }
+            Err(_) => return Ok(None),
+        };
+
+        let matching = Instant::now();
+        let multiple = documents.len() > 1;
+        let mut matched_rules = BTreeSet::new();
+
+        for document in &documents {
+            if multiple {
+                explanation.note(0, || format!("document {}", document.path));
+                explanation.indent();
+            }
+
+            matched_rules.extend(self.validate_document(
+                document,
+                verbose,
+                explanation,
+                violations,
+            )?);
+
+            if multiple {
+                explanation.dedent();
+            }
+        }
+        stats.match_time += matching.elapsed();
+
+        Ok(Some(matched_rules))
+    }
+
+    /// Violation reported under `--fail-on-parse-error` for a manifest that could not be read
+    fn parse_error(file_path: &str, reason: String) -> Violation {
+        Violation {
+            file_path: file_path.to_string(),
+            rule_id: PARSE_ERROR_RULE_ID.to_string(),
+            rule_name: None,
+            severity: Severity::Error,
+            rule_description: "File could not be parsed as YAML or JSON".to_string(),
+            rule_source: None,
+            unauthorized_changes: vec![UnauthorizedChange {
+                path: String::new(),
+                old: None,
+                new: None,
+                reason: Some(reason),
+            }],
+        }
+    }
+
+    /// Parse a changed file into its documents. Files with several YAML documents are
+    /// split, pairing base and current documents by position. Fails when the file is
+    /// skipped, with the parse error if its current content is not YAML/JSON.
+    fn documents(
+        file_path: &str,
+        file_change: &FileChange,
+        options: &ValidatorOptions,
+        verbose: bool,
+        explanation: &mut Explanation,
+    ) -> std::result::Result<Vec<Document>, Option<anyhow::Error>> {
+        let deleted = file_change.change_type == ChangeType::Deleted;
+
+        // For deleted files, we need to check the base content to see if it would match rules
+        let current = if deleted {
+            Vec::new()
+        } else {
+            let Some(content) = &file_change.current_content else {
+                return Err(None);
             };
 
-            for rule in &self.config.rules {
-                if Self::file_matches_rule(&json_for_rule_matching, rule) {
-                    files_matched += 1;
+            match Self::parse_documents(content, file_path, options) {
+                Ok(documents) => documents,
+                Err(err) => {
+                    if verbose {
+                        println!("  Skipping non-YAML/JSON file: {}", file_path);
+                    }
+                    explanation.note(0, || "skipped: not YAML/JSON".to_string());
+                    return Err(Some(err));
+                }
+            }
+        };
 
+        let base = match (&file_change.change_type, &file_change.base_content) {
+            (ChangeType::Added, _) => None,
+            (_, Some(content)) => Some(Self::parse_documents(content, file_path, options)),
+            (_, None) => None,
+        };
+
+        if deleted {
+            match &base {
+                Some(Ok(_)) => {}
+                Some(Err(_)) => {
+                    if verbose {
+                        println!("  Skipping non-YAML/JSON deleted file: {}", file_path);
+                    }
+                    explanation.note(0, || "skipped: not YAML/JSON".to_string());
+                    return Err(None);
+                }
+                None => {
                     if verbose {
                         println!(
-                            "  File matches rule with {} match conditions",
-                            rule.match_conditions.len()
+                            "  No base content available for deleted file: {}",
+                            file_path
                         );
                     }
+                    return Err(None);
+                }
+            }
+        }
+
+        let base_count = match &base {
+            Some(Ok(documents)) => documents.len(),
+            _ => 1,
+        };
+        let count = base_count.max(current.len());
+
+        if count <= 1 {
+            return Ok(vec![Document {
+                path: file_path.to_string(),
+                file_path: file_path.to_string(),
+                change_type: file_change.change_type.clone(),
+                base: base.map(|base| base.map(|documents| documents.into_iter().next())),
+                current: current.into_iter().next(),
+            }]);
+        }
+
+        let documents = (0..count)
+            .map(|index| {
+                let base = base.as_ref().map(|base| match base {
+                    Ok(documents) => Ok(documents.get(index).cloned()),
+                    Err(err) => Err(anyhow::anyhow!("{:#}", err)),
+                });
+                let current = current.get(index).cloned();
+
+                // Documents only present on one side were added or deleted
+                let change_type = match (&file_change.change_type, &base, &current) {
+                    (ChangeType::Added | ChangeType::Deleted, _, _) => {
+                        file_change.change_type.clone()
+                    }
+                    (_, Some(Ok(None)), _) => ChangeType::Added,
+                    (_, _, None) => ChangeType::Deleted,
+                    (change_type, _, _) => change_type.clone(),
+                };
+
+                Document {
+                    path: format!("{}#{}", file_path, index),
+                    file_path: file_path.to_string(),
+                    change_type,
+                    base,
+                    current,
+                }
+            })
+            .collect();
+
+        Ok(documents)
+    }
+
+    /// Evaluate every matching rule against one document, returning the ids of the
+    /// rules that matched
+    fn validate_document(
+        &self,
+        document: &Document,
+        verbose: bool,
+        explanation: &mut Explanation,
+        violations: &mut Vec<Violation>,
+    ) -> Result<Vec<String>> {
+        let json_for_rule_matching = if document.change_type == ChangeType::Deleted {
+            match &document.base {
+                Some(Ok(Some(json))) => json,
+                _ => return Ok(Vec::new()),
+            }
+        } else {
+            match &document.current {
+                Some(json) => json,
+                None => return Ok(Vec::new()),
+            }
+        };
+
+        let mut matched_rules = Vec::new();
+
+        let rules = self
+            .config
+            .rules
+            .iter()
+            .zip(&self.on_branch)
+            .filter(|(_, on_branch)| **on_branch)
+            .map(|(rule, _)| rule);
+
+        for rule in rules {
+            if Self::file_matches_rule(json_for_rule_matching, &document.file_path, rule)? {
+                matched_rules.push(rule.id());
 
-                    if let Some(violation) =
-                        self.validate_file_against_rule(file_path, file_change, rule, verbose)?
-                    {
-                        violations.push(violation);
+                if verbose {
+                    println!(
+                        "  File matches rule with {} match conditions",
+                        rule.match_conditions.len()
+                    );
+                }
+
+                explanation.note(0, || format!("rule {} matched", rule.id()));
+                explanation.indent();
+                let violation =
+                    self.validate_file_against_rule(document, rule, verbose, explanation)?;
+                explanation.dedent();
+
+                if let Some(mut violation) = violation {
+                    if let Some(message) = &rule.message {
+                        violation.rule_description =
+                            Self::fill_message(message, violation.unauthorized_changes.first());
                     }
+                    violations.push(violation);
+                }
 
+                // Every matching rule is enforced unless the rule opts into first-match-wins
+                if rule.stop_on_match {
                     break;
                 }
+            } else if verbose {
+                println!(
+                    "  Rule {} does not match: {}",
+                    rule.id(),
+                    Self::match_failure(json_for_rule_matching, &document.file_path, rule)?
+                );
             }
         }
 
-        Ok(ValidationResult {
-            is_valid: violations.is_empty(),
-            violations,
-            files_processed: git_diff.changed_files.len(),
-            files_matched,
-        })
+        let matched = !matched_rules.is_empty();
+
+        if !matched {
+            explanation.note(0, || "no rule matched".to_string());
+        }
+
+        if !matched
+            && self.options.require_rule_match
+            && document.change_type != ChangeType::Deleted
+        {
+            if verbose {
+                println!("  File is not covered by any policy rule");
+            }
+
+            violations.push(Violation {
+                file_path: document.path.clone(),
+                rule_id: UNCOVERED_RULE_ID.to_string(),
+                rule_name: None,
+                severity: Severity::Error,
+                rule_description: "File not covered by any policy rule".to_string(),
+                rule_source: None,
+                unauthorized_changes: vec![UnauthorizedChange {
+                    path: String::new(),
+                    old: None,
+                    new: None,
+                    reason: Some("File not covered by any policy rule".to_string()),
+                }],
+            });
+        }
+
+        Ok(matched_rules)
     }
 
     fn validate_file_against_rule(
         &self,
-        file_path: &str,
-        file_change: &crate::git::FileChange,
+        document: &Document,
         rule: &Rule,
         verbose: bool,
+        explanation: &mut Explanation,
     ) -> Result<Option<Violation>> {
-        // For new files, we allow any content that matches the rule
-        if file_change.change_type == ChangeType::Added {
+        let file_path = document.path.as_str();
+
+        if document.change_type == ChangeType::Added {
+            // By default, we allow any content in new files that match the rule
+            if rule.allow_new_files {
+                if verbose {
+                    println!("  New file - allowing all content");
+                }
+                explanation.note(0, || "new file: allowed by allowNewFiles".to_string());
+
+                return Ok(None);
+            }
+
+            let Some(current_json) = &document.current else {
+                return Ok(None);
+            };
+
+            // Otherwise every leaf of the new file is validated as an addition
+            let changes = JsonPathMatcher::get_all_leaves(current_json);
+            explanation.note(0, || {
+                "new file: every value is checked as an addition".to_string()
+            });
+            let unauthorized_changes = self
+                .find_unauthorized_changes(&changes, None, current_json, rule, explanation)
+                .with_context(|| format!("Failed to validate new file {}", file_path))?;
+
+            if unauthorized_changes.is_empty() {
+                if verbose {
+                    println!("  New file - all content is authorized");
+                }
+
+                return Ok(None);
+            }
+
             if verbose {
-                println!("  New file - allowing all content");
+                println!("  New file - found unauthorized content");
             }
 
-            return Ok(None);
+            return Ok(Some(Violation {
+                file_path: file_path.to_string(),
+                rule_id: rule.id(),
+                rule_name: rule.name.clone(),
+                severity: rule.severity,
+                rule_description: Self::describe_restrictions(rule),
+                rule_source: rule.location(),
+                unauthorized_changes,
+            }));
         }
 
-        // For deleted files, this is always a violation since they matched a rule
-        if file_change.change_type == ChangeType::Deleted {
+        // For deleted files, this is a violation unless the rule allows deletion
+        if document.change_type == ChangeType::Deleted {
+            if rule.allow_deletion {
+                if verbose {
+                    println!("  File deletion - allowed by rule");
+                }
+                explanation.note(0, || "deletion: allowed by allowDeletion".to_string());
+
+                return Ok(None);
+            }
+
             if verbose {
                 println!("  File deletion - violation (matches rule)");
             }
+            explanation.note(0, || "deletion: prohibited".to_string());
             return Ok(Some(Violation {
                 file_path: file_path.to_string(),
-                rule_description: format!(
-                    "Rule matching {:?} prohibits deletion of files",
-                    rule.match_conditions
-                        .iter()
-                        .map(|c| format!("{}={}", c.path, c.value))
-                        .collect::<Vec<_>>()
-                ),
-                unauthorized_changes: vec!["File deletion".to_string()],
+                rule_id: rule.id(),
+                rule_name: rule.name.clone(),
+                severity: rule.deletion_severity.unwrap_or(rule.severity),
+                rule_description: format!("{} prohibits deletion of files", rule.describe()),
+                rule_source: rule.location(),
+                unauthorized_changes: vec![UnauthorizedChange {
+                    path: String::new(),
+                    old: None,
+                    new: None,
+                    reason: Some("File deletion".to_string()),
+                }],
             }));
         }
 
-        // Parse base content for modified files
-        let base_content = match &file_change.base_content {
-            Some(content) => content,
-            None => {
+        if verbose {
+            match &document.change_type {
+                ChangeType::Renamed { from } => println!("  Renamed from {}", from),
+                ChangeType::Copied { from } => println!("  Copied from {}", from),
+                _ => {}
+            }
+        }
+
+        // Base content of modified, renamed and copied files
+        let base_json = match &document.base {
+            Some(Ok(Some(json))) => json,
+            Some(Err(err)) => {
+                return Err(anyhow::Error::from(JiffsError::Parse(anyhow::anyhow!(
+                    "{:#}", err
+                ))))
+                .with_context(|| format!("Failed to parse base content for {}", file_path));
+            }
+            _ => {
                 if verbose {
                     println!("  No base content available - allowing changes");
                 }
+                explanation.note(0, || "no base content: changes allowed".to_string());
                 return Ok(None);
             }
         };
 
-        let base_json = Self::parse_yaml_or_json(base_content)
-            .with_context(|| format!("Failed to parse base content for {}", file_path))?;
-
         // Get current content for comparison
-        let current_json = match &file_change.current_content {
-            Some(content) => Self::parse_yaml_or_json(content)
-                .with_context(|| format!("Failed to parse current content for {}", file_path))?,
-            None => {
-                return Err(anyhow::anyhow!(
-                    "No current content available for modified file: {}",
-                    file_path
-                ));
-            }
+        let Some(current_json) = &document.current else {
+            return Err(anyhow::anyhow!(
+                "No current content available for modified file: {}",
+                file_path
+            ));
         };
 
         // Check if changes are allowed
-        let changes_allowed = JsonPathMatcher::has_allowed_changes_only(
-            &base_json,
-            &current_json,
-            &rule.allowed_changes,
-            rule.when_conditions.as_deref(),
-        )
-        .with_context(|| format!("Failed to validate changes for {}", file_path))?;
+        let changes =
+            JsonPathMatcher::get_all_changes_keyed(base_json, current_json, &rule.array_keys)?;
+        if changes.is_empty() {
+            explanation.note(0, || "no changes".to_string());
+        }
+        let mut unauthorized_changes = self
+            .find_unauthorized_changes(&changes, Some(base_json), current_json, rule, explanation)
+            .with_context(|| format!("Failed to validate changes for {}", file_path))?;
 
-        if !changes_allowed {
+        // The path change itself is only judged when the rule forbids renames
+        if let ChangeType::Renamed { from } = &document.change_type
+            && !rule.allow_renames
+        {
+            explanation.note(0, || format!("rename from {}: prohibited", from));
+            unauthorized_changes.insert(
+                0,
+                UnauthorizedChange {
+                    path: String::new(),
+                    old: None,
+                    new: None,
+                    reason: Some(format!("File renamed from {}", from)),
+                },
+            );
+        }
+
+        if !unauthorized_changes.is_empty() {
             if verbose {
                 println!("  Found unauthorized changes");
             }
 
-            let unauthorized_changes = self.find_unauthorized_changes(
-                &base_json,
-                &current_json,
-                &rule.allowed_changes,
-                rule.when_conditions.as_deref(),
-            )?;
-
             return Ok(Some(Violation {
                 file_path: file_path.to_string(),
-                rule_description: format!(
-                    "Rule matching {:?} allows only changes to: {:?}",
-                    rule.match_conditions
-                        .iter()
-                        .map(|c| format!("{}={}", c.path, c.value))
-                        .collect::<Vec<_>>(),
-                    rule.allowed_changes
-                ),
+                rule_id: rule.id(),
+                rule_name: rule.name.clone(),
+                severity: rule.severity,
+                rule_description: Self::describe_restrictions(rule),
+                rule_source: rule.location(),
                 unauthorized_changes,
             }));
         }
@@ -208,39 +950,476 @@ impl Validator {
         Ok(None)
     }
 
+    /// The changes of each document of a changed file before any rule applies, keyed by
+    /// the document path violations use. Every value of an added document is reported
+    /// as an addition and every value of a deleted one as a removal. `None` when the
+    /// file is skipped because its content is not YAML/JSON.
+    pub(crate) fn document_changes(
+        file_path: &str,
+        file_change: &FileChange,
+    ) -> Result<Option<Vec<(String, ChangeMap)>>> {
+        let options = ValidatorOptions::default();
+        let Ok(documents) = Self::documents(
+            file_path,
+            file_change,
+            &options,
+            false,
+            &mut Explanation::disabled(),
+        ) else {
+            return Ok(None);
+        };
+
+        let mut changes = Vec::with_capacity(documents.len());
+        for document in documents {
+            let base = match document.base {
+                Some(Ok(base)) => base,
+                Some(Err(err)) => {
+                    return Err(anyhow::Error::from(JiffsError::Parse(err))).with_context(|| {
+                        format!("Failed to parse base content for {}", file_path)
+                    });
+                }
+                None => None,
+            };
+
+            let document_changes = match (&base, &document.current) {
+                (Some(base), Some(current)) => JsonPathMatcher::get_all_changes(base, current)?,
+                (None, Some(current)) => JsonPathMatcher::get_all_leaves(current),
+                (Some(base), None) => JsonPathMatcher::get_all_leaves(base)
+                    .into_iter()
+                    .map(|(path, (_, value))| (path, (value, None)))
+                    .collect(),
+                (None, None) => ChangeMap::new(),
+            };
+            changes.push((document.path, document_changes));
+        }
+
+        Ok(Some(changes))
+    }
+
+    /// Explain how `rule` judges every change from `base` to `current`, as if the
+    /// rule applied to the document: its `files` and `match` conditions are not checked.
+    /// Nothing is printed and no git diff is needed.
+    pub fn explain_file(
+        base: &Value,
+        current: &Value,
+        rule: &Rule,
+    ) -> Result<FileExplanation, JiffsError> {
+        let changes = JsonPathMatcher::get_all_changes_keyed(base, current, &rule.array_keys)
+            .map_err(JiffsError::validation)?;
+        let (changes, missing_required) = Self::judge_changes(
+            &changes,
+            Some(base),
+            current,
+            rule,
+            &mut Explanation::disabled(),
+        )
+        .map_err(JiffsError::validation)?;
+
+        Ok(FileExplanation {
+            rule_id: rule.id(),
+            is_valid: changes.iter().all(|change| change.allowed) && missing_required.is_empty(),
+            changes,
+            missing_required,
+        })
+    }
+
     fn find_unauthorized_changes(
         &self,
-        base_json: &Value,
+        changes: &ChangeMap,
+        base_json: Option<&Value>,
         current_json: &Value,
-        allowed_patterns: &[String],
-        when_conditions: Option<&[crate::config::PathValue]>,
-    ) -> Result<Vec<String>> {
-        let all_changes = JsonPathMatcher::get_all_changes(base_json, current_json)?;
-        let mut unauthorized = Vec::new();
+        rule: &Rule,
+        explanation: &mut Explanation,
+    ) -> Result<Vec<UnauthorizedChange>> {
+        let (changes, missing_required) =
+            Self::judge_changes(changes, base_json, current_json, rule, explanation)?;
 
-        for change_path in all_changes.keys() {
-            if !JsonPathMatcher::path_matches_any_pattern(change_path, allowed_patterns) {
-                unauthorized.push(change_path.clone());
+        let rejected = changes
+            .into_iter()
+            .filter(|change| !change.allowed)
+            .map(UnauthorizedChange::from);
+        let missing = missing_required
+            .into_iter()
+            .map(|pattern| UnauthorizedChange {
+                path: pattern,
+                old: None,
+                new: None,
+                reason: Some("required change missing".to_string()),
+            });
 
-                continue;
-            }
+        Ok(rejected.chain(missing).collect())
+    }
 
-            if let Some(when_conditions) = when_conditions
-                && !JsonPathMatcher::when_conditions_met(
-                    current_json,
+    /// Judge every change in path order, and list the required patterns no change matched
+    fn judge_changes(
+        changes: &ChangeMap,
+        base_json: Option<&Value>,
+        current_json: &Value,
+        rule: &Rule,
+        explanation: &mut Explanation,
+    ) -> Result<(Vec<ChangeExplanation>, Vec<String>)> {
+        let mut change_paths: Vec<&String> = changes.keys().collect();
+        change_paths.sort();
+
+        let judged = change_paths
+            .into_iter()
+            .map(|change_path| {
+                Self::judge_change(
                     change_path,
-                    when_conditions,
-                )?
-            {
-                unauthorized.push(format!("{} (when condition not met)", change_path));
+                    &changes[change_path],
+                    base_json,
+                    current_json,
+                    rule,
+                    explanation,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Every required pattern must be matched by at least one observed change
+        let mut missing_required = Vec::new();
+        for required_pattern in &rule.required_changes {
+            let satisfied = changes.keys().any(|change_path| {
+                JsonPathMatcher::path_matches_pattern(change_path, required_pattern)
+            });
+            explanation.note(0, || {
+                format!(
+                    "required {}: {}",
+                    required_pattern,
+                    if satisfied { "present" } else { "missing" }
+                )
+            });
+
+            if !satisfied {
+                missing_required.push(required_pattern.clone());
             }
         }
 
-        Ok(unauthorized)
+        Ok((judged, missing_required))
     }
 
-    fn file_matches_rule(json: &Value, rule: &Rule) -> bool {
-        JsonPathMatcher::matches_conditions(json, &rule.match_conditions)
+    fn judge_change(
+        change_path: &str,
+        change: &(Option<Value>, Option<Value>),
+        base_json: Option<&Value>,
+        current_json: &Value,
+        rule: &Rule,
+        explanation: &mut Explanation,
+    ) -> Result<ChangeExplanation> {
+        let mut judged = ChangeExplanation {
+            path: change_path.to_string(),
+            old: change.0.clone(),
+            new: change.1.clone(),
+            pattern: None,
+            when_met: None,
+            allowed: false,
+            reason: None,
+        };
+
+        // Immutable paths take precedence over everything else, but only in files that
+        // existed before
+        if base_json.is_some()
+            && let Some(pattern) = rule
+                .immutable
+                .iter()
+                .find(|pattern| JsonPathMatcher::path_overlaps_pattern(change_path, pattern))
+        {
+            explanation.note(0, || format!("{}: immutable by {}", change_path, pattern));
+            judged.pattern = Some(pattern.clone());
+            judged.reason = Some("immutable field changed".to_string());
+
+            return Ok(judged);
+        }
+
+        // Forbidden paths take precedence over the allow-list, including when a parent
+        // object holding them is removed or replaced
+        if let Some(pattern) = rule
+            .forbidden_changes
+            .iter()
+            .find(|pattern| JsonPathMatcher::path_overlaps_pattern(change_path, pattern))
+        {
+            explanation.note(0, || format!("{}: forbidden by {}", change_path, pattern));
+            judged.pattern = Some(pattern.clone());
+            judged.reason = Some("forbidden path modified".to_string());
+
+            return Ok(judged);
+        }
+
+        // Removals are checked against `removals` alone when it is given, and their
+        // `when` conditions against the base, where the removed value still exists
+        let removal = ChangeKind::of(change) == ChangeKind::Removal;
+        let conditions_json = if removal && !rule.removals.is_empty() {
+            let Some(pattern) =
+                JsonPathMatcher::matching_pattern_at(change_path, &rule.removals, rule.granularity)
+            else {
+                explanation.note(0, || {
+                    format!(
+                        "{}: removal not matched by any removals pattern",
+                        change_path
+                    )
+                });
+                judged.reason = Some("removal not allowed".to_string());
+
+                return Ok(judged);
+            };
+
+            explanation.note(0, || {
+                format!("{}: removal allowed by {}", change_path, pattern)
+            });
+            judged.pattern = Some(pattern.clone());
+            base_json.unwrap_or(current_json)
+        } else if let Some(allowed_patterns) = &rule.allowed_changes {
+            let Some(pattern) = JsonPathMatcher::matching_change_pattern_at(
+                change_path,
+                change,
+                allowed_patterns,
+                rule.granularity,
+            ) else {
+                // A path allowed only for other kinds of change, or other values, gets a
+                // specific reason
+                let kind = ChangeKind::of(change);
+                let path_patterns: Vec<&AllowedChange> = allowed_patterns
+                    .iter()
+                    .filter(|pattern| {
+                        JsonPathMatcher::matching_pattern_at(
+                            change_path,
+                            std::slice::from_ref(&pattern.path),
+                            rule.granularity,
+                        )
+                        .is_some()
+                    })
+                    .collect();
+                judged.reason = match &change.1 {
+                    Some(new) if path_patterns.iter().any(|pattern| pattern.covers(kind)) => {
+                        Some(format!("value {} not allowed", new))
+                    }
+                    _ if !path_patterns.is_empty() => Some(format!("{} not allowed", kind)),
+                    _ => None,
+                };
+                explanation.note(0, || {
+                    format!("{}: not matched by any allowed pattern", change_path)
+                });
+
+                return Ok(judged);
+            };
+
+            explanation.note(0, || format!("{}: allowed by {}", change_path, pattern));
+            judged.pattern = Some(pattern.to_string());
+            current_json
+        } else {
+            explanation.note(0, || format!("{}: not forbidden", change_path));
+            current_json
+        };
+
+        if let Some(when_conditions) = &rule.when_conditions {
+            let met = JsonPathMatcher::when_conditions_met_explained(
+                conditions_json,
+                change_path,
+                when_conditions,
+                explanation,
+            )?;
+            judged.when_met = Some(met);
+
+            if !met {
+                judged.reason = Some("when condition not met".to_string());
+
+                return Ok(judged);
+            }
+        }
+
+        // `unless` is only consulted once `when` holds, so either can reject a change
+        if JsonPathMatcher::unless_conditions_met_explained(
+            conditions_json,
+            change_path,
+            &rule.unless,
+            explanation,
+        )? {
+            judged.reason = Some("unless condition met".to_string());
+
+            return Ok(judged);
+        }
+
+        if let Some(version_policy) = &rule.version_policy
+            && JsonPathMatcher::path_matches_any_pattern(change_path, &version_policy.paths)
+        {
+            let rejection =
+                JsonPathMatcher::check_version_change(change.0.as_ref(), change.1.as_ref());
+            explanation.note(1, || {
+                format!(
+                    "version policy: {}",
+                    rejection.unwrap_or("version moves forward")
+                )
+            });
+
+            if let Some(reason) = rejection {
+                judged.reason = Some(reason.to_string());
+
+                return Ok(judged);
+            }
+        }
+
+        judged.allowed = true;
+        Ok(judged)
+    }
+
+    /// Describe the allow-list and deny-list of a rule for violation messages
+    fn describe_restrictions(rule: &Rule) -> String {
+        let mut restrictions = Vec::new();
+
+        if let Some(allowed_changes) = &rule.allowed_changes {
+            let allowed_changes: Vec<String> =
+                allowed_changes.iter().map(ToString::to_string).collect();
+            restrictions.push(format!("allows only changes to: {:?}", allowed_changes));
+        }
+
+        if !rule.forbidden_changes.is_empty() {
+            restrictions.push(format!("forbids changes to: {:?}", rule.forbidden_changes));
+        }
+
+        if !rule.required_changes.is_empty() {
+            restrictions.push(format!("requires changes to: {:?}", rule.required_changes));
+        }
+
+        if !rule.immutable.is_empty() {
+            restrictions.push(format!("keeps immutable: {:?}", rule.immutable));
+        }
+
+        if !rule.allow_renames {
+            restrictions.push("prohibits renames".to_string());
+        }
+
+        if restrictions.is_empty() {
+            restrictions.push("restricts changes".to_string());
+        }
+
+        format!("{} {}", rule.describe(), restrictions.join(" and "))
+    }
+
+    /// Substitute `{path}`, `{old}` and `{new}` in a rule's `message` with the change.
+    /// Strings are filled in without quotes; missing values leave the placeholder empty.
+    fn fill_message(message: &str, change: Option<&UnauthorizedChange>) -> String {
+        let render = |value: Option<&Value>| match value {
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        };
+
+        message
+            .replace("{path}", change.map_or("", |change| change.path.as_str()))
+            .replace(
+                "{old}",
+                &render(change.and_then(|change| change.old.as_ref())),
+            )
+            .replace(
+                "{new}",
+                &render(change.and_then(|change| change.new.as_ref())),
+            )
+    }
+
+    /// Whether a rule applies to a file: its path must match one of the rule's `files`
+    /// globs, if any, and its content the `match` conditions. A condition comparing
+    /// values that cannot be ordered is an error naming the rule and file.
+    fn file_matches_rule(json: &Value, file_path: &str, rule: &Rule) -> Result<bool> {
+        if !rule.files.is_empty() && !rule.files.is_match(file_path) {
+            return Ok(false);
+        }
+
+        JsonPathMatcher::matches_conditions(json, &rule.match_conditions).with_context(|| {
+            format!(
+                "{} ({}) cannot be matched against {}",
+                rule.describe(),
+                rule.id(),
+                file_path
+            )
+        })
+    }
+
+    /// Why a rule does not apply to a file, naming the first failing `match` condition
+    /// and whether its path was missing or held a different value
+    fn match_failure(json: &Value, file_path: &str, rule: &Rule) -> Result<String> {
+        if !rule.files.is_empty() && !rule.files.is_match(file_path) {
+            return Ok(format!(
+                "path not matched by files {:?}",
+                rule.files.patterns()
+            ));
+        }
+
+        for condition in &rule.match_conditions {
+            if let Some(failure) = JsonPathMatcher::condition_failure(json, condition)? {
+                return Ok(format!("condition {} failed: {}", condition, failure));
+            }
+        }
+
+        Ok("no condition failed".to_string())
+    }
+
+    /// Parse a file's content into its documents. TOML files are recognized by their
+    /// extension; everything else is parsed as JSON or YAML. JSON and single-document
+    /// YAML yield one document; in multi-document YAML, empty documents are dropped.
+    /// Files of unknown type that are not YAML/JSON are tried as TOML. A leading UTF-8
+    /// byte order mark is ignored, and CRLF line endings are read as LF unless
+    /// [`ValidatorOptions::preserve_line_endings`] is set. Documents nested deeper than
+    /// [`ValidatorOptions::max_depth`] fail to parse.
+    fn parse_documents(
+        content: &str,
+        file_path: &str,
+        options: &ValidatorOptions,
+    ) -> Result<Vec<Value>> {
+        let normalized;
+        let content = if !options.preserve_line_endings && content.contains("\r\n") {
+            normalized = content.replace("\r\n", "\n");
+            normalized.as_str()
+        } else {
+            content
+        };
+
+        let documents = Self::parse_any_documents(content, file_path)?;
+        for document in &documents {
+            JsonPathMatcher::check_depth(document, options.max_depth())?;
+        }
+
+        Ok(documents)
+    }
+
+    fn parse_any_documents(content: &str, file_path: &str) -> Result<Vec<Value>> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let extension = Path::new(file_path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let extension = extension.as_deref().unwrap_or_default();
+
+        if let Some(document) = formats::from_str(extension, content) {
+            return Ok(vec![document?]);
+        }
+
+        let documents = Self::parse_yaml_documents(content);
+
+        // Other file types are tried as TOML too
+        if documents.is_err()
+            && !MANIFEST_EXTENSIONS.contains(&extension)
+            && let Some(Ok(document)) = formats::from_str("toml", content)
+        {
+            return Ok(vec![document]);
+        }
+
+        documents
+    }
+
+    fn parse_yaml_documents(content: &str) -> Result<Vec<Value>> {
+        if let Ok(json) = serde_json::from_str(content) {
+            return Ok(vec![json]);
+        }
+
+        let documents = serde_norway::Deserializer::from_str(content)
+            .map(Value::deserialize)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("Failed to parse as YAML or JSON")?;
+
+        match documents.len() {
+            0 => Ok(vec![Self::parse_yaml_or_json(content)?]),
+            1 => Ok(documents),
+            _ => Ok(documents.into_iter().filter(|d| !d.is_null()).collect()),
+        }
     }
 
     fn parse_yaml_or_json(content: &str) -> Result<Value> {
@@ -255,7 +1434,8 @@ impl Validator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{PathValue, Rule};
+    use crate::config::{Granularity, PathValue, Rule};
+    use crate::glob::GlobSet;
     use serde_json::json;
 
     #[test]
@@ -268,15 +1448,92 @@ mod tests {
         });
 
         let rule = Rule {
+            id: None,
+            name: None,
+            files: GlobSet::default(),
             match_conditions: vec![PathValue {
                 path: "kind".to_string(),
                 value: json!("ApplicationSet"),
+                ..Default::default()
             }],
-            allowed_changes: vec![],
+            match_ref: None,
+            allowed_changes: Some(vec![]),
+            forbidden_changes: vec![],
+            required_changes: vec![],
+            removals: vec![],
+            immutable: vec![],
             when_conditions: None,
+            when_ref: None,
+            unless: vec![],
+            severity: Severity::Error,
+            allow_new_files: true,
+            version_policy: None,
+            stop_on_match: false,
+            allow_deletion: false,
+            allow_renames: true,
+            message: None,
+            array_keys: BTreeMap::new(),
+            branches: GlobSet::default(),
+            deletion_severity: None,
+            granularity: Granularity::Leaf,
+            source: None,
+            source_line: None,
+        };
+
+        assert!(Validator::file_matches_rule(&json, "app.yaml", &rule).unwrap());
+
+        // With `files`, the path must match too
+        let rule = Rule {
+            files: GlobSet::new(&["secrets/**"]).unwrap(),
+            ..rule
         };
+        assert!(Validator::file_matches_rule(&json, "secrets/db.yaml", &rule).unwrap());
+        assert!(!Validator::file_matches_rule(&json, "app.yaml", &rule).unwrap());
+        assert_eq!(
+            Validator::match_failure(&json, "app.yaml", &rule).unwrap(),
+            "path not matched by files [\"secrets/**\"]"
+        );
 
-        assert!(Validator::file_matches_rule(&json, &rule));
+        // Otherwise the first failing condition is named, with what was found instead
+        let other = json!({"kind": "Application", "metadata": {"name": "test"}});
+        assert_eq!(
+            Validator::match_failure(&other, "secrets/db.yaml", &rule).unwrap(),
+            "condition kind eq \"ApplicationSet\" failed: found \"Application\""
+        );
+        assert_eq!(
+            Validator::match_failure(&json!({}), "secrets/db.yaml", &rule).unwrap(),
+            "condition kind eq \"ApplicationSet\" failed: path not found"
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let mut result = ValidationResult {
+            files_processed: 120,
+            files_matched: 15,
+            ..Default::default()
+        };
+        assert_eq!(
+            result.summary_line(Severity::Error),
+            "jiffs: result=pass files=120 matched=15 violations=0 errors=0 warnings=0"
+        );
+
+        for severity in [Severity::Error, Severity::Error, Severity::Warning] {
+            result.violations.push(Violation {
+                file_path: "app.yaml".to_string(),
+                rule_id: "app".to_string(),
+                rule_name: None,
+                severity,
+                rule_description: String::new(),
+                rule_source: None,
+                unauthorized_changes: vec![],
+            });
+        }
+        result.recount();
+        assert_eq!(
+            result.summary_line(Severity::Error),
+            "jiffs: result=fail files=120 matched=15 violations=3 errors=2 warnings=1"
+        );
     }
 
     #[test]
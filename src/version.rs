@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A semantic version (https://semver.org). A leading `v` is accepted, as is common for
+/// git tags. Build metadata is parsed but ignored for precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Vec<Identifier>,
+}
+
+/// A dot-separated pre-release identifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identifier {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(String);
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid semantic version '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+
+        let version = s.strip_prefix('v').unwrap_or(s);
+        let version = match version.split_once('+') {
+            Some((version, build)) if is_valid_identifiers(build) => version,
+            Some(_) => return Err(err()),
+            None => version,
+        };
+
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) if is_valid_identifiers(pre) => (core, Some(pre)),
+            Some(_) => return Err(err()),
+            None => (version, None),
+        };
+
+        let numbers = core
+            .split('.')
+            .map(parse_numeric)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(err)?;
+
+        let [major, minor, patch] = numbers[..] else {
+            return Err(err());
+        };
+
+        let pre = match pre {
+            Some(pre) => pre
+                .split('.')
+                .map(|part| match part.bytes().all(|b| b.is_ascii_digit()) {
+                    true => parse_numeric(part).map(Identifier::Numeric),
+                    false => Some(Identifier::Alphanumeric(part.to_string())),
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(err)?,
+            None => Vec::new(),
+        };
+
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+}
+
+/// Parse a numeric identifier, rejecting leading zeros
+fn parse_numeric(part: &str) -> Option<u64> {
+    if part.is_empty() || (part.len() > 1 && part.starts_with('0')) {
+        return None;
+    }
+
+    part.parse().ok()
+}
+
+fn is_valid_identifiers(s: &str) -> bool {
+    s.split('.').all(|part| {
+        !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    })
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A pre-release has lower precedence than the associated release
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Identifier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Identifier::Numeric(a), Identifier::Numeric(b)) => a.cmp(b),
+            (Identifier::Numeric(_), Identifier::Alphanumeric(_)) => Ordering::Less,
+            (Identifier::Alphanumeric(_), Identifier::Numeric(_)) => Ordering::Greater,
+            (Identifier::Alphanumeric(a), Identifier::Alphanumeric(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            v("1.2.3"),
+            Version {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                pre: vec![],
+            }
+        );
+        assert_eq!(v("v0.19.2"), v("0.19.2"));
+        assert_eq!(v("1.0.0+build.5"), v("1.0.0"));
+        assert_eq!(
+            v("1.0.0-rc.1").pre,
+            vec![
+                Identifier::Alphanumeric("rc".to_string()),
+                Identifier::Numeric(1)
+            ]
+        );
+
+        for invalid in [
+            "1.2",
+            "1.2.3.4",
+            "01.2.3",
+            "1.2.x",
+            "main",
+            "1.2.3-",
+            "1.2.3-a..b",
+        ] {
+            assert!(invalid.parse::<Version>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_version_precedence() {
+        assert!(v("1.2.2") < v("1.2.3"));
+        assert!(v("1.10.0") > v("1.9.9"));
+        assert!(v("1.0.0-alpha") < v("1.0.0"));
+        assert!(v("1.0.0-alpha") < v("1.0.0-alpha.1"));
+        assert!(v("1.0.0-alpha.1") < v("1.0.0-alpha.beta"));
+        assert!(v("1.0.0-beta.2") < v("1.0.0-beta.11"));
+        assert!(v("1.0.0-rc.1") < v("1.0.0"));
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher as _};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Watches files, and directories recursively, for changes, skipping `.git` directories
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    events: Receiver<notify::Result<notify::Event>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Start watching `paths`. Files are watched through their parent directory, so
+    /// that editors replacing a file on save are still noticed.
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).context("Failed to start watching files")?;
+
+        let paths = paths
+            .iter()
+            // Events name canonical paths on some platforms, e.g. /private/var on macOS
+            .map(|path| {
+                path.canonicalize()
+                    .or_else(|_| std::path::absolute(path))
+                    .unwrap_or_else(|_| path.clone())
+            })
+            .collect::<Vec<_>>();
+        for path in &paths {
+            let (target, mode) = if path.is_dir() {
+                (path.as_path(), RecursiveMode::Recursive)
+            } else {
+                let parent = path.parent().unwrap_or(Path::new("."));
+                (parent, RecursiveMode::NonRecursive)
+            };
+            watcher
+                .watch(target, mode)
+                .with_context(|| format!("Failed to watch {}", path.display()))?;
+        }
+
+        Ok(Self {
+            paths,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Block until a watched file is created, modified or removed, then until nothing
+    /// changed for `debounce`, so that a burst of writes (e.g. an editor saving several
+    /// files) is reported once
+    pub fn wait_for_change(&self, debounce: Duration) -> Result<()> {
+        loop {
+            let event = self.events.recv().context("Stopped watching files")?;
+            if self.is_relevant(&event?) {
+                break;
+            }
+        }
+
+        loop {
+            match self.events.recv_timeout(debounce) {
+                Ok(event) => {
+                    event?;
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Stopped watching files"),
+            }
+        }
+    }
+
+    fn is_relevant(&self, event: &notify::Event) -> bool {
+        if event.kind.is_access() {
+            return false;
+        }
+
+        event.paths.iter().any(|changed| {
+            self.paths.iter().any(|path| {
+                changed.starts_with(path)
+                    && !changed
+                        .strip_prefix(path)
+                        .unwrap_or(changed)
+                        .components()
+                        .any(|component| component == Component::Normal(".git".as_ref()))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, EventKind, ModifyKind};
+    use std::fs;
+
+    #[test]
+    fn test_relevant_events() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("apps")).unwrap();
+        fs::write(dir.path().join("policy.yaml"), "rules: []\n").unwrap();
+        let watcher =
+            Watcher::new(&[dir.path().join("apps"), dir.path().join("policy.yaml")]).unwrap();
+
+        let event = |kind: EventKind, path: &str| {
+            notify::Event::new(kind).add_path(dir.path().canonicalize().unwrap().join(path))
+        };
+        let modify = EventKind::Modify(ModifyKind::Any);
+
+        assert!(watcher.is_relevant(&event(modify, "apps/app.yaml")));
+        assert!(watcher.is_relevant(&event(EventKind::Create(CreateKind::File), "apps/new")));
+        assert!(watcher.is_relevant(&event(modify, "policy.yaml")));
+        // Files inside .git, and siblings of watched files, are not watched
+        assert!(!watcher.is_relevant(&event(modify, "apps/.git/index")));
+        assert!(!watcher.is_relevant(&event(modify, "other.yaml")));
+        assert!(!watcher.is_relevant(&event(
+            EventKind::Access(notify::event::AccessKind::Any),
+            "apps/app.yaml"
+        )));
+    }
+
+    #[test]
+    fn test_wait_for_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let watcher = Watcher::new(&[dir.path().to_path_buf()]).unwrap();
+
+        fs::write(dir.path().join("app.yaml"), "replicas: 10\n").unwrap();
+        watcher.wait_for_change(Duration::from_millis(50)).unwrap();
+    }
+}
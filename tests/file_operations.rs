@@ -1,13 +1,22 @@
 #[cfg(test)]
 mod file_operations {
     use anyhow::Result;
-    use jiffs::config::Config;
+    use jiffs::config::{Config, Severity};
     use jiffs::git::{ChangeType, FileChange, GitDiff};
-    use jiffs::validator::Validator;
+    use jiffs::validator::{Validator, Violation};
     use std::collections::HashMap;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    /// Render a violation's unauthorized changes the way they are logged
+    fn changes(violation: &Violation) -> Vec<String> {
+        violation
+            .unauthorized_changes
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
     #[test]
     fn deleted_files_matching_rules_cause_validation_failure() -> Result<()> {
         // Create rules that match ApplicationSet files
@@ -48,7 +57,10 @@ spec:
             },
         );
 
-        let git_diff = GitDiff { changed_files };
+        let git_diff = GitDiff {
+            changed_files,
+            ..Default::default()
+        };
 
         // Validate - should fail because the deleted file matches a rule
         let result = validator.validate(&git_diff, false)?;
@@ -67,7 +79,7 @@ spec:
         let violation = &result.violations[0];
         assert_eq!(violation.file_path, "deleted-app.yaml");
         assert!(violation.rule_description.contains("prohibits deletion"));
-        assert_eq!(violation.unauthorized_changes, vec!["File deletion"]);
+        assert_eq!(changes(violation), vec!["File deletion"]);
 
         Ok(())
     }
@@ -109,7 +121,10 @@ spec:
             },
         );
 
-        let git_diff = GitDiff { changed_files };
+        let git_diff = GitDiff {
+            changed_files,
+            ..Default::default()
+        };
 
         // Validate - should pass because the deleted file doesn't match any rule
         let result = validator.validate(&git_diff, false)?;
@@ -123,4 +138,173 @@ spec:
 
         Ok(())
     }
+
+    #[test]
+    fn renamed_files_are_validated_against_their_source() -> Result<()> {
+        let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/clusters/values/revision
+"#;
+
+        let mut rules_file = NamedTempFile::new()?;
+        write!(rules_file, "{}", rules_content)?;
+        let config = Config::from_file(rules_file.path())?;
+        let validator = Validator::new(config);
+
+        let base_content = r#"
+kind: ApplicationSet
+metadata:
+  name: test-app
+spec:
+  generators:
+  - clusters:
+      values:
+        revision: 1.0.0
+"#;
+
+        let current_content = r#"
+kind: ApplicationSet
+metadata:
+  name: renamed-app
+spec:
+  generators:
+  - clusters:
+      values:
+        revision: 1.1.0
+"#;
+
+        let mut changed_files = HashMap::new();
+        changed_files.insert(
+            "apps/renamed-app.yaml".to_string(),
+            FileChange {
+                base_content: Some(base_content.to_string()),
+                current_content: Some(current_content.to_string()),
+                change_type: ChangeType::Renamed {
+                    from: "apps/test-app.yaml".to_string(),
+                },
+            },
+        );
+
+        let git_diff = GitDiff {
+            changed_files,
+            ..Default::default()
+        };
+
+        // Validate - the revision bump is allowed, the name change is not
+        let result = validator.validate(&git_diff, false)?;
+
+        assert!(!result.is_valid);
+        assert_eq!(result.violations.len(), 1);
+
+        let violation = &result.violations[0];
+        assert_eq!(violation.file_path, "apps/renamed-app.yaml");
+        assert_eq!(changes(violation), vec!["/metadata/name"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pure_renames_pass_validation() -> Result<()> {
+        let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges: []
+"#;
+
+        let mut rules_file = NamedTempFile::new()?;
+        write!(rules_file, "{}", rules_content)?;
+        let config = Config::from_file(rules_file.path())?;
+        let validator = Validator::new(config);
+
+        let content = "kind: ApplicationSet\nmetadata:\n  name: test-app\n";
+
+        let mut changed_files = HashMap::new();
+        changed_files.insert(
+            "new.yaml".to_string(),
+            FileChange {
+                base_content: Some(content.to_string()),
+                current_content: Some(content.to_string()),
+                change_type: ChangeType::Renamed {
+                    from: "old.yaml".to_string(),
+                },
+            },
+        );
+
+        let git_diff = GitDiff {
+            changed_files,
+            ..Default::default()
+        };
+
+        let result = validator.validate(&git_diff, false)?;
+
+        assert!(result.is_valid);
+        assert_eq!(result.files_matched, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deletion_policy_is_configurable_per_rule() -> Result<()> {
+        let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges: []
+    allowDeletion: true
+  - match:
+    - path: kind
+      value: Application
+    allowedChanges: []
+    deletionSeverity: warning
+"#;
+
+        let mut rules_file = NamedTempFile::new()?;
+        write!(rules_file, "{}", rules_content)?;
+        let config = Config::from_file(rules_file.path())?;
+        let validator = Validator::new(config);
+
+        let mut changed_files = HashMap::new();
+        changed_files.insert(
+            "appset.yaml".to_string(),
+            FileChange {
+                base_content: Some("kind: ApplicationSet\n".to_string()),
+                current_content: None,
+                change_type: ChangeType::Deleted,
+            },
+        );
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some("kind: Application\n".to_string()),
+                current_content: None,
+                change_type: ChangeType::Deleted,
+            },
+        );
+
+        let git_diff = GitDiff {
+            changed_files,
+            ..Default::default()
+        };
+
+        // Validate - the ApplicationSet deletion is allowed, the Application one only warns
+        let result = validator.validate(&git_diff, false)?;
+
+        assert!(result.is_valid);
+        assert_eq!(result.files_matched, 2);
+        assert_eq!(result.violations.len(), 1);
+
+        let violation = &result.violations[0];
+        assert_eq!(violation.file_path, "app.yaml");
+        assert_eq!(violation.severity, Severity::Warning);
+        assert_eq!(changes(violation), vec!["File deletion"]);
+
+        Ok(())
+    }
 }
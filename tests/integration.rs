@@ -1,12 +1,24 @@
 use anyhow::Result;
 use jiffs::{
-    config::Config,
+    config::{Config, Severity},
+    error::JiffsError,
     git::{ChangeType, FileChange, GitDiff},
-    validator::Validator,
+    validator::{
+        PARSE_ERROR_RULE_ID, RuleStats, UNCOVERED_RULE_ID, Validator, ValidatorOptions, Violation,
+    },
 };
 use std::io::Write;
 use tempfile::NamedTempFile;
 
+/// Render a violation's unauthorized changes the way they are logged
+fn changes(violation: &Violation) -> Vec<String> {
+    violation
+        .unauthorized_changes
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
 #[test]
 fn test_end_to_end_validation_with_allowed_changes() -> Result<()> {
     // Create a temporary rules file
@@ -57,7 +69,10 @@ spec:
         },
     );
 
-    let git_diff = GitDiff { changed_files };
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
 
     // Validate - should pass
     let result = validator.validate(&git_diff, false)?;
@@ -122,7 +137,10 @@ spec:
         },
     );
 
-    let git_diff = GitDiff { changed_files };
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
 
     // Validate - should fail
     let result = validator.validate(&git_diff, false)?;
@@ -132,7 +150,11 @@ spec:
 
     let violation = &result.violations[0];
     assert_eq!(violation.file_path, "test.yaml");
-    assert!(!violation.unauthorized_changes.is_empty());
+    assert_eq!(changes(violation), vec!["/metadata/name"]);
+
+    let change = &violation.unauthorized_changes[0];
+    assert_eq!(change.old, Some(serde_json::json!("test")));
+    assert_eq!(change.new, Some(serde_json::json!("updated-test")));
 
     Ok(())
 }
@@ -174,7 +196,10 @@ metadata:
         },
     );
 
-    let git_diff = GitDiff { changed_files };
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
 
     // Validate - should pass (no matching files)
     let result = validator.validate(&git_diff, false)?;
@@ -227,7 +252,10 @@ spec:
         },
     );
 
-    let git_diff = GitDiff { changed_files };
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
 
     // Validate - should pass (new files are allowed)
     let result = validator.validate(&git_diff, false)?;
@@ -237,3 +265,2295 @@ spec:
 
     Ok(())
 }
+
+#[test]
+fn test_warning_severity_does_not_invalidate() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/clusters/values/revision
+    severity: warning
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let base_content = r#"
+kind: ApplicationSet
+metadata:
+  name: test
+"#;
+
+    let current_content = r#"
+kind: ApplicationSet
+metadata:
+  name: updated-test
+"#;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "test.yaml".to_string(),
+        FileChange {
+            base_content: Some(base_content.to_string()),
+            current_content: Some(current_content.to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    // Validate - the violation is reported but only as a warning
+    let result = validator.validate(&git_diff, false)?;
+    assert!(result.is_valid);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.warning_count, 1);
+    assert_eq!(result.error_count, 0);
+    assert!(!result.fails_at(Severity::Error));
+    assert!(result.fails_at(Severity::Warning));
+
+    Ok(())
+}
+
+#[test]
+fn test_forbidden_changes_take_precedence() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    forbiddenChanges:
+    - /metadata/name
+  - match:
+    - path: kind
+      value: Application
+    allowedChanges:
+    - /spec/source/repoURL
+    forbiddenChanges:
+    - /spec/source/repoURL
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let mut changed_files = std::collections::HashMap::new();
+
+    // Deny-list only: everything except /metadata/name may change
+    changed_files.insert(
+        "appset.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: ApplicationSet\nmetadata:\n  name: a\n  labels: {}\n".into()),
+            current_content: Some(
+                "kind: ApplicationSet\nmetadata:\n  name: a\n  labels: {team: x}\n".into(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    // Both lists: the forbidden path is rejected even though it is also allowed
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: Application\nspec:\n  source: {repoURL: a}\n".into()),
+            current_content: Some("kind: Application\nspec:\n  source: {repoURL: b}\n".into()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    let result = validator.validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.files_matched, 2);
+    assert_eq!(result.violations.len(), 1);
+
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "app.yaml");
+    assert_eq!(
+        changes(violation),
+        vec!["/spec/source/repoURL (forbidden path modified)"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_forbidden_changes_are_detected() -> Result<()> {
+    let validate = |forbidden: &str| {
+        let config: Config = format!(
+            "rules:\n  - match: []\n    forbiddenChanges: [{}]\n",
+            forbidden
+        )
+        .parse()?;
+
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some("metadata:\n  name: app\n  labels: {team: a}\n".into()),
+                current_content: Some("metadata:\n  name: app\n  labels: {team: b}\n".into()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        Validator::new(config).validate_changes(&changed_files)
+    };
+
+    let result = validate("/metadata/labels/team")?;
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(
+        changes(&result.violations[0]),
+        vec!["/metadata/labels/team (forbidden path modified)"]
+    );
+
+    // Only the changed paths are checked against the forbidden patterns
+    assert!(validate("/metadata/name")?.violations.is_empty());
+
+    // Removing the parent object removes the forbidden label with it
+    let config: Config =
+        "rules:\n  - match: []\n    allowedChanges: [/**]\n    forbiddenChanges: [/metadata/labels/team]\n"
+            .parse()?;
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("metadata:\n  name: app\n  labels: {team: a}\n".into()),
+            current_content: Some("metadata: {}\n".into()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+    assert_eq!(result.violations.len(), 1);
+    assert!(
+        changes(&result.violations[0])
+            .iter()
+            .any(|change| change.ends_with("(forbidden path modified)")),
+        "{:?}",
+        changes(&result.violations[0])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_new_file_validated_when_new_files_disallowed() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /kind
+    - /metadata/name
+    - /spec/generators/*/clusters/values/revision
+    allowNewFiles: false
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    assert!(!config.rules[0].allow_new_files);
+    let validator = Validator::new(config);
+
+    let allowed_content = r#"
+kind: ApplicationSet
+metadata:
+  name: new-app
+spec:
+  generators:
+  - clusters:
+      values:
+        revision: 0.19.2
+"#;
+
+    let unauthorized_content = r#"
+kind: ApplicationSet
+metadata:
+  name: new-app
+  namespace: argocd
+spec:
+  generators:
+  - clusters:
+      values:
+        revision: 0.19.2
+"#;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "allowed.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some(allowed_content.to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+    changed_files.insert(
+        "unauthorized.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some(unauthorized_content.to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    // Validate - only the file adding an unlisted field fails
+    let result = validator.validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.files_matched, 2);
+    assert_eq!(result.violations.len(), 1);
+
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "unauthorized.yaml");
+    assert_eq!(changes(violation), vec!["/metadata/namespace"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_version_policy_rejects_downgrades() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/clusters/values/revision
+    versionPolicy:
+      paths:
+      - /spec/generators/*/clusters/values/revision
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let content = |revision: &str| {
+        format!(
+            "kind: ApplicationSet\nspec:\n  generators:\n  - clusters:\n      values:\n        revision: {}\n",
+            revision
+        )
+    };
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "upgrade.yaml".to_string(),
+        FileChange {
+            base_content: Some(content("1.2.3")),
+            current_content: Some(content("1.3.0")),
+            change_type: ChangeType::Modified,
+        },
+    );
+    changed_files.insert(
+        "downgrade.yaml".to_string(),
+        FileChange {
+            base_content: Some(content("1.2.3")),
+            current_content: Some(content("1.2.2")),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    // Validate - the path is allowed, but only the upgrade passes
+    let result = validator.validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.violations.len(), 1);
+
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "downgrade.yaml");
+    assert_eq!(
+        changes(violation),
+        vec!["/spec/generators/0/clusters/values/revision (version downgrade)"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_require_rule_match_reports_uncovered_files() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges: []
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::with_options(
+        config,
+        ValidatorOptions {
+            require_rule_match: true,
+            ..Default::default()
+        },
+    );
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some("kind: Application\n".to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+    changed_files.insert(
+        "appset.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some("kind: ApplicationSet\n".to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+    changed_files.insert(
+        "notes.txt".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some("{ not: [valid".to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    // Validate - only the parseable file without a matching rule is reported
+    let result = validator.validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "app.yaml");
+    assert_eq!(violation.rule_id, UNCOVERED_RULE_ID);
+    assert_eq!(
+        changes(violation),
+        vec!["File not covered by any policy rule"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_all_matching_rules_are_enforced() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: ApplicationSet
+    forbiddenChanges:
+    - /metadata/name
+  - match:
+    - path: kind
+      value: ApplicationSet
+    - path: /metadata/labels/env
+      value: production
+    allowedChanges:
+    - /metadata/name
+"#;
+
+    let base_content = r#"
+kind: ApplicationSet
+metadata:
+  name: test
+  labels:
+    env: production
+"#;
+
+    let current_content = r#"
+kind: ApplicationSet
+metadata:
+  name: updated-test
+  labels:
+    env: production
+"#;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "test.yaml".to_string(),
+        FileChange {
+            base_content: Some(base_content.to_string()),
+            current_content: Some(current_content.to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+    let config = Config::from_file(rules_file.path())?;
+
+    // Both rules match; the first rejects the rename even though the second allows it
+    let result = Validator::new(config.clone()).validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(
+        changes(&result.violations[0]),
+        vec!["/metadata/name (forbidden path modified)"]
+    );
+
+    // With stopOnMatch on the first rule, the second rule is never evaluated
+    let mut config = config;
+    config.rules.swap(0, 1);
+    config.rules[0].stop_on_match = true;
+
+    let result = Validator::new(config).validate(&git_diff, false)?;
+    assert!(result.is_valid);
+    assert_eq!(result.files_matched, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_required_changes_must_be_present() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/image
+    - /metadata/annotations/deployedAt
+    requiredChanges:
+    - /metadata/annotations/deployedAt
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "bumped.yaml".to_string(),
+        FileChange {
+            base_content: Some(
+                "kind: Deployment\nmetadata:\n  annotations:\n    deployedAt: '1'\nspec:\n  image: a\n"
+                    .to_string(),
+            ),
+            current_content: Some(
+                "kind: Deployment\nmetadata:\n  annotations:\n    deployedAt: '2'\nspec:\n  image: b\n"
+                    .to_string(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+    changed_files.insert(
+        "not-bumped.yaml".to_string(),
+        FileChange {
+            base_content: Some(
+                "kind: Deployment\nmetadata:\n  annotations:\n    deployedAt: '1'\nspec:\n  image: a\n"
+                    .to_string(),
+            ),
+            current_content: Some(
+                "kind: Deployment\nmetadata:\n  annotations:\n    deployedAt: '1'\nspec:\n  image: b\n"
+                    .to_string(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let git_diff = GitDiff {
+        changed_files,
+        ..Default::default()
+    };
+
+    // Validate - the image change without bumping deployedAt is a violation
+    let result = validator.validate(&git_diff, false)?;
+    assert!(!result.is_valid);
+    assert_eq!(result.violations.len(), 1);
+
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "not-bumped.yaml");
+    assert_eq!(
+        changes(violation),
+        vec!["/metadata/annotations/deployedAt (required change missing)"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_subtree_granularity_authorizes_nested_changes() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - id: leaf
+    match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*
+  - id: subtree
+    match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*
+    granularity: subtree
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let base_content = r#"
+kind: ApplicationSet
+spec:
+  generators:
+  - clusters:
+      values:
+        revision: 0.19.2
+        region: eu
+  template:
+    name: app
+"#;
+
+    let current_content = r#"
+kind: ApplicationSet
+spec:
+  generators:
+  - list:
+      elements: []
+  template:
+    name: renamed
+"#;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "appset.yaml".to_string(),
+        FileChange {
+            base_content: Some(base_content.to_string()),
+            current_content: Some(current_content.to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = validator.validate(
+        &GitDiff {
+            changed_files,
+            ..Default::default()
+        },
+        false,
+    )?;
+    assert_eq!(result.violations.len(), 2);
+
+    let leaf = result
+        .violations
+        .iter()
+        .find(|v| v.rule_id == "leaf")
+        .unwrap();
+    assert!(changes(leaf).contains(&"/spec/generators/0/clusters".to_string()));
+    assert!(changes(leaf).contains(&"/spec/template/name".to_string()));
+
+    // Nested generator changes are covered as a whole; the template is still outside
+    let subtree = result
+        .violations
+        .iter()
+        .find(|v| v.rule_id == "subtree")
+        .unwrap();
+    assert_eq!(changes(subtree), vec!["/spec/template/name"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_in_memory_changes() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - id: replicas-only
+    match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/replicas
+"#,
+    )?;
+    let validator = Validator::new(config);
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "deploy.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: Deployment\nspec:\n  replicas: 1\n".to_string()),
+            current_content: Some("kind: Deployment\nspec:\n  replicas: 2\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    changed_files.insert(
+        "other.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: Deployment\nspec:\n  image: a\n".to_string()),
+            current_content: Some("kind: Deployment\nspec:\n  image: b\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = validator.validate_changes(&changed_files)?;
+    assert_eq!(result.files_processed, 2);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.violations[0].file_path, "other.yaml");
+    assert_eq!(changes(&result.violations[0]), vec!["/spec/image"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_multi_document_files_are_validated_per_document() -> Result<()> {
+    let rules_content = r#"
+rules:
+  - id: replicas-only
+    match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/replicas
+"#;
+
+    let mut rules_file = NamedTempFile::new()?;
+    write!(rules_file, "{}", rules_content)?;
+
+    let config = Config::from_file(rules_file.path())?;
+    let validator = Validator::new(config);
+
+    let base_content = r#"
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 1
+---
+kind: Deployment
+metadata:
+  name: worker
+spec:
+  replicas: 1
+  image: worker:1
+"#;
+
+    let current_content = r#"
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 3
+---
+kind: Deployment
+metadata:
+  name: worker
+spec:
+  replicas: 1
+  image: worker:2
+"#;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "deploy.yaml".to_string(),
+        FileChange {
+            base_content: Some(base_content.to_string()),
+            current_content: Some(current_content.to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = validator.validate(
+        &GitDiff {
+            changed_files,
+            ..Default::default()
+        },
+        false,
+    )?;
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.violations[0].file_path, "deploy.yaml#1");
+    assert_eq!(changes(&result.violations[0]), vec!["/spec/image"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_on_parse_error_reports_broken_manifests() -> Result<()> {
+    let config: Config = serde_norway::from_str("rules: []")?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    for (path, content) in [
+        ("broken.yaml", "kind: [unclosed\n"),
+        ("notes.txt", "kind: [not yaml either\n"),
+        ("valid.yaml", "kind: Deployment\n"),
+    ] {
+        changed_files.insert(
+            path.to_string(),
+            FileChange {
+                base_content: None,
+                current_content: Some(content.to_string()),
+                change_type: ChangeType::Added,
+            },
+        );
+    }
+
+    // By default unparseable files are skipped
+    let result = Validator::new(config.clone()).validate_changes(&changed_files)?;
+    assert!(result.violations.is_empty());
+
+    let validator = Validator::with_options(
+        config,
+        ValidatorOptions {
+            fail_on_parse_error: true,
+            ..Default::default()
+        },
+    );
+    let result = validator.validate_changes(&changed_files)?;
+
+    // Only the file expected to be YAML is reported; other file types are still ignored
+    assert_eq!(result.violations.len(), 1);
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "broken.yaml");
+    assert_eq!(violation.rule_id, PARSE_ERROR_RULE_ID);
+    assert!(
+        violation.unauthorized_changes[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .starts_with("Failed to parse as YAML or JSON")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_byte_order_mark_is_ignored() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - match:
+    - path: /kind
+      value: Deployment
+    allowedChanges:
+    - /spec/replicas
+"#,
+    )?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("\u{feff}kind: Deployment\nspec:\n  replicas: 1\n".to_string()),
+            current_content: Some(
+                "\u{feff}kind: Deployment\nspec:\n  replicas: 2\n  image: app:2\n".to_string(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(
+        result.violations[0].unauthorized_changes[0].path,
+        "/spec/image"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_on_parse_error_reports_non_utf8_manifests() -> Result<()> {
+    let config: Config = serde_norway::from_str("rules: []")?;
+    let git_diff = GitDiff {
+        non_utf8_files: vec!["latin1.yaml".to_string(), "logo.png".to_string()],
+        ..Default::default()
+    };
+
+    // By default files that are not UTF-8 are skipped
+    let result = Validator::new(config.clone()).validate(&git_diff, false)?;
+    assert!(result.violations.is_empty());
+
+    let validator = Validator::with_options(
+        config,
+        ValidatorOptions {
+            fail_on_parse_error: true,
+            ..Default::default()
+        },
+    );
+    let result = validator.validate(&git_diff, false)?;
+
+    assert!(!result.is_valid);
+    assert_eq!(result.error_count, 1);
+    let violation = &result.violations[0];
+    assert_eq!(violation.file_path, "latin1.yaml");
+    assert_eq!(violation.rule_id, PARSE_ERROR_RULE_ID);
+    assert_eq!(
+        violation.unauthorized_changes[0].reason.as_deref(),
+        Some("content is not valid UTF-8")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rules_sharing_a_file_report_as_if_validated_alone() -> Result<()> {
+    let rules = [
+        "  - id: replicas\n    match:\n    - path: kind\n      value: Deployment\n    allowedChanges:\n    - /spec/replicas\n",
+        "  - id: no-image\n    match:\n    - path: kind\n      value: Deployment\n    forbiddenChanges:\n    - /spec/image\n",
+        "  - id: bump\n    match:\n    - path: kind\n      value: Deployment\n    requiredChanges:\n    - /metadata/annotations/deployedAt\n",
+    ];
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "deploy.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: Deployment\nspec:\n  replicas: 1\n  image: a\n".to_string()),
+            current_content: Some(
+                "kind: Deployment\nspec:\n  replicas: 2\n  image: b\n".to_string(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    // The file's content is parsed once and shared by all three rules
+    let config: Config = serde_norway::from_str(&format!("rules:\n{}", rules.concat()))?;
+    let combined = Validator::new(config).validate_changes(&changed_files)?;
+
+    let mut separate = Vec::new();
+    for rule in rules {
+        let config: Config = serde_norway::from_str(&format!("rules:\n{}", rule))?;
+        let result = Validator::new(config).validate_changes(&changed_files)?;
+        separate.extend(result.violations);
+    }
+
+    assert_eq!(combined.violations.len(), 3);
+    assert_eq!(
+        serde_json::to_value(&combined.violations)?,
+        serde_json::to_value(&separate)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rule_stats_count_matches_and_violations() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - id: replicas-only
+    match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/replicas
+  - id: services
+    match:
+    - path: kind
+      value: Service
+    allowedChanges:
+    - /spec/ports
+"#,
+    )?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    for (file, image) in [("a.yaml", "y"), ("b.yaml", "x")] {
+        changed_files.insert(
+            file.to_string(),
+            FileChange {
+                base_content: Some("kind: Deployment\nspec:\n  image: x\n".to_string()),
+                current_content: Some(format!("kind: Deployment\nspec:\n  image: {}\n", image)),
+                change_type: ChangeType::Modified,
+            },
+        );
+    }
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+
+    // The second file's image is unchanged, so only the first is a violation
+    assert_eq!(
+        result.rule_stats["replicas-only"],
+        RuleStats {
+            files_matched: 2,
+            violations_count: 1,
+            files: vec!["a.yaml".to_string(), "b.yaml".to_string()],
+        }
+    );
+    // Rules that never match are still listed
+    assert_eq!(result.rule_stats["services"], RuleStats::default());
+
+    let json = serde_json::to_value(&result)?;
+    assert_eq!(json["rule_stats"]["replicas-only"]["files_matched"], 2);
+
+    // Coverage lists the matched files per rule, including rules that matched none
+    let coverage = serde_json::to_value(result.coverage())?;
+    assert_eq!(
+        coverage,
+        serde_json::json!({ "replicas-only": ["a.yaml", "b.yaml"], "services": [] })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_errors_are_classified() -> Result<()> {
+    let err = "rules:\n  - match: []\n    allowedChanges: ['spec//x']\n"
+        .parse::<Config>()
+        .unwrap_err();
+    assert!(matches!(err, JiffsError::InvalidPointer(_)), "{:?}", err);
+    assert!(format!("{:#}", err).contains("Invalid policy"));
+
+    let err = "rules: [".parse::<Config>().unwrap_err();
+    assert!(matches!(err, JiffsError::Config(_)), "{:?}", err);
+
+    let config: Config = "rules:\n  - match: []\n    allowedChanges: []\n".parse()?;
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "deploy.yaml".to_string(),
+        FileChange {
+            base_content: Some("kind: [".to_string()),
+            current_content: Some("kind: Deployment\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    let err = Validator::new(config)
+        .validate_changes(&changed_files)
+        .unwrap_err();
+    assert!(matches!(err, JiffsError::Parse(_)), "{:?}", err);
+    assert!(format!("{:#}", err).contains("Failed to parse base content for deploy.yaml"));
+
+    Ok(())
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_toml_and_yaml_manifests_detect_the_same_changes() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: replicas-only
+    match:
+    - path: kind
+      value: Deployment
+    allowedChanges:
+    - /spec/replicas
+"#
+    .parse()?;
+
+    let file = |base: &str, current: &str| FileChange {
+        base_content: Some(base.to_string()),
+        current_content: Some(current.to_string()),
+        change_type: ChangeType::Modified,
+    };
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "deploy.toml".to_string(),
+        file(
+            "kind = \"Deployment\"\n\n[spec]\nreplicas = 1\nimage = \"a\"\n",
+            "kind = \"Deployment\"\n\n[spec]\nreplicas = 2\nimage = \"b\"\n",
+        ),
+    );
+    changed_files.insert(
+        "deploy.yaml".to_string(),
+        file(
+            "kind: Deployment\nspec:\n  replicas: 1\n  image: a\n",
+            "kind: Deployment\nspec:\n  replicas: 2\n  image: b\n",
+        ),
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+    assert_eq!(result.files_matched, 2);
+
+    let mut violations: Vec<_> = result.violations.iter().collect();
+    violations.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+    assert_eq!(violations.len(), 2);
+    assert_eq!(violations[0].file_path, "deploy.toml");
+    assert_eq!(changes(violations[0]), changes(violations[1]));
+    assert_eq!(changes(violations[0]), vec!["/spec/image"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_removals_only_allow_removing_listed_paths() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: scale-down
+    match:
+    - path: kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/values/revision
+    - /metadata/labels/*
+    removals:
+    - /spec/generators/*
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let base = r#"
+kind: ApplicationSet
+metadata:
+  labels:
+    team: web
+spec:
+  generators:
+  - values: { revision: "1.0.0" }
+  - values: { revision: "1.0.0" }
+"#;
+    let validate = |current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "appset.yaml".to_string(),
+            FileChange {
+                base_content: Some(base.to_string()),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+
+    // Dropping a generator is a removal under /spec/generators/*
+    let result = validate(
+        "kind: ApplicationSet\nmetadata:\n  labels:\n    team: web\nspec:\n  generators:\n  - values: { revision: \"1.0.0\" }\n",
+    )?;
+    assert!(result.violations.is_empty(), "{:?}", result.violations);
+
+    // Removing a label is not, even though allowedChanges covers the path
+    let result = validate(
+        "kind: ApplicationSet\nmetadata:\n  labels: {}\nspec:\n  generators:\n  - values: { revision: \"1.0.0\" }\n  - values: { revision: \"1.0.0\" }\n",
+    )?;
+    assert_eq!(result.violations.len(), 1);
+    let change = &result.violations[0].unauthorized_changes[0];
+    assert_eq!(change.path, "/metadata/labels/team");
+    assert_eq!(change.reason.as_deref(), Some("removal not allowed"));
+
+    // Adding a generator still needs allowedChanges
+    let result = validate(&format!("{}  - values: {{ revision: \"2.0.0\" }}\n", base))?;
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(changes(&result.violations[0]), vec!["/spec/generators/2"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_stats_count_parsed_files_and_bytes() -> Result<()> {
+    let config: Config = "rules:\n  - match: []\n    allowedChanges: [/replicas]\n".parse()?;
+    let validator = Validator::new(config);
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("replicas: 1\n".to_string()),
+            current_content: Some("replicas: 2\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    changed_files.insert(
+        "new.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some("a: b\n".to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+
+    let result = validator.validate_changes(&changed_files)?;
+    assert_eq!(result.stats.files_parsed, 2);
+    assert_eq!(result.stats.bytes_parsed, 12 + 12 + 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_rule_files_target_paths_without_match_conditions() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: secrets
+    files: ["secrets/**/*.yaml"]
+    allowedChanges: [/data/*]
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let change = || FileChange {
+        base_content: Some("kind: Secret\ntype: Opaque\ndata:\n  key: a\n".to_string()),
+        current_content: Some("kind: Secret\ntype: tls\ndata:\n  key: b\n".to_string()),
+        change_type: ChangeType::Modified,
+    };
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert("secrets/prod/db.yaml".to_string(), change());
+    changed_files.insert("apps/db.yaml".to_string(), change());
+
+    let result = validator.validate_changes(&changed_files)?;
+    assert_eq!(result.matched_files, vec!["secrets/prod/db.yaml"]);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(changes(&result.violations[0]), vec!["/type"]);
+
+    // Invalid globs are rejected when the policy is loaded
+    assert!(
+        "rules:\n  - files: [\"secrets/[\"]\n"
+            .parse::<Config>()
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_violations_report_rule_source() -> Result<()> {
+    let mut policy = NamedTempFile::new()?;
+    write!(
+        policy,
+        "rules:\n  - id: frozen\n    match: []\n    allowedChanges: []\n"
+    )?;
+    let validator = Validator::new(Config::from_file(policy.path())?);
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("replicas: 1\n".to_string()),
+            current_content: Some("replicas: 2\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = validator.validate_changes(&changed_files)?;
+    assert_eq!(
+        result.violations[0].rule_source,
+        Some(format!("{}:2", policy.path().display()))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_allowed_changes_restricted_to_ops() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: no-new-keys
+    match: []
+    allowedChanges:
+    - path: /spec/*
+      ops: [modify]
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let validate = |current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some("spec:\n  replicas: 1\n".to_string()),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+
+    assert!(validate("spec:\n  replicas: 2\n")?.violations.is_empty());
+
+    let result = validate("spec:\n  replicas: 1\n  paused: true\n")?;
+    let change = &result.violations[0].unauthorized_changes[0];
+    assert_eq!(change.path, "/spec/paused");
+    assert_eq!(change.reason.as_deref(), Some("addition not allowed"));
+
+    let result = validate("spec: {}\n")?;
+    let change = &result.violations[0].unauthorized_changes[0];
+    assert_eq!(change.reason.as_deref(), Some("removal not allowed"));
+
+    Ok(())
+}
+
+#[test]
+fn test_explain_file_judges_each_change() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - id: app
+    allowedChanges:
+    - /spec/replicas
+    - /spec/image
+    forbiddenChanges:
+    - /metadata/name
+    requiredChanges:
+    - /metadata/annotations/deployedAt
+    when:
+    - path: /metadata/labels/env
+      value: staging
+"#,
+    )?;
+    let rule = &config.rules[0];
+
+    let base = serde_json::json!({
+        "metadata": {"name": "app", "labels": {"env": "staging"}},
+        "spec": {"replicas": 1, "image": "app:1", "port": 80}
+    });
+    let current = serde_json::json!({
+        "metadata": {"name": "renamed", "labels": {"env": "staging"}},
+        "spec": {"replicas": 2, "image": "app:1", "port": 8080}
+    });
+
+    let explanation = Validator::explain_file(&base, &current, rule)?;
+
+    assert_eq!(explanation.rule_id, "app");
+    assert!(!explanation.is_valid);
+    assert_eq!(
+        explanation.missing_required,
+        vec!["/metadata/annotations/deployedAt"]
+    );
+
+    let paths: Vec<&str> = explanation
+        .changes
+        .iter()
+        .map(|change| change.path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["/metadata/name", "/spec/port", "/spec/replicas"]
+    );
+
+    let name = &explanation.changes[0];
+    assert!(!name.allowed);
+    assert_eq!(name.pattern.as_deref(), Some("/metadata/name"));
+    assert_eq!(name.reason.as_deref(), Some("forbidden path modified"));
+    assert_eq!(name.when_met, None);
+
+    let port = &explanation.changes[1];
+    assert!(!port.allowed);
+    assert_eq!(port.pattern, None);
+    assert_eq!(port.old, Some(serde_json::json!(80)));
+    assert_eq!(port.new, Some(serde_json::json!(8080)));
+
+    let replicas = &explanation.changes[2];
+    assert!(replicas.allowed);
+    assert_eq!(replicas.pattern.as_deref(), Some("/spec/replicas"));
+    assert_eq!(replicas.when_met, Some(true));
+    assert_eq!(replicas.reason, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_violations_caps_the_report() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - id: info
+    match:
+    - path: /kind
+      value: Deployment
+    allowedChanges: []
+    severity: info
+  - id: frozen
+    match:
+    - path: /kind
+      value: Deployment
+    allowedChanges: []
+"#,
+    )?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    for path in ["a.yaml", "b.yaml", "c.yaml"] {
+        changed_files.insert(
+            path.to_string(),
+            FileChange {
+                base_content: Some("kind: Deployment\nreplicas: 1\n".to_string()),
+                current_content: Some("kind: Deployment\nreplicas: 2\n".to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+    }
+
+    let validator = |max_violations| {
+        Validator::with_options(
+            config.clone(),
+            ValidatorOptions {
+                max_violations,
+                ..Default::default()
+            },
+        )
+    };
+
+    let result = validator(None).validate_changes(&changed_files)?;
+    assert_eq!(result.violations.len(), 6);
+    assert_eq!(result.omitted_violations, 0);
+    assert!(!result.truncated);
+
+    // The first file's info violation is dropped in favour of its error, and the
+    // remaining files are not checked
+    let result = validator(Some(1)).validate_changes(&changed_files)?;
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.violations[0].file_path, "a.yaml");
+    assert_eq!(result.violations[0].rule_id, "frozen");
+    assert_eq!(result.omitted_violations, 1);
+    assert!(result.truncated);
+    assert_eq!(result.files_processed, 1);
+    assert!(!result.is_valid);
+    assert_eq!(result.error_count, 1);
+
+    let json = serde_json::to_value(&result)?;
+    assert_eq!(json["omitted_violations"], 1);
+    assert_eq!(json["truncated"], true);
+
+    // A cap reached exactly on the last file leaves nothing out
+    let result = validator(Some(6)).validate_changes(&changed_files)?;
+    assert_eq!(result.violations.len(), 6);
+    assert_eq!(result.omitted_violations, 0);
+    assert!(!result.truncated);
+
+    Ok(())
+}
+
+#[test]
+fn test_immutable_paths_override_the_allow_list() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - match:
+    - path: /kind
+      value: Application
+    allowedChanges:
+    - /kind
+    - /metadata/**
+    - /spec/**
+    immutable:
+    - /metadata/name
+    - /spec/project
+    allowNewFiles: false
+"#,
+    )?;
+
+    let change = |base: &str, current: &str| FileChange {
+        base_content: Some(base.to_string()),
+        current_content: Some(current.to_string()),
+        change_type: ChangeType::Modified,
+    };
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "renamed.yaml".to_string(),
+        change(
+            "kind: Application\nmetadata:\n  name: app\n",
+            "kind: Application\nmetadata:\n  name: other\n",
+        ),
+    );
+    // Removing the field, or an object containing it, is a change too
+    changed_files.insert(
+        "removed.yaml".to_string(),
+        change(
+            "kind: Application\nmetadata:\n  name: app\nspec:\n  project: default\n",
+            "kind: Application\nmetadata:\n  name: app\n",
+        ),
+    );
+    changed_files.insert(
+        "allowed.yaml".to_string(),
+        change(
+            "kind: Application\nmetadata:\n  name: app\n  labels: {}\n",
+            "kind: Application\nmetadata:\n  name: app\n  labels: {team: a}\n",
+        ),
+    );
+    // New files may set immutable fields
+    changed_files.insert(
+        "new.yaml".to_string(),
+        FileChange {
+            base_content: None,
+            current_content: Some("kind: Application\nmetadata:\n  name: app\n".to_string()),
+            change_type: ChangeType::Added,
+        },
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+
+    let mut found: Vec<(String, String, Option<String>)> = result
+        .violations
+        .iter()
+        .flat_map(|v| {
+            v.unauthorized_changes
+                .iter()
+                .map(|c| (v.file_path.clone(), c.path.clone(), c.reason.clone()))
+        })
+        .collect();
+    found.sort();
+
+    let immutable = Some("immutable field changed".to_string());
+    assert_eq!(
+        found,
+        vec![
+            (
+                "removed.yaml".to_string(),
+                "/spec".to_string(),
+                immutable.clone()
+            ),
+            (
+                "renamed.yaml".to_string(),
+                "/metadata/name".to_string(),
+                immutable
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_index_ranges_limit_allowed_elements() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - match:
+    - path: /kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/[0:2]/revision
+"#,
+    )?;
+
+    let generators = |revisions: [&str; 3]| {
+        let items: Vec<String> = revisions
+            .iter()
+            .map(|revision| format!("  - revision: {}\n", revision))
+            .collect();
+        format!(
+            "kind: ApplicationSet\nspec:\n  generators:\n{}",
+            items.concat()
+        )
+    };
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "appset.yaml".to_string(),
+        FileChange {
+            base_content: Some(generators(["1.0.0", "1.0.0", "1.0.0"])),
+            current_content: Some(generators(["1.1.0", "1.1.0", "1.1.0"])),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+
+    assert_eq!(result.violations.len(), 1);
+    let changes = &result.violations[0].unauthorized_changes;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].path, "/spec/generators/2/revision");
+
+    for (pattern, message) in [
+        ("/spec/[3:1]", "index range '[3:1]' is empty"),
+        ("/spec/[x:1]", "index range must be written as [start:end]"),
+    ] {
+        let err = format!(
+            "rules:\n  - match: []\n    allowedChanges: ['{}']\n",
+            pattern
+        )
+        .parse::<Config>()
+        .unwrap_err();
+        assert!(matches!(err, JiffsError::InvalidPointer(_)), "{:?}", err);
+        assert!(format!("{:#}", err).contains(message), "{:#}", err);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_violations_are_reported_in_a_stable_order() -> Result<()> {
+    let config: Config = serde_norway::from_str(
+        r#"
+rules:
+  - match:
+    - path: /kind
+      value: Deployment
+    allowedChanges: []
+    requiredChanges:
+    - /metadata/annotations/deployedAt
+"#,
+    )?;
+
+    // Each HashMap gets its own random iteration order
+    let changes = || {
+        let mut changed_files = std::collections::HashMap::new();
+        for i in 0..20 {
+            changed_files.insert(
+                format!("apps/app-{:02}.yaml", i),
+                FileChange {
+                    base_content: Some("kind: Deployment\nspec: {a: 1, b: 1, c: 1}\n".to_string()),
+                    current_content: Some(
+                        "kind: Deployment\nspec: {a: 2, b: 2, c: 2}\n".to_string(),
+                    ),
+                    change_type: ChangeType::Modified,
+                },
+            );
+        }
+        changed_files
+    };
+
+    let run = || -> Result<String> {
+        let result = Validator::new(config.clone()).validate_changes(&changes())?;
+        Ok(serde_json::to_string(&result)?)
+    };
+    let first = run()?;
+    for _ in 0..5 {
+        assert_eq!(run()?, first);
+    }
+
+    let result = Validator::new(config.clone()).validate_changes(&changes())?;
+    let files: Vec<&str> = result
+        .violations
+        .iter()
+        .map(|v| v.file_path.as_str())
+        .collect();
+    let mut sorted = files.clone();
+    sorted.sort();
+    assert_eq!(files, sorted);
+
+    let paths: Vec<&str> = result.violations[0]
+        .unauthorized_changes
+        .iter()
+        .map(|c| c.path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/metadata/annotations/deployedAt",
+            "/spec/a",
+            "/spec/b",
+            "/spec/c"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_allowed_values_restrict_new_values() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: images
+    match: []
+    allowedChanges:
+    - path: /spec/image
+      allowedValues: [{glob: "registry.example.com/app:*"}, {regex: "^ghcr\\.io/"}]
+    - path: /spec/replicas
+      allowedValues: [1, 2, 3]
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let validate = |current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some(
+                    "spec:\n  image: registry.example.com/app:1.0\n  replicas: 1\n".to_string(),
+                ),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+
+    assert!(
+        validate("spec:\n  image: registry.example.com/app:1.1\n  replicas: 3\n")?
+            .violations
+            .is_empty()
+    );
+    assert!(
+        validate("spec:\n  image: ghcr.io/app:1.1\n  replicas: 1\n")?
+            .violations
+            .is_empty()
+    );
+
+    let result = validate("spec:\n  image: docker.io/app:1.1\n  replicas: 10\n")?;
+    let changes = &result.violations[0].unauthorized_changes;
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].path, "/spec/image");
+    assert_eq!(
+        changes[0].reason.as_deref(),
+        Some("value \"docker.io/app:1.1\" not allowed")
+    );
+    assert_eq!(changes[1].path, "/spec/replicas");
+    assert_eq!(changes[1].reason.as_deref(), Some("value 10 not allowed"));
+
+    Ok(())
+}
+
+#[test]
+fn test_renamed_files_are_validated_against_their_source() -> Result<()> {
+    let policy = |allow_renames: bool| -> Result<Config> {
+        Ok(format!(
+            r#"
+rules:
+  - id: apps
+    files: ["apps/**"]
+    allowedChanges: [/spec/replicas]
+    forbiddenChanges: [/spec/project]
+    allowRenames: {}
+"#,
+            allow_renames
+        )
+        .parse()?)
+    };
+
+    let renamed = |current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "apps/new.yaml".to_string(),
+            FileChange {
+                base_content: Some("spec:\n  project: a\n  replicas: 1\n".to_string()),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Renamed {
+                    from: "apps/old.yaml".to_string(),
+                },
+            },
+        );
+        changed_files
+    };
+    let unchanged = renamed("spec:\n  project: a\n  replicas: 1\n");
+    let flipped = renamed("spec:\n  project: b\n  replicas: 2\n");
+
+    let validator = Validator::new(policy(true)?);
+    assert!(
+        validator
+            .validate_changes(&unchanged)?
+            .violations
+            .is_empty()
+    );
+    assert!(
+        validator
+            .validate_changes(&renamed("spec:\n  project: a\n  replicas: 2\n"))?
+            .violations
+            .is_empty()
+    );
+
+    // Flipping a forbidden field is caught even though the path changed too
+    let result = validator.validate_changes(&flipped)?;
+    assert_eq!(
+        changes(&result.violations[0]),
+        vec!["/spec/project (forbidden path modified)"]
+    );
+
+    let validator = Validator::new(policy(false)?);
+    let result = validator.validate_changes(&unchanged)?;
+    assert_eq!(
+        result.violations[0].unauthorized_changes[0]
+            .reason
+            .as_deref(),
+        Some("File renamed from apps/old.yaml")
+    );
+    let result = validator.validate_changes(&flipped)?;
+    assert_eq!(result.violations[0].unauthorized_changes.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_rule_message_replaces_generated_description() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: release
+    message: "Only bump {path} via the release bot (was {old}, got {new})"
+    match: []
+    allowedChanges: [/spec/replicas]
+  - id: generated
+    match: []
+    forbiddenChanges: [/spec/project]
+"#
+    .parse()?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("spec:\n  project: a\n  targetRevision: v1\n".to_string()),
+            current_content: Some("spec:\n  project: b\n  targetRevision: v2\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+
+    assert_eq!(
+        result.violations[0].rule_description,
+        "Only bump /spec/project via the release bot (was a, got b)"
+    );
+    assert!(
+        result.violations[1]
+            .rule_description
+            .contains("forbids changes to")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_array_keys_pair_elements_by_identity() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: appset
+    match: []
+    allowedChanges: [/spec/generators/*/list/revision]
+    arrayKeys:
+      /spec/generators: env
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let validate = |current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "appset.yaml".to_string(),
+            FileChange {
+                base_content: Some(
+                    "spec:\n  generators:\n  - {env: dev, list: {revision: v1}}\n  - {env: prod, list: {revision: v1}}\n"
+                        .to_string(),
+                ),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+
+    // Reordering while bumping a revision only reports the bump, which is allowed
+    let result = validate(
+        "spec:\n  generators:\n  - {env: prod, list: {revision: v1}}\n  - {env: dev, list: {revision: v2}}\n",
+    )?;
+    assert!(result.violations.is_empty(), "{:?}", result.violations);
+
+    let result = validate(
+        "spec:\n  generators:\n  - {env: qa, list: {revision: v1}}\n  - {env: dev, list: {revision: v1}}\n  - {env: prod, list: {revision: v1}}\n",
+    )?;
+    assert_eq!(changes(&result.violations[0]), vec!["/spec/generators/0"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_rules_with_branches_only_apply_on_matching_branches() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: strict
+    branches: [main, "release/*"]
+    match: []
+    allowedChanges: []
+  - id: relaxed
+    match: []
+    forbiddenChanges: [/metadata/name]
+"#
+    .parse()?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some("spec:\n  replicas: 1\n".to_string()),
+            current_content: Some("spec:\n  replicas: 2\n".to_string()),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let rule_ids = |branch: Option<&str>| -> Result<Vec<String>> {
+        let validator = Validator::with_options(
+            config.clone(),
+            ValidatorOptions {
+                branch: branch.map(str::to_string),
+                ..Default::default()
+            },
+        );
+        let result = validator.validate_changes(&changed_files)?;
+        Ok(result.violations.into_iter().map(|v| v.rule_id).collect())
+    };
+
+    assert_eq!(rule_ids(Some("main"))?, vec!["strict"]);
+    assert_eq!(rule_ids(Some("release/1.2"))?, vec!["strict"]);
+    assert!(rule_ids(Some("feature/x"))?.is_empty());
+    // An unknown branch, e.g. a detached HEAD, matches no branches
+    assert!(rule_ids(None)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_deeply_nested_documents_fail_gracefully() -> Result<()> {
+    let config: Config = "rules:\n  - match: []\n    allowedChanges: [/spec/replicas]\n".parse()?;
+    let validator = Validator::with_options(
+        config,
+        ValidatorOptions {
+            fail_on_parse_error: true,
+            max_depth: Some(10),
+            ..Default::default()
+        },
+    );
+
+    let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+    let mut changed_files = std::collections::HashMap::new();
+    for (file_path, content) in [
+        ("deep.json", nested(20)),
+        ("pathological.json", nested(100_000)),
+        ("shallow.json", nested(5)),
+    ] {
+        changed_files.insert(
+            file_path.to_string(),
+            FileChange {
+                base_content: None,
+                current_content: Some(content),
+                change_type: ChangeType::Added,
+            },
+        );
+    }
+
+    let result = validator.validate_changes(&changed_files)?;
+    let files: Vec<&str> = result
+        .violations
+        .iter()
+        .map(|v| v.file_path.as_str())
+        .collect();
+    assert_eq!(files, vec!["deep.json", "pathological.json"]);
+    assert!(
+        result
+            .violations
+            .iter()
+            .all(|v| v.rule_id == PARSE_ERROR_RULE_ID)
+    );
+    assert!(
+        result.violations[0].unauthorized_changes[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("nested deeper than 10 levels")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_exists_conditions_in_match_and_when() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: automated
+    match:
+    - path: /spec/syncPolicy/automated
+      exists: true
+    allowedChanges: [/spec/replicas]
+    when:
+    - path: /metadata/annotations/frozen
+      exists: false
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let validate = |base: &str, current: &str| {
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some(base.to_string()),
+                current_content: Some(current.to_string()),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+
+    let automated = "spec:\n  syncPolicy:\n    automated: {}\n  replicas: 1\n";
+    let result = validate(automated, &automated.replace("replicas: 1", "replicas: 2"))?;
+    assert_eq!(result.files_matched, 1);
+    assert!(result.violations.is_empty());
+
+    let frozen = format!(
+        "metadata:\n  annotations:\n    frozen: 'yes'\n{}",
+        automated
+    );
+    let result = validate(&frozen, &frozen.replace("replicas: 1", "replicas: 2"))?;
+    assert_eq!(result.violations.len(), 1);
+
+    let manual = "spec:\n  syncPolicy: {}\n  image: app:1\n";
+    let result = validate(manual, &manual.replace("app:1", "app:2"))?;
+    assert_eq!(result.files_matched, 0);
+
+    let err = "rules:\n  - match:\n    - path: /spec\n      exists: true\n      value: 1\n"
+        .parse::<Config>()
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("cannot be combined"));
+
+    Ok(())
+}
+
+#[test]
+fn test_when_paths_without_leading_slash() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - match:
+    - path: kind
+      value: Deployment
+    allowedChanges: [/spec/replicas]
+    when:
+    - path: kind
+      value: Deployment
+"#
+    .parse()?;
+
+    let base = "kind: Deployment\nspec:\n  replicas: 1\n";
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "app.yaml".to_string(),
+        FileChange {
+            base_content: Some(base.to_string()),
+            current_content: Some(base.replace("replicas: 1", "replicas: 2")),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+    assert_eq!(result.files_matched, 1);
+    assert!(result.violations.is_empty(), "{:?}", result.violations);
+
+    Ok(())
+}
+
+#[test]
+fn test_conditions_that_cannot_be_compared_are_errors() -> Result<()> {
+    let validate = |policy: &str| -> Result<_> {
+        let config: Config = policy.parse()?;
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "app.yaml".to_string(),
+            FileChange {
+                base_content: Some("spec:\n  replicas: many\n  containers: [{image: a}]\n".into()),
+                current_content: Some(
+                    "spec:\n  replicas: many\n  containers: [{image: b}]\n".into(),
+                ),
+                change_type: ChangeType::Modified,
+            },
+        );
+        Ok(Validator::new(config).validate_changes(&changed_files))
+    };
+
+    // A match condition ordering a string against a number names the rule and file
+    let err = validate(
+        "rules:\n  - id: scaled\n    match:\n    - {path: /spec/replicas, op: ge, value: 3}\n    allowedChanges: []\n",
+    )?
+    .unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("scaled"), "{}", message);
+    assert!(message.contains("app.yaml"), "{}", message);
+    assert!(message.contains("Cannot compare"), "{}", message);
+
+    // So does a `when` condition whose wildcards are evaluated across the document
+    let err = validate(
+        "rules:\n  - match: []\n    allowedChanges: [/spec/containers/*/image]\n    when:\n    - {path: /**/replicas, op: lt, value: 3}\n",
+    )?
+    .unwrap_err();
+    assert!(format!("{:#}", err).contains("Cannot compare"));
+
+    Ok(())
+}
+
+#[test]
+fn test_explanations_are_returned_with_the_result() -> Result<()> {
+    let config: Config =
+        "rules:\n  - id: replicas\n    match: []\n    allowedChanges: [/spec/replicas]\n"
+            .parse()?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    for file in ["b.yaml", "a.yaml"] {
+        changed_files.insert(
+            file.to_string(),
+            FileChange {
+                base_content: Some("spec:\n  replicas: 1\n".into()),
+                current_content: Some("spec:\n  replicas: 2\n".into()),
+                change_type: ChangeType::Modified,
+            },
+        );
+    }
+
+    let validator = |explain| {
+        Validator::with_options(
+            config.clone(),
+            ValidatorOptions {
+                explain,
+                ..Default::default()
+            },
+        )
+    };
+
+    assert!(
+        validator(false)
+            .validate_changes(&changed_files)?
+            .explanations
+            .is_empty()
+    );
+
+    // Left to the caller to print, in the order the files were checked
+    let result = validator(true).validate_changes(&changed_files)?;
+    let files: Vec<&str> = result
+        .explanations
+        .iter()
+        .map(|(file, _)| file.as_str())
+        .collect();
+    assert_eq!(files, vec!["a.yaml", "b.yaml"]);
+    let lines = &result.explanations[0].1;
+    assert!(
+        lines.iter().any(|line| line == "rule replicas matched"),
+        "{:?}",
+        lines
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_scanned_directory_is_validated_as_new_files() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - match:
+    - path: /kind
+      value: Secret
+    allowedChanges: [/metadata/**]
+    allowNewFiles: false
+"#
+    .parse()?;
+
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir(dir.path().join("apps"))?;
+    std::fs::write(
+        dir.path().join("apps/secret.yaml"),
+        "kind: Secret\nmetadata:\n  name: token\ndata:\n  token: c2VjcmV0\n",
+    )?;
+    std::fs::write(dir.path().join("apps/config.yaml"), "kind: ConfigMap\n")?;
+
+    let diff = GitDiff::from_dir(dir.path(), &Default::default())?;
+    let result = Validator::new(config).validate_changes(&diff.changed_files)?;
+
+    assert_eq!(result.files_processed, 2);
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.violations[0].file_path, "apps/secret.yaml");
+    assert_eq!(changes(&result.violations[0]), ["/data/token", "/kind"]);
+
+    Ok(())
+}
+
+#[cfg(feature = "hcl")]
+#[test]
+fn test_hcl_manifests_are_validated() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - id: instance-type-only
+    files: ["**/*.tf"]
+    match: []
+    allowedChanges:
+    - /resource/aws_instance/*/instance_type
+"#
+    .parse()?;
+
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert(
+        "infra/main.tf".to_string(),
+        FileChange {
+            base_content: Some(
+                "resource \"aws_instance\" \"web\" {\n  instance_type = \"t3.micro\"\n  ami = \"a\"\n}\n"
+                    .to_string(),
+            ),
+            current_content: Some(
+                "resource \"aws_instance\" \"web\" {\n  instance_type = \"t3.large\"\n  ami = \"b\"\n}\n"
+                    .to_string(),
+            ),
+            change_type: ChangeType::Modified,
+        },
+    );
+
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+    assert_eq!(result.files_matched, 1);
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(
+        changes(&result.violations[0]),
+        ["/resource/aws_instance/web/ami"]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_crlf_line_endings_compare_equal_to_lf() -> Result<()> {
+    let policy = r#"
+rules:
+  - match:
+    - path: /kind
+      value: ConfigMap
+    allowedChanges: [/metadata/**]
+"#;
+
+    // A multiline string committed with LF and checked out with CRLF
+    let mut changed_files = std::collections::HashMap::new();
+    for (file_path, content) in [
+        (
+            "config.yaml",
+            "kind: ConfigMap\ndata:\n  script: |\n    set -e\n    run\n",
+        ),
+        (
+            "config.toml",
+            "kind = \"ConfigMap\"\n\n[data]\nscript = \"\"\"\nset -e\nrun\n\"\"\"\n",
+        ),
+    ] {
+        changed_files.insert(
+            file_path.to_string(),
+            FileChange {
+                base_content: Some(content.to_string()),
+                current_content: Some(content.replace('\n', "\r\n")),
+                change_type: ChangeType::Modified,
+            },
+        );
+    }
+
+    let result = Validator::new(policy.parse()?).validate_changes(&changed_files)?;
+    assert_eq!(result.files_matched, 2);
+    assert!(result.violations.is_empty(), "{:?}", result.violations);
+
+    // Exact line endings make the TOML string differ
+    let result = Validator::with_options(
+        policy.parse()?,
+        ValidatorOptions {
+            preserve_line_endings: true,
+            ..Default::default()
+        },
+    )
+    .validate_changes(&changed_files)?;
+    assert_eq!(result.violations.len(), 1);
+    assert_eq!(result.violations[0].file_path, "config.toml");
+    assert_eq!(changes(&result.violations[0]), ["/data/script"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_files_parsed_counts_only_parseable_files() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - match:
+    - path: /kind
+      value: Deployment
+"#
+    .parse()?;
+
+    let added = |content: &str| FileChange {
+        base_content: None,
+        current_content: Some(content.to_string()),
+        change_type: ChangeType::Added,
+    };
+    let mut changed_files = std::collections::HashMap::new();
+    changed_files.insert("service.yaml".to_string(), added("kind: Service\n"));
+    changed_files.insert("broken.yaml".to_string(), added("kind: [\n"));
+
+    // Nothing matched although a manifest changed, which --require-any-match rejects
+    let result = Validator::new(config).validate_changes(&changed_files)?;
+    assert_eq!(result.files_processed, 2);
+    assert_eq!(result.files_parsed, 1);
+    assert_eq!(result.files_matched, 0);
+
+    // The --stats counts leave out the file that failed to parse, too
+    assert_eq!(result.stats.files_parsed, 1);
+    assert_eq!(result.stats.bytes_parsed, "kind: Service\n".len());
+
+    Ok(())
+}
+
+#[test]
+fn test_unless_conditions_reject_otherwise_allowed_changes() -> Result<()> {
+    let config: Config = r#"
+rules:
+  - match:
+    - path: /kind
+      value: ApplicationSet
+    allowedChanges:
+    - /spec/generators/*/values/revision
+    when:
+    - path: /spec/generators/*/values/managed
+      value: true
+    unless:
+    - path: /spec/generators/*/env
+      value: production
+"#
+    .parse()?;
+    let validator = Validator::new(config);
+
+    let generator = |env: &str, managed: bool, revision: &str| {
+        format!(
+            "  - env: {}\n    values:\n      managed: {}\n      revision: {}\n",
+            env, managed, revision
+        )
+    };
+    let validate = |env: &str, managed: bool| {
+        let file = |revision| {
+            format!(
+                "kind: ApplicationSet\nspec:\n  generators:\n{}",
+                generator(env, managed, revision)
+            )
+        };
+        let mut changed_files = std::collections::HashMap::new();
+        changed_files.insert(
+            "appset.yaml".to_string(),
+            FileChange {
+                base_content: Some(file("'1.0'")),
+                current_content: Some(file("'1.1'")),
+                change_type: ChangeType::Modified,
+            },
+        );
+        validator.validate_changes(&changed_files)
+    };
+    let reasons = |result: &jiffs::validator::ValidationResult| -> Vec<Option<String>> {
+        result
+            .violations
+            .iter()
+            .flat_map(|v| v.unauthorized_changes.iter().map(|c| c.reason.clone()))
+            .collect()
+    };
+
+    assert!(validate("staging", true)?.violations.is_empty());
+    assert_eq!(
+        reasons(&validate("production", true)?),
+        [Some("unless condition met".to_string())]
+    );
+    // `when` is checked first, so a change it rejects is reported as such
+    assert_eq!(
+        reasons(&validate("production", false)?),
+        [Some("when condition not met".to_string())]
+    );
+
+    Ok(())
+}
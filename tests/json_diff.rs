@@ -1,3 +1,4 @@
+use jiffs::git::{ChangeType, FileChange, GitDiff};
 use jiffs::json_path::JsonPathMatcher;
 use serde_json::json;
 
@@ -90,3 +91,56 @@ fn json_diff_handles_additions_and_modifications() {
     assert_eq!(new_field_change.0, None);
     assert_eq!(new_field_change.1, Some(json!("added")));
 }
+
+#[test]
+fn computed_changes_cover_every_parseable_file() {
+    let file = |base: Option<&str>, current: Option<&str>, change_type| FileChange {
+        base_content: base.map(str::to_string),
+        current_content: current.map(str::to_string),
+        change_type,
+    };
+
+    let mut diff = GitDiff::default();
+    diff.changed_files.insert(
+        "app.yaml".to_string(),
+        file(
+            Some("spec:\n  replicas: 1\n  image: app:1\n"),
+            Some("spec:\n  replicas: 2\n  image: app:1\n"),
+            ChangeType::Modified,
+        ),
+    );
+    diff.changed_files.insert(
+        "multi.yaml".to_string(),
+        file(
+            Some("kind: A\n"),
+            Some("kind: A\n---\nkind: B\n"),
+            ChangeType::Modified,
+        ),
+    );
+    diff.changed_files.insert(
+        "old.json".to_string(),
+        file(Some(r#"{"name": "old"}"#), None, ChangeType::Deleted),
+    );
+    diff.changed_files.insert(
+        "notes.txt".to_string(),
+        file(None, Some("key: [unterminated\n"), ChangeType::Added),
+    );
+
+    let changes = diff.compute_changes().unwrap();
+
+    let mut paths: Vec<&String> = changes.keys().collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        ["app.yaml", "multi.yaml#0", "multi.yaml#1", "old.json"]
+    );
+
+    assert_eq!(changes["app.yaml"].len(), 1);
+    assert_eq!(
+        changes["app.yaml"]["/spec/replicas"],
+        (Some(json!(1)), Some(json!(2)))
+    );
+    assert!(changes["multi.yaml#0"].is_empty());
+    assert_eq!(changes["multi.yaml#1"]["/kind"], (None, Some(json!("B"))));
+    assert_eq!(changes["old.json"]["/name"], (Some(json!("old")), None));
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use jiffs::git::{ChangeType, DiffOptions, GitDiff};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+fn commit_file(dir: &Path, path: &str, content: &str) -> Result<()> {
+    std::fs::write(dir.join(path), content)?;
+    git(dir, &["add", path])?;
+    git(dir, &["commit", "-q", "-m", path])
+}
+
+#[test]
+fn merge_base_excludes_unrelated_commits_on_base() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    commit_file(dir, "app.yaml", "kind: ApplicationSet\nrevision: 1.0.0\n")?;
+
+    // Diverge: the feature branch bumps the revision while main gains another file
+    git(dir, &["checkout", "-q", "-b", "feature"])?;
+    commit_file(dir, "app.yaml", "kind: ApplicationSet\nrevision: 1.1.0\n")?;
+    git(dir, &["checkout", "-q", "main"])?;
+    commit_file(dir, "other.yaml", "kind: Application\n")?;
+    git(dir, &["checkout", "-q", "feature"])?;
+
+    std::env::set_current_dir(dir)?;
+
+    // Against the tip of main, the unrelated file shows up as a deletion
+    let git_diff = GitDiff::new("main", &DiffOptions::default())?;
+    assert_eq!(git_diff.changed_files.len(), 2);
+    assert_eq!(
+        git_diff.changed_files["other.yaml"].change_type,
+        ChangeType::Deleted
+    );
+
+    // Against the merge base, only the branch's own change remains
+    let options = DiffOptions {
+        merge_base: true,
+        ..Default::default()
+    };
+    let git_diff = GitDiff::new("main", &options)?;
+    assert_eq!(git_diff.changed_file_paths(), vec!["app.yaml"]);
+
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.change_type, ChangeType::Modified);
+    assert_eq!(
+        change.base_content.as_deref(),
+        Some("kind: ApplicationSet\nrevision: 1.0.0\n")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,587 @@
+use anyhow::Result;
+use jiffs::error::JiffsError;
+use jiffs::git::{self, ChangeType, DiffMode, DiffOptions, GitDiff, SymlinkMode};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn diff_runs_in_repository_outside_current_directory() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["add", "app.yaml"])?;
+    git(dir, &["commit", "-q", "-m", "app"])?;
+
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        ..Default::default()
+    };
+
+    // The working tree copy is read relative to the repository, not the process
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.change_type, ChangeType::Modified);
+    assert_eq!(change.base_content.as_deref(), Some("revision: 1.0.0\n"));
+    assert_eq!(change.current_content.as_deref(), Some("revision: 1.1.0\n"));
+
+    // Nothing is staged yet
+    options.mode = DiffMode::Staged;
+    assert!(GitDiff::new("HEAD", &options)?.changed_files.is_empty());
+
+    let err = GitDiff::new("no-such-ref", &options).unwrap_err();
+    assert!(matches!(err, JiffsError::Git(_)), "{:?}", err);
+
+    Ok(())
+}
+
+#[test]
+fn diff_between_two_commits_reads_both_sides_from_git() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    std::fs::write(dir.join("old.yaml"), "kind: Old\n")?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+    git(dir, &["tag", "first"])?;
+
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+    git(dir, &["rm", "-q", "old.yaml"])?;
+    git(dir, &["commit", "-q", "-am", "second"])?;
+    git(dir, &["tag", "second"])?;
+
+    // Later history and the working tree must not affect a diff between two commits
+    std::fs::write(dir.join("app.yaml"), "revision: 2.0.0\n")?;
+    std::fs::write(dir.join("old.yaml"), "kind: Restored\n")?;
+    git(dir, &["commit", "-q", "-am", "third"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 3.0.0\n")?;
+
+    let options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        head: Some("second".to_string()),
+        ..Default::default()
+    };
+    let git_diff = GitDiff::new("first", &options)?;
+    assert_eq!(git_diff.changed_files.len(), 2);
+
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.base_content.as_deref(), Some("revision: 1.0.0\n"));
+    assert_eq!(change.current_content.as_deref(), Some("revision: 1.1.0\n"));
+
+    let deleted = &git_diff.changed_files["old.yaml"];
+    assert_eq!(deleted.change_type, ChangeType::Deleted);
+    assert_eq!(deleted.base_content.as_deref(), Some("kind: Old\n"));
+    assert!(deleted.current_content.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn blobs_are_read_from_both_commits() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::create_dir(dir.join("with space"))?;
+    let files = [
+        "a.yaml",
+        "with space/b.yaml",
+        "empty.yaml",
+        "gone.yaml",
+        "new\nline.yaml",
+    ];
+    for (i, name) in files.iter().enumerate() {
+        let content = if *name == "empty.yaml" {
+            String::new()
+        } else {
+            format!("file: {}\n", i)
+        };
+        std::fs::write(dir.join(name), content)?;
+    }
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+    git(dir, &["tag", "first"])?;
+
+    for name in [
+        "a.yaml",
+        "with space/b.yaml",
+        "empty.yaml",
+        "new\nline.yaml",
+    ] {
+        std::fs::write(dir.join(name), format!("changed: {}\n", name))?;
+    }
+    git(dir, &["rm", "-q", "gone.yaml"])?;
+    git(dir, &["commit", "-q", "-am", "second"])?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        head: Some("HEAD".to_string()),
+        ..Default::default()
+    };
+
+    // A path with a newline cannot go through `git cat-file --batch`, so both the
+    // batched and the per-file reads must return what was committed
+    for with_newline in [false, true] {
+        options.files = files
+            .iter()
+            .filter(|name| with_newline || !name.contains('\n'))
+            .map(|name| name.to_string())
+            .collect();
+        let git_diff = GitDiff::new("first", &options)?;
+        assert_eq!(git_diff.changed_files.len(), options.files.len());
+
+        for (i, name) in files.iter().enumerate() {
+            let Some(change) = git_diff.changed_files.get(*name) else {
+                assert!(!with_newline && name.contains('\n'));
+                continue;
+            };
+            let base = if *name == "empty.yaml" {
+                String::new()
+            } else {
+                format!("file: {}\n", i)
+            };
+            assert_eq!(
+                change.base_content.as_deref(),
+                Some(base.as_str()),
+                "{}",
+                name
+            );
+
+            if *name == "gone.yaml" {
+                assert_eq!(change.change_type, ChangeType::Deleted);
+                assert!(change.current_content.is_none());
+            } else {
+                let current = format!("changed: {}\n", name);
+                assert_eq!(change.current_content.as_deref(), Some(current.as_str()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn files_over_the_size_limit_are_skipped() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    let large = format!("items:\n{}", "- item\n".repeat(100));
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    std::fs::write(dir.join("generated.yaml"), &large)?;
+    std::fs::write(dir.join("shrunk.yaml"), &large)?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+    std::fs::write(dir.join("generated.yaml"), format!("{}- more\n", large))?;
+    // Only the base side is large
+    std::fs::write(dir.join("shrunk.yaml"), "items: []\n")?;
+    std::fs::write(dir.join("new.yaml"), &large)?;
+    git(dir, &["add", "."])?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        max_file_bytes: Some(100),
+        ..Default::default()
+    };
+
+    for mode in [DiffMode::Worktree, DiffMode::Staged] {
+        options.mode = mode;
+        let git_diff = GitDiff::new("HEAD", &options)?;
+
+        assert_eq!(
+            git_diff.oversized_files,
+            vec!["generated.yaml", "new.yaml", "shrunk.yaml"]
+        );
+        assert_eq!(
+            git_diff.changed_files.keys().collect::<Vec<_>>(),
+            vec!["app.yaml"]
+        );
+    }
+
+    options.max_file_bytes = None;
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    assert!(git_diff.oversized_files.is_empty());
+    assert_eq!(git_diff.changed_files.len(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn files_that_are_not_utf8_are_skipped() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+    // Latin-1 encoded "name: café"
+    std::fs::write(dir.join("latin1.yaml"), b"name: caf\xe9\n")?;
+    git(dir, &["add", "."])?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        ..Default::default()
+    };
+
+    for mode in [DiffMode::Worktree, DiffMode::Staged] {
+        options.mode = mode;
+        let git_diff = GitDiff::new("HEAD", &options)?;
+
+        assert_eq!(git_diff.non_utf8_files, vec!["latin1.yaml"]);
+        assert_eq!(
+            git_diff.changed_files.keys().collect::<Vec<_>>(),
+            vec!["app.yaml"]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn renamed_files_are_compared_against_their_source() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    // Renames are detected even when the repository turns detection off
+    git(dir, &["config", "diff.renames", "false"])?;
+    let content = "spec:\n  project: default\n  image: app:1.0\n  replicas: 1\n";
+    std::fs::write(dir.join("old.yaml"), content)?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    git(dir, &["mv", "old.yaml", "new.yaml"])?;
+    std::fs::write(
+        dir.join("new.yaml"),
+        content.replace("replicas: 1", "replicas: 2"),
+    )?;
+    git(dir, &["add", "."])?;
+
+    let options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        mode: DiffMode::Staged,
+        ..Default::default()
+    };
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    assert_eq!(git_diff.changed_file_paths(), vec!["new.yaml"]);
+
+    let change = &git_diff.changed_files["new.yaml"];
+    assert_eq!(
+        change.change_type,
+        ChangeType::Renamed {
+            from: "old.yaml".to_string()
+        }
+    );
+    assert_eq!(change.base_content.as_deref(), Some(content));
+    assert!(
+        change
+            .current_content
+            .as_deref()
+            .unwrap()
+            .contains("replicas: 2")
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_read_as_link_text_when_not_followed() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(
+        dir.join("prod.yaml"),
+        "replicas: 3
+",
+    )?;
+    std::fs::write(
+        dir.join("staging.yaml"),
+        "replicas: 1
+",
+    )?;
+    std::os::unix::fs::symlink("staging.yaml", dir.join("app.yaml"))?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    std::fs::remove_file(dir.join("app.yaml"))?;
+    std::os::unix::fs::symlink("prod.yaml", dir.join("app.yaml"))?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        ..Default::default()
+    };
+
+    // Git always stores the link text, while the working tree copy is followed by default
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.base_content.as_deref(), Some("staging.yaml"));
+    assert_eq!(change.current_content.as_deref(), Some("replicas: 3\n"));
+
+    options.symlinks = SymlinkMode::LinkText;
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.current_content.as_deref(), Some("prod.yaml"));
+
+    // A link to a missing file still exists as link text
+    std::fs::remove_file(dir.join("prod.yaml"))?;
+    options.files = vec!["app.yaml".to_string()];
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    let change = &git_diff.changed_files["app.yaml"];
+    assert_eq!(change.change_type, ChangeType::Modified);
+    assert_eq!(change.current_content.as_deref(), Some("prod.yaml"));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn configured_git_binary_runs_every_git_command() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["add", "app.yaml"])?;
+    git(dir, &["commit", "-q", "-m", "app"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+
+    // A wrapper logging its subcommand before handing over to the real git
+    let bin = TempDir::new()?;
+    let wrapper = bin.path().join("git-wrapper");
+    let log = bin.path().join("calls.log");
+    std::fs::write(
+        &wrapper,
+        format!(
+            "#!/bin/sh\necho \"$1\" >> '{}'\nexec git \"$@\"\n",
+            log.display()
+        ),
+    )?;
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        merge_base: true,
+        git_binary: Some(wrapper),
+        ..Default::default()
+    };
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    assert_eq!(
+        git_diff.changed_files["app.yaml"].base_content.as_deref(),
+        Some("revision: 1.0.0\n")
+    );
+
+    let calls = std::fs::read_to_string(&log)?;
+    let calls: Vec<_> = calls.lines().collect();
+    assert_eq!(calls[..2], ["merge-base", "diff"]);
+    assert!(calls[2..].iter().all(|call| *call == "cat-file"));
+
+    options.git_binary = Some(bin.path().join("missing"));
+    let err = GitDiff::new("HEAD", &options).unwrap_err();
+    assert!(matches!(err, JiffsError::Git(_)), "{:?}", err);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn hanging_git_commands_time_out_and_transient_failures_are_retried() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["add", "app.yaml"])?;
+    git(dir, &["commit", "-q", "-m", "app"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+
+    // A wrapper whose `git diff` hangs
+    let bin = TempDir::new()?;
+    let hanging = bin.path().join("hanging-git");
+    std::fs::write(
+        &hanging,
+        "#!/bin/sh\nif [ \"$1\" = diff ]; then exec sleep 30; fi\nexec git \"$@\"\n",
+    )?;
+    std::fs::set_permissions(&hanging, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        git_binary: Some(hanging),
+        git_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let err = GitDiff::new("HEAD", &options).unwrap_err();
+    assert!(matches!(err, JiffsError::Git(_)), "{:?}", err);
+    assert!(
+        format!("{:#}", err).contains("git diff timed out after 200ms"),
+        "{:#}",
+        err
+    );
+
+    // A wrapper whose first `git diff` finds the index locked
+    let marker = bin.path().join("locked-once");
+    let flaky = bin.path().join("flaky-git");
+    std::fs::write(
+        &flaky,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = diff ] && [ ! -e '{0}' ]; then\n  touch '{0}'\n  echo \"fatal: Unable to create '.git/index.lock': File exists.\" >&2\n  exit 128\nfi\nexec git \"$@\"\n",
+            marker.display()
+        ),
+    )?;
+    std::fs::set_permissions(&flaky, std::fs::Permissions::from_mode(0o755))?;
+    options.git_binary = Some(flaky);
+
+    options.git_retries = 0;
+    let err = GitDiff::new("HEAD", &options).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("index.lock': File exists"),
+        "{:#}",
+        err
+    );
+
+    std::fs::remove_file(&marker)?;
+    options.git_retries = 1;
+    let git_diff = GitDiff::new("HEAD", &options)?;
+    assert_eq!(
+        git_diff.changed_files["app.yaml"]
+            .current_content
+            .as_deref(),
+        Some("revision: 1.1.0\n")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn only_the_given_files_are_diffed() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    for name in ["app.yaml", "other.yaml", "old.yaml"] {
+        std::fs::write(dir.join(name), "revision: 1.0.0\n")?;
+    }
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    std::fs::write(dir.join("app.yaml"), "revision: 1.1.0\n")?;
+    std::fs::write(dir.join("other.yaml"), "revision: 1.1.0\n")?;
+    std::fs::write(dir.join("new.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["rm", "-q", "old.yaml"])?;
+    git(dir, &["add", "app.yaml", "new.yaml"])?;
+
+    let mut options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        files: ["app.yaml", "new.yaml", "old.yaml", "missing.yaml"]
+            .map(String::from)
+            .to_vec(),
+        ..Default::default()
+    };
+
+    for mode in [DiffMode::Worktree, DiffMode::Staged] {
+        options.mode = mode;
+        let git_diff = GitDiff::new("HEAD", &options)?;
+
+        // other.yaml changed but was not given; missing.yaml exists on neither side
+        let mut paths = git_diff.changed_file_paths();
+        paths.sort();
+        assert_eq!(paths, vec!["app.yaml", "new.yaml", "old.yaml"]);
+
+        let app = &git_diff.changed_files["app.yaml"];
+        assert_eq!(app.change_type, ChangeType::Modified);
+        assert_eq!(app.base_content.as_deref(), Some("revision: 1.0.0\n"));
+        assert_eq!(app.current_content.as_deref(), Some("revision: 1.1.0\n"));
+        assert_eq!(
+            git_diff.changed_files["new.yaml"].change_type,
+            ChangeType::Added
+        );
+        assert_eq!(
+            git_diff.changed_files["old.yaml"].change_type,
+            ChangeType::Deleted
+        );
+    }
+
+    let err = GitDiff::new("no-such-ref", &options).unwrap_err();
+    assert!(matches!(err, JiffsError::Git(_)), "{:?}", err);
+
+    Ok(())
+}
+
+#[test]
+fn current_branch_is_read_from_the_repository() -> Result<()> {
+    let repo = TempDir::new()?;
+    let dir = repo.path();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    git(dir, &["config", "user.email", "test@example.com"])?;
+    git(dir, &["config", "user.name", "test"])?;
+    std::fs::write(dir.join("app.yaml"), "revision: 1.0.0\n")?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-q", "-m", "first"])?;
+
+    let options = DiffOptions {
+        repo: Some(dir.to_path_buf()),
+        ..Default::default()
+    };
+    assert_eq!(git::current_branch(&options)?.as_deref(), Some("main"));
+
+    git(dir, &["checkout", "-q", "-b", "release/1.0"])?;
+    assert_eq!(
+        git::current_branch(&options)?.as_deref(),
+        Some("release/1.0")
+    );
+
+    git(dir, &["checkout", "-q", "--detach"])?;
+    assert_eq!(git::current_branch(&options)?, None);
+
+    Ok(())
+}
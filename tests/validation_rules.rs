@@ -1,8 +1,8 @@
 #[cfg(test)]
 mod validation_rules {
     use anyhow::Result;
-    use jiffs::config::PathValue;
-    use jiffs::json_path::JsonPathMatcher;
+    use jiffs::config::{LengthBounds, OnMissing, Operator, PathValue};
+    use jiffs::json_path::{Explanation, JsonPathMatcher};
     use serde_json::json;
 
     #[test]
@@ -77,6 +77,7 @@ mod validation_rules {
         let when_dev = vec![PathValue {
             path: "/spec/generators/*/clusters/selector/matchLabels/env".to_string(),
             value: json!("development"),
+            ..Default::default()
         }];
 
         let result_dev = JsonPathMatcher::has_allowed_changes_only(
@@ -84,6 +85,7 @@ mod validation_rules {
             &current_json,
             &allowed_patterns,
             Some(&when_dev),
+            &[],
         )?;
 
         // Should be false because we changed production, but when condition only allows development
@@ -96,6 +98,7 @@ mod validation_rules {
         let when_prod = vec![PathValue {
             path: "/spec/generators/*/clusters/selector/matchLabels/env".to_string(),
             value: json!("production"),
+            ..Default::default()
         }];
 
         let result_prod = JsonPathMatcher::has_allowed_changes_only(
@@ -103,6 +106,7 @@ mod validation_rules {
             &current_json,
             &allowed_patterns,
             Some(&when_prod),
+            &[],
         )?;
 
         // Should be true because we changed production and when condition allows production
@@ -117,6 +121,7 @@ mod validation_rules {
             &current_json,
             &allowed_patterns,
             None,
+            &[],
         )?;
 
         // Should be true because the path matches and there are no when conditions
@@ -127,4 +132,364 @@ mod validation_rules {
 
         Ok(())
     }
+
+    #[test]
+    fn unless_conditions_reject_changes_where_they_hold() -> Result<()> {
+        let generator = |env: &str, revision: &str| {
+            json!({
+                "clusters": {
+                    "selector": { "matchLabels": { "env": env } },
+                    "values": { "revision": revision }
+                }
+            })
+        };
+        let base_json = json!({
+            "spec": { "generators": [generator("staging", "1.0"), generator("production", "1.0")] }
+        });
+        let staging_bumped = json!({
+            "spec": { "generators": [generator("staging", "1.1"), generator("production", "1.0")] }
+        });
+        let production_bumped = json!({
+            "spec": { "generators": [generator("staging", "1.0"), generator("production", "1.1")] }
+        });
+
+        let allowed_patterns = vec!["/spec/generators/*/clusters/values/revision".to_string()];
+        let unless_production = vec![PathValue {
+            path: "/spec/generators/*/clusters/selector/matchLabels/env".to_string(),
+            value: json!("production"),
+            ..Default::default()
+        }];
+
+        assert!(JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &staging_bumped,
+            &allowed_patterns,
+            None,
+            &unless_production,
+        )?);
+        assert!(!JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &production_bumped,
+            &allowed_patterns,
+            None,
+            &unless_production,
+        )?);
+
+        // A skipped condition is ignored, and `unless` without evaluated conditions
+        // rejects nothing
+        let unless_missing = vec![PathValue {
+            path: "/spec/generators/*/frozen".to_string(),
+            value: json!(true),
+            on_missing: OnMissing::Skip,
+            ..Default::default()
+        }];
+        assert!(!JsonPathMatcher::unless_conditions_met(
+            &production_bumped,
+            "/spec/generators/1/clusters/values/revision",
+            &unless_missing,
+        )?);
+        assert!(!JsonPathMatcher::unless_conditions_met(
+            &production_bumped,
+            "/spec/generators/1/clusters/values/revision",
+            &[],
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn when_conditions_support_comparison_operators() -> Result<()> {
+        let allowed_patterns = vec!["/spec/image".to_string()];
+        let when_min_replicas = vec![PathValue {
+            path: "/spec/replicas".to_string(),
+            value: json!(3),
+            op: Operator::Ge,
+            ..Default::default()
+        }];
+
+        let base_json = json!({ "spec": { "replicas": 3, "image": "nginx:1.20" } });
+        let current_json = json!({ "spec": { "replicas": 3, "image": "nginx:1.21" } });
+
+        assert!(
+            JsonPathMatcher::has_allowed_changes_only(
+                &base_json,
+                &current_json,
+                &allowed_patterns,
+                Some(&when_min_replicas),
+                &[],
+            )?,
+            "Image change should be allowed with at least 3 replicas"
+        );
+
+        let base_json = json!({ "spec": { "replicas": 2, "image": "nginx:1.20" } });
+        let current_json = json!({ "spec": { "replicas": 2, "image": "nginx:1.21" } });
+
+        assert!(
+            !JsonPathMatcher::has_allowed_changes_only(
+                &base_json,
+                &current_json,
+                &allowed_patterns,
+                Some(&when_min_replicas),
+                &[],
+            )?,
+            "Image change should be rejected with fewer than 3 replicas"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn explanation_records_patterns_and_when_conditions() -> Result<()> {
+        let base_json = json!({
+            "metadata": { "labels": { "env": "staging" } },
+            "spec": { "image": "nginx:1.20", "replicas": 2 }
+        });
+        let current_json = json!({
+            "metadata": { "labels": { "env": "staging" } },
+            "spec": { "image": "nginx:1.21", "replicas": 3 }
+        });
+
+        let allowed_patterns = vec!["/spec/image".to_string()];
+        let when_production = vec![PathValue {
+            path: "/metadata/labels/env".to_string(),
+            value: json!("production"),
+            ..Default::default()
+        }];
+
+        let mut explanation = Explanation::new();
+        let allowed = JsonPathMatcher::has_allowed_changes_only_explained(
+            &base_json,
+            &current_json,
+            &allowed_patterns,
+            Some(&when_production),
+            &[],
+            &mut explanation,
+        )?;
+
+        assert!(!allowed);
+        assert_eq!(
+            explanation.lines(),
+            [
+                "/spec/image: allowed by /spec/image",
+                "  when /metadata/labels/env eq \"production\" (at /metadata/labels/env): false",
+                "/spec/replicas: not matched by any allowed pattern",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn negated_conditions_hold_for_other_and_absent_values() -> Result<()> {
+        let not_application_set = vec![PathValue {
+            path: "kind".to_string(),
+            value: json!("ApplicationSet"),
+            negate: true,
+            ..Default::default()
+        }];
+
+        assert!(JsonPathMatcher::matches_conditions(
+            &json!({ "kind": "Deployment" }),
+            &not_application_set
+        )?);
+        assert!(!JsonPathMatcher::matches_conditions(
+            &json!({ "kind": "ApplicationSet" }),
+            &not_application_set
+        )?);
+        assert!(JsonPathMatcher::matches_conditions(
+            &json!({ "metadata": {} }),
+            &not_application_set
+        )?);
+
+        // In `when`, a negated condition on an absent path holds
+        let not_frozen = vec![PathValue {
+            path: "/metadata/labels/frozen".to_string(),
+            value: json!("true"),
+            negate: true,
+            ..Default::default()
+        }];
+        let base_json = json!({ "spec": { "image": "nginx:1.20" } });
+        let current_json = json!({ "spec": { "image": "nginx:1.21" } });
+        let allowed_patterns = vec!["/spec/image".to_string()];
+
+        assert!(JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &current_json,
+            &allowed_patterns,
+            Some(&not_frozen),
+            &[],
+        )?);
+
+        let current_json = json!({
+            "metadata": { "labels": { "frozen": "true" } },
+            "spec": { "image": "nginx:1.21" }
+        });
+        let base_json = json!({
+            "metadata": { "labels": { "frozen": "true" } },
+            "spec": { "image": "nginx:1.20" }
+        });
+
+        assert!(!JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &current_json,
+            &allowed_patterns,
+            Some(&not_frozen),
+            &[],
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn when_indices_align_on_the_common_prefix() -> Result<()> {
+        let json = json!({
+            "spec": {
+                "generators": [
+                    {
+                        "selector": { "containers": [{ "name": "web" }] },
+                        "template": { "spec": { "containers": [
+                            { "image": "a" }, { "image": "b" }, { "image": "c" }
+                        ] } }
+                    },
+                    {
+                        "selector": { "containers": [{ "name": "api" }] },
+                        "template": { "spec": { "containers": [
+                            { "image": "a" }, { "image": "b" }, { "image": "c" }
+                        ] } }
+                    }
+                ]
+            }
+        });
+        let when = [PathValue {
+            path: "/spec/generators/*/selector/containers/*/name".to_string(),
+            value: json!("web"),
+            ..Default::default()
+        }];
+
+        // The generator index is reused, but the selector's containers are a different
+        // array than the changed container, so their wildcard matches any element
+        assert!(JsonPathMatcher::when_conditions_met(
+            &json,
+            "/spec/generators/0/template/spec/containers/2/image",
+            &when
+        )?);
+        assert!(!JsonPathMatcher::when_conditions_met(
+            &json,
+            "/spec/generators/1/template/spec/containers/2/image",
+            &when
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn on_missing_decides_conditions_on_absent_paths() -> Result<()> {
+        let json = json!({ "spec": { "image": "nginx:1.21" } });
+        let condition = |on_missing| PathValue {
+            path: "/spec/selector/env".to_string(),
+            value: json!("development"),
+            on_missing,
+            ..Default::default()
+        };
+
+        let met = |on_missing| {
+            JsonPathMatcher::when_conditions_met(
+                &json,
+                "/spec/image",
+                &[
+                    condition(on_missing),
+                    PathValue {
+                        path: "/spec/image".to_string(),
+                        value: json!("nginx:1.21"),
+                        ..Default::default()
+                    },
+                ],
+            )
+        };
+
+        assert!(!met(OnMissing::Fail)?);
+        assert!(met(OnMissing::Pass)?);
+        assert!(met(OnMissing::Skip)?);
+
+        // A skipped condition does not make an otherwise failing set pass
+        let failing = [
+            condition(OnMissing::Skip),
+            PathValue {
+                path: "/spec/image".to_string(),
+                value: json!("nginx:1.20"),
+                ..Default::default()
+            },
+        ];
+        assert!(!JsonPathMatcher::when_conditions_met(
+            &json,
+            "/spec/image",
+            &failing
+        )?);
+
+        // onMissing decides the comparison, which negate then inverts
+        let negated = PathValue {
+            negate: true,
+            ..condition(OnMissing::Pass)
+        };
+        assert!(!JsonPathMatcher::matches_conditions(&json, &[negated])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn length_conditions_compare_element_counts() -> Result<()> {
+        let has_generators = vec![PathValue {
+            path: "/spec/generators".to_string(),
+            length: Some(LengthBounds {
+                ge: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+
+        assert!(JsonPathMatcher::matches_conditions(
+            &json!({ "spec": { "generators": [{ "list": {} }] } }),
+            &has_generators
+        )?);
+        assert!(!JsonPathMatcher::matches_conditions(
+            &json!({ "spec": { "generators": [] } }),
+            &has_generators
+        )?);
+        // Scalars have no length, which is an error rather than a silent mismatch
+        let err = JsonPathMatcher::matches_conditions(
+            &json!({ "spec": { "generators": "list" } }),
+            &has_generators,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("Cannot take the length"));
+
+        // In `when`, only allow changes while the object stays within bounds
+        let few_labels = vec![PathValue {
+            path: "/metadata/labels".to_string(),
+            length: Some(LengthBounds {
+                le: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+        let base_json = json!({ "metadata": { "labels": { "a": "1" } } });
+        let allowed_patterns = vec!["/metadata/labels/*".to_string()];
+
+        assert!(JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &json!({ "metadata": { "labels": { "a": "2" } } }),
+            &allowed_patterns,
+            Some(&few_labels),
+            &[],
+        )?);
+        assert!(!JsonPathMatcher::has_allowed_changes_only(
+            &base_json,
+            &json!({ "metadata": { "labels": { "a": "1", "b": "2" } } }),
+            &allowed_patterns,
+            Some(&few_labels),
+            &[],
+        )?);
+
+        Ok(())
+    }
 }